@@ -0,0 +1,518 @@
+pub mod tests;
+
+use std::{collections::HashMap, fmt};
+
+use super::{
+    bytecode::{Chunk, Instruction, LocalVariable, Location, OperandKind, OperationCode, Span},
+    object::{ArrayObject, FunctionObject, Object, StringObject},
+    value::Value,
+};
+
+// Textual assembly format, as emitted by debug::disassemble_function:
+//
+//   .function Global 0          ; name and arity
+//   .globals                    ; global names, by slot
+//       0: add
+//   .locals                     ; local variables: name, slot and [start, end) offsets
+//       x 0 4 20
+//   .constants                  ; numbers, booleans, void, strings, arrays or nested functions
+//       0: .function add 2
+//           .code
+//               1    GET_LOCAL 0
+//               ...
+//           .end
+//   .code
+//       1    CONSTANT 0         ; optional line number, mnemonic and operand
+//   L12:                        ; labels are used as jump operands
+//       2    JUMP L12
+//   .end
+//
+// Everything after a ';' is a comment.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Assembly error on line {}: {}", self.line, self.message)
+    }
+}
+
+pub fn assemble(source: &str) -> Result<FunctionObject, AssemblyError> {
+    let lines = source.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, strip_comment(line).trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect();
+
+    let mut assembler = Assembler {
+        lines,
+        position: 0,
+    };
+
+    let (line, header) = match assembler.next_line() {
+        Some(line) => line,
+        None => return Err(error(0, "Expected a .function block")),
+    };
+
+    let function = assembler.parse_function(line, header)?;
+
+    if let Some((line, _)) = assembler.next_line() {
+        return Err(error(line, "Unexpected content after the top-level function"));
+    }
+
+    return Ok(function)
+}
+
+enum Section {
+    None,
+    Globals,
+    Locals,
+    Constants,
+    Code,
+}
+
+enum Operand<'a> {
+    None,
+    Number(usize),
+    Label(&'a str),
+}
+
+struct PendingInstruction<'a> {
+    source_line: usize,
+    location: Location,
+    operation: OperationCode,
+    operand: Operand<'a>,
+}
+
+struct Assembler<'a> {
+    lines: Vec<(usize, &'a str)>,
+    position: usize,
+}
+
+impl<'a> Assembler<'a> {
+
+    fn next_line(&mut self) -> Option<(usize, &'a str)> {
+        let line = self.lines.get(self.position).copied();
+        self.position += 1;
+        return line
+    }
+
+    // Parses a function block, starting after its ".function name arity" header
+    fn parse_function(&mut self, header_line: usize, header: &'a str) -> Result<FunctionObject, AssemblyError> {
+        let parts: Vec<&str> = header.split_whitespace().collect();
+        if parts.len() != 3 || parts[0] != ".function" {
+            return Err(error(header_line, "Expected '.function <name> <arity>'"));
+        }
+
+        let arity = match parts[2].parse::<usize>() {
+            Ok(arity) => arity,
+            Err(_) => return Err(error(header_line, &format!("Invalid arity '{}'", parts[2]))),
+        };
+
+        let mut chunk = Chunk::new();
+        let mut section = Section::None;
+        let mut instructions = Vec::<PendingInstruction>::new();
+        let mut labels = HashMap::<&str, usize>::new();
+        let mut offset = 0;
+
+        loop {
+            let (line, text) = match self.next_line() {
+                Some(line) => line,
+                None => return Err(error(header_line, "Missing .end for function")),
+            };
+
+            match text {
+                ".globals" => section = Section::Globals,
+                ".locals" => section = Section::Locals,
+                ".constants" => section = Section::Constants,
+                ".code" => section = Section::Code,
+                ".end" => break,
+                _ if text.starts_with(".span") => chunk.span = parse_span(line, text)?,
+                _ => match section {
+                    Section::None => return Err(error(line, "Expected .globals, .locals, .constants or .code")),
+                    Section::Globals => {
+                        let (index, name) = split_index(line, text)?;
+                        if index != chunk.globals.len() {
+                            return Err(error(line, &format!("Expected global {}", chunk.globals.len())));
+                        }
+
+                        chunk.globals.push(name.to_string());
+                    },
+                    Section::Locals => chunk.locals.push(parse_local(line, text)?),
+                    Section::Constants => {
+                        let (index, value) = split_index(line, text)?;
+                        if index != chunk.contants.len() {
+                            return Err(error(line, &format!("Expected constant {}", chunk.contants.len())));
+                        }
+
+                        let constant = if value.starts_with(".function") {
+                            Value::Object(Object::Function(self.parse_function(line, value)?))
+                        } else {
+                            parse_value(line, value)?
+                        };
+
+                        chunk.contants.push(constant);
+                    },
+                    Section::Code => {
+                        if let Some(label) = text.strip_suffix(':') {
+                            if labels.insert(label, offset).is_some() {
+                                return Err(error(line, &format!("Duplicate label '{label}'")));
+                            }
+
+                            continue;
+                        }
+
+                        let previous_location = match instructions.last() {
+                            Some(instruction) => instruction.location,
+                            None => Location::default(),
+                        };
+
+                        let instruction = parse_instruction(line, text, previous_location)?;
+                        offset += instruction.operation.width();
+                        instructions.push(instruction);
+                    },
+                },
+            }
+        }
+
+        offset = 0;
+        for pending in instructions {
+            let instruction = resolve_instruction(&pending, offset, &labels)?;
+            chunk.write(instruction, pending.location);
+            offset += instruction.width();
+        }
+
+        return Ok(FunctionObject {
+            arity,
+            chunk,
+            name: parts[1].to_string(),
+        })
+    }
+
+}
+
+fn parse_instruction(source_line: usize, text: &str, previous_location: Location) -> Result<PendingInstruction<'_>, AssemblyError> {
+    let mut parts = text.split_whitespace().peekable();
+
+    // The location is optional, it defaults to the location of the previous instruction
+    let location = match parts.peek().and_then(|part| parse_location(part)) {
+        Some(location) => {
+            parts.next();
+            location
+        },
+        None => previous_location,
+    };
+
+    let mnemonic = match parts.next() {
+        Some(mnemonic) => mnemonic,
+        None => return Err(error(source_line, "Expected an instruction")),
+    };
+
+    let operation = match OperationCode::from_name(mnemonic) {
+        Some(operation) => operation,
+        None => return Err(error(source_line, &format!("Unknown instruction '{mnemonic}'"))),
+    };
+
+    let operand = match (operation.operand(), parts.next()) {
+        (None, None) => Operand::None,
+        (None, Some(_)) => {
+            return Err(error(source_line, &format!("{mnemonic} takes no operand")))
+        },
+        (Some(_), None) => {
+            return Err(error(source_line, &format!("{mnemonic} expects an operand")))
+        },
+        (Some(OperandKind::Jump | OperandKind::Loop), Some(label)) => Operand::Label(label),
+        (Some(_), Some(value)) => match value.parse::<usize>() {
+            Ok(value) => Operand::Number(value),
+            Err(_) => return Err(error(source_line, &format!("Invalid operand '{value}'"))),
+        },
+    };
+
+    if parts.next().is_some() {
+        return Err(error(source_line, "Unexpected tokens after the operand"));
+    }
+
+    return Ok(PendingInstruction {
+        source_line,
+        location,
+        operation,
+        operand,
+    })
+}
+
+fn resolve_instruction(
+    pending: &PendingInstruction,
+    offset: usize,
+    labels: &HashMap<&str, usize>
+) -> Result<Instruction, AssemblyError> {
+    let operand = match pending.operand {
+        Operand::None => 0,
+        Operand::Number(value) => value,
+        Operand::Label(label) => {
+            let target = match labels.get(label) {
+                Some(target) => *target,
+                None => return Err(error(pending.source_line, &format!("Unknown label '{label}'"))),
+            };
+
+            let next = offset + pending.operation.width();
+            match pending.operation.operand() {
+                Some(OperandKind::Loop) if target <= next => next - target,
+                Some(OperandKind::Jump) if target >= next => target - next,
+                Some(OperandKind::Loop) => {
+                    return Err(error(pending.source_line, &format!("{} must jump backward", pending.operation.name())))
+                },
+                _ => {
+                    return Err(error(pending.source_line, &format!("{} must jump forward", pending.operation.name())))
+                },
+            }
+        },
+    };
+
+    let maximum = match pending.operation.operand() {
+        Some(kind) if kind.width() == 2 => u16::MAX as usize,
+        _ => u8::MAX as usize,
+    };
+
+    if operand > maximum {
+        return Err(error(pending.source_line, &format!("Operand {operand} is out of range")));
+    }
+
+    let mut code = vec![pending.operation as u8];
+    match pending.operation.operand() {
+        Some(kind) if kind.width() == 2 => {
+            code.push((operand >> 8) as u8);
+            code.push(operand as u8);
+        },
+        Some(_) => code.push(operand as u8),
+        None => {},
+    }
+
+    match Instruction::decode(&code, 0) {
+        Ok(instruction) => Ok(instruction),
+        Err(decode_error) => Err(error(pending.source_line, &decode_error.to_string())),
+    }
+}
+
+// Parses "name slot start end"
+fn parse_local(line: usize, text: &str) -> Result<LocalVariable, AssemblyError> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    if parts.len() != 4 {
+        return Err(error(line, "Expected '<name> <slot> <start> <end>'"));
+    }
+
+    let mut numbers = [0; 3];
+    for (number, part) in numbers.iter_mut().zip(&parts[1..]) {
+        *number = match part.parse::<usize>() {
+            Ok(value) => value,
+            Err(_) => return Err(error(line, &format!("Invalid number '{part}'"))),
+        };
+    }
+
+    return Ok(LocalVariable {
+        name: parts[0].to_string(),
+        slot: numbers[0],
+        start: numbers[1],
+        end: numbers[2],
+    })
+}
+
+// Parses ".span <line:column> <line:column>"
+fn parse_span(line: usize, text: &str) -> Result<Span, AssemblyError> {
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    let locations: Vec<Location> = parts.iter().skip(1).filter_map(|part| parse_location(part)).collect();
+
+    match locations.as_slice() {
+        [start, end] if parts.len() == 3 => Ok(Span { start: *start, end: *end }),
+        _ => Err(error(line, "Expected '.span <line:column> <line:column>'")),
+    }
+}
+
+// Parses "line" or "line:column", a missing column is 0
+fn parse_location(text: &str) -> Option<Location> {
+    let (line, column) = match text.split_once(':') {
+        Some((line, column)) => (line, column.parse::<usize>().ok()?),
+        None => (text, 0),
+    };
+
+    return Some(Location::new(line.parse::<usize>().ok()?, column))
+}
+
+// Splits "index: content"
+fn split_index(line: usize, text: &str) -> Result<(usize, &str), AssemblyError> {
+    let (index, content) = match text.split_once(':') {
+        Some(parts) => parts,
+        None => return Err(error(line, "Expected '<index>: <value>'")),
+    };
+
+    match index.trim().parse::<usize>() {
+        Ok(index) => Ok((index, content.trim())),
+        Err(_) => Err(error(line, &format!("Invalid index '{}'", index.trim()))),
+    }
+}
+
+fn parse_value(line: usize, text: &str) -> Result<Value, AssemblyError> {
+    let characters: Vec<char> = text.chars().collect();
+    let mut parser = ValueParser {
+        characters,
+        position: 0,
+        line,
+    };
+
+    let value = parser.parse_value()?;
+
+    parser.skip_whitespace();
+    if parser.position < parser.characters.len() {
+        return Err(error(line, &format!("Unexpected content in constant '{text}'")));
+    }
+
+    return Ok(value)
+}
+
+struct ValueParser {
+    characters: Vec<char>,
+    position: usize,
+    line: usize,
+}
+
+impl ValueParser {
+
+    fn peek(&self) -> Option<char> {
+        self.characters.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(character) if character.is_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, AssemblyError> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('"') => self.parse_string(),
+            Some('[') => self.parse_array(),
+            Some(_) => self.parse_word(),
+            None => Err(error(self.line, "Expected a value")),
+        }
+    }
+
+    fn parse_word(&mut self) -> Result<Value, AssemblyError> {
+        let start = self.position;
+        while matches!(self.peek(), Some(character) if !character.is_whitespace() && character != ',' && character != ']') {
+            self.position += 1;
+        }
+
+        let word: String = self.characters[start..self.position].iter().collect();
+
+        match word.as_str() {
+            "true" => Ok(Value::Boolean(true)),
+            "false" => Ok(Value::Boolean(false)),
+            "void" => Ok(Value::Void),
+            _ => match word.parse::<f64>() {
+                Ok(number) => Ok(Value::F64(number)),
+                Err(_) => Err(error(self.line, &format!("Invalid constant '{word}'"))),
+            },
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<Value, AssemblyError> {
+        // Skipping the opening quote
+        self.position += 1;
+
+        let mut value = String::new();
+        loop {
+            let character = match self.peek() {
+                Some(character) => character,
+                None => return Err(error(self.line, "Unterminated string")),
+            };
+            self.position += 1;
+
+            match character {
+                '"' => break,
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        _ => return Err(error(self.line, "Invalid escape sequence")),
+                    };
+
+                    self.position += 1;
+                    value.push(escaped);
+                },
+                character => value.push(character),
+            }
+        }
+
+        return Ok(Value::Object(Object::String(StringObject {
+            length: value.len(),
+            value,
+        })))
+    }
+
+    fn parse_array(&mut self) -> Result<Value, AssemblyError> {
+        // Skipping the opening bracket
+        self.position += 1;
+
+        let mut elements = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Value::Object(Object::Array(ArrayObject { elements })));
+        }
+
+        loop {
+            elements.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => {
+                    self.position += 1;
+                    break;
+                },
+                _ => return Err(error(self.line, "Expected ',' or ']' in array")),
+            }
+        }
+
+        return Ok(Value::Object(Object::Array(ArrayObject { elements })))
+    }
+
+}
+
+// Removes the comment of a line, ignoring ';' inside strings
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (index, character) in line.char_indices() {
+        match character {
+            '\\' if in_string => {
+                escaped = !escaped;
+                continue;
+            },
+            '"' if !escaped => in_string = !in_string,
+            ';' if !in_string => return &line[..index],
+            _ => {},
+        }
+
+        escaped = false;
+    }
+
+    return line
+}
+
+fn error(line: usize, message: &str) -> AssemblyError {
+    AssemblyError {
+        line,
+        message: message.to_string(),
+    }
+}
@@ -0,0 +1,175 @@
+#[cfg(test)]
+mod tests {
+    use crate::backend::{
+        assembler::assemble,
+        bytecode::Chunk,
+        compiler::Compiler,
+        debug::disassemble_function,
+        object::{FunctionObject, Object},
+        serialize::save,
+        value::Value,
+        vm::{InterpretationResult, VM},
+    };
+
+    use crate::frontend::{
+        lexer::Lexer,
+        parser::{parse_file, Parser},
+        typecheck::check_program,
+    };
+
+    fn compile(source: &str) -> FunctionObject {
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(&mut lexer);
+
+        let ast = parse_file(&mut parser);
+        check_program(&ast).unwrap();
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        let mut compiler = Compiler::new(function);
+        compiler.compile(&ast).clone()
+    }
+
+    fn assemble_or_panic(source: &str) -> FunctionObject {
+        match assemble(source) {
+            Ok(function) => function,
+            Err(error) => panic!("{error}\n{source}"),
+        }
+    }
+
+    const PROGRAM: &str = "
+        fn count(limit: int) -> int {
+            let index = 0;
+            while index < limit {
+                index = index + 1;
+            };
+            return index;
+        }
+
+        {
+            let name = \"si;lk\";
+            let values = [15, -2, 3];
+            if values[0] > 1 {
+                count(10);
+            } else {
+                count(2);
+            };
+        }
+    ";
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        let function = compile(PROGRAM);
+        let text = disassemble_function(&function);
+
+        let assembled = assemble_or_panic(&text);
+
+        // Same bytes, lines, constants and globals, including nested functions
+        assert_eq!(save(&assembled).unwrap(), save(&function).unwrap());
+        assert_eq!(disassemble_function(&assembled), text);
+
+        let nested = assembled.chunk.contants.iter().find_map(|constant| match constant {
+            Value::Object(Object::Function(function)) => Some(function),
+            _ => None,
+        });
+        match nested {
+            Some(nested) => {
+                assert_eq!(nested.name, "count");
+                assert_eq!(nested.arity, 1);
+            },
+            None => panic!("Expected nested function constant"),
+        }
+    }
+
+    #[test]
+    fn test_assemble_handwritten_program() {
+        let mut function = assemble_or_panic("
+            ; Counts down from 3, returning void
+            .function Global 0
+            .constants
+                0: 3
+                1: 1
+                2: 0
+            .code
+                1 CONSTANT 0
+                  SET_LOCAL 0
+                  POP
+            loop:
+                2 GET_LOCAL 0
+                  CONSTANT 2
+                  GREATER
+                  JUMP_IF_FALSE exit
+                  POP
+                3 GET_LOCAL 0
+                  CONSTANT 1
+                  SUBSTRACT
+                  SET_LOCAL 0
+                  POP
+                  LOOP loop
+            exit:
+                4 POP
+                  VOID
+                  RETURN
+            .end
+        ");
+
+        assert_eq!(function.chunk.line(0), 1);
+        assert_eq!(function.chunk.line(function.chunk.code.len() - 1), 4);
+
+        let mut vm = VM::new(&mut function);
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+    }
+
+    #[test]
+    fn test_assemble_constants() {
+        let function = assemble_or_panic("
+            .function Global 0
+            .constants
+                0: \"a;b \\\"c\\\"\"   ; comment after a string
+                1: [1, [true, void], \"x\"]
+                2: -0.5
+            .code
+                1 VOID
+                  RETURN
+            .end
+        ");
+
+        let constants = &function.chunk.contants;
+        match &constants[0] {
+            Value::Object(Object::String(string)) => assert_eq!(string.value, "a;b \"c\""),
+            constant => panic!("Unexpected constant {:?}", constant),
+        }
+        match &constants[1] {
+            Value::Object(Object::Array(array)) => assert_eq!(array.elements.len(), 3),
+            constant => panic!("Unexpected constant {:?}", constant),
+        }
+        assert_eq!(constants[2], Value::F64(-0.5));
+    }
+
+    #[test]
+    fn test_assembly_errors() {
+        let cases = [
+            (".function Global 0\n.code\n1 PUSH\n.end", 3, "Unknown instruction 'PUSH'"),
+            (".function Global 0\n.code\n1 JUMP nowhere\n.end", 3, "Unknown label 'nowhere'"),
+            (".function Global 0\n.code\n1 CONSTANT\n.end", 3, "CONSTANT expects an operand"),
+            (".function Global 0\n.code\n1 POP 2\n.end", 3, "POP takes no operand"),
+            (".function Global 0\n.code\n1 CONSTANT 300\n.end", 3, "Operand 300 is out of range"),
+            (".function Global 0\n.constants\n1: true\n.end", 3, "Expected constant 0"),
+            (".function Global 0\n.code\n1 VOID", 1, "Missing .end for function"),
+        ];
+
+        for (source, line, message) in cases {
+            match assemble(source) {
+                Ok(_) => panic!("Expected an error for:\n{source}"),
+                Err(error) => {
+                    assert_eq!(error.line, line, "{source}");
+                    assert_eq!(error.message, message, "{source}");
+                },
+            }
+        }
+    }
+}
@@ -1,77 +1,366 @@
+pub mod tests;
+
+use std::fmt;
+
 use super::value::Value;
 
-#[derive(Debug, Clone, Copy)]
-#[allow(non_camel_case_types)]
-pub enum OperationCode {
-    UNKNOW,
-    CONSTANT,
-    TRUE,
-    FALSE,
-    ADD,
-    SUBSTRACT,
-    MULTIPLY,
-    DIVIDE,
-    EQUALS,
-    NOT_EQUALS,
-    GREATER,
-    LESS,
-    NOT,
-    NEGATE,
-    SET_GLOBAL,
-    GET_GLOBAL,
-    SET_LOCAL,
-    GET_LOCAL,
-    JUMP,
-    JUMP_IF_FALSE,
-    LOOP,
-    CALL,
-    BUILD_ARRAY,
-    INDEX_ARRAY,
-    RETURN,
-    POP,
-}
-
-impl OperationCode {
-    pub fn from_u8(value: u8) -> Self {
-        match value {
-            1 => OperationCode::CONSTANT,
-            2 => OperationCode::TRUE,
-            3 => OperationCode::FALSE,
-            4 => OperationCode::ADD,
-            5 => OperationCode::SUBSTRACT,
-            6 => OperationCode::MULTIPLY,
-            7 => OperationCode::DIVIDE,
-            8 => OperationCode::EQUALS,
-            9 => OperationCode::NOT_EQUALS,
-            10 => OperationCode::GREATER,
-            11 => OperationCode::LESS,
-            12 => OperationCode::NOT,
-            13 => OperationCode::NEGATE,
-            14 => OperationCode::SET_GLOBAL,
-            15 => OperationCode::GET_GLOBAL,
-            16 => OperationCode::SET_LOCAL,
-            17 => OperationCode::GET_LOCAL,
-            18 => OperationCode::JUMP,
-            19 => OperationCode::JUMP_IF_FALSE,
-            20 => OperationCode::LOOP,
-            21 => OperationCode::CALL,
-            22 => OperationCode::BUILD_ARRAY,
-            23 => OperationCode::INDEX_ARRAY,
-            24 => OperationCode::RETURN,
-            25 => OperationCode::POP,
-            unknown => {
-                println!("Unknown instruction '{}'", unknown);
-                OperationCode::UNKNOW
+// Kind of the operand following an operation code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    Constant,   // u8 index in the constant pool
+    Local,      // u8 local slot
+    Global,     // u8 global slot
+    Count,      // u8 count of stack values (arguments, elements) or of struct fields
+    Field,      // u8 field slot of a struct
+    Jump,       // u16 forward offset
+    Loop,       // u16 backward offset
+}
+
+impl OperandKind {
+    pub fn width(&self) -> usize {
+        match self {
+            OperandKind::Constant
+            | OperandKind::Local
+            | OperandKind::Global
+            | OperandKind::Count
+            | OperandKind::Field => 1,
+            OperandKind::Jump | OperandKind::Loop => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    UnknownOperation { offset: usize, byte: u8 },
+    TruncatedOperand { offset: usize, operation: OperationCode },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownOperation { offset, byte } => {
+                write!(f, "Unknown operation code {} at offset {}", byte, offset)
             },
+            DecodeError::TruncatedOperand { offset, operation } => {
+                write!(f, "Missing operand for {} at offset {}", operation.name(), offset)
+            },
+        }
+    }
+}
+
+macro_rules! operand_type {
+    (Constant) => { u8 };
+    (Local) => { u8 };
+    (Global) => { u8 };
+    (Count) => { u8 };
+    (Field) => { u8 };
+    (Jump) => { u16 };
+    (Loop) => { u16 };
+}
+
+// Single source of truth for the instruction set.
+// Each line reads: code => OPERATION_CODE, Instruction { operand: OperandKind } (popped => pushed)
+macro_rules! instructions {
+    (@kind) => { None };
+    (@kind $kind:ident) => { Some(OperandKind::$kind) };
+
+    (@operand) => { None };
+    (@operand $kind:ident $field:ident) => { Some((OperandKind::$kind, *$field as usize)) };
+
+    (@read Jump, $code:ident, $offset:ident) => { read_short($code, $offset + 1) };
+    (@read Loop, $code:ident, $offset:ident) => { read_short($code, $offset + 1) };
+    (@read $kind:ident, $code:ident, $offset:ident) => { $code[$offset + 1] };
+
+    ($( $code:literal => $operation:ident, $variant:ident $({ $field:ident : $kind:ident })? ($popped:expr => $pushed:expr); )*) => {
+
+        #[repr(u8)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[allow(non_camel_case_types)]
+        pub enum OperationCode {
+            $( $operation = $code, )*
+        }
+
+        impl OperationCode {
+            pub const ALL: &'static [OperationCode] = &[ $( OperationCode::$operation, )* ];
+
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $( OperationCode::$operation => stringify!($operation), )*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $( stringify!($operation) => Some(OperationCode::$operation), )*
+                    _ => None,
+                }
+            }
+
+            pub fn operand(&self) -> Option<OperandKind> {
+                match self {
+                    $( OperationCode::$operation => instructions!(@kind $($kind)?), )*
+                }
+            }
+
+            // Size of the operation code and its operand, in bytes
+            pub fn width(&self) -> usize {
+                match self.operand() {
+                    Some(kind) => 1 + kind.width(),
+                    None => 1,
+                }
+            }
+        }
+
+        impl TryFrom<u8> for OperationCode {
+            type Error = u8;
+
+            fn try_from(value: u8) -> Result<Self, Self::Error> {
+                match value {
+                    $( $code => Ok(OperationCode::$operation), )*
+                    unknown => Err(unknown),
+                }
+            }
+        }
+
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Instruction {
+            $( $variant $({ $field: operand_type!($kind) })?, )*
+        }
+
+        impl Instruction {
+            pub fn operation(&self) -> OperationCode {
+                match self {
+                    $( Instruction::$variant $({ $field: _ })? => OperationCode::$operation, )*
+                }
+            }
+
+            // The operand widened to usize, along with its kind
+            pub fn operand(&self) -> Option<(OperandKind, usize)> {
+                match self {
+                    $( Instruction::$variant $({ $field })? => instructions!(@operand $($kind $field)?), )*
+                }
+            }
+
+            pub fn width(&self) -> usize {
+                self.operation().width()
+            }
+
+            // Number of values popped from and pushed to the stack
+            #[allow(unused_variables)]
+            pub fn stack_effect(&self) -> (usize, usize) {
+                match *self {
+                    $( Instruction::$variant $({ $field })? => ($popped, $pushed), )*
+                }
+            }
+
+            pub fn encode(&self, code: &mut Vec<u8>) {
+                code.push(self.operation() as u8);
+
+                match self.operand() {
+                    Some((kind, value)) => match kind.width() {
+                        1 => code.push(value as u8),
+                        _ => {
+                            code.push((value >> 8) as u8);
+                            code.push(value as u8);
+                        },
+                    },
+                    None => {},
+                }
+            }
+
+            pub fn decode(code: &[u8], offset: usize) -> Result<Self, DecodeError> {
+                let byte = code[offset];
+                let operation = match OperationCode::try_from(byte) {
+                    Ok(operation) => operation,
+                    Err(byte) => return Err(DecodeError::UnknownOperation { offset, byte }),
+                };
+
+                if offset + operation.width() > code.len() {
+                    return Err(DecodeError::TruncatedOperand { offset, operation });
+                }
+
+                let instruction = match operation {
+                    $( OperationCode::$operation => Instruction::$variant $({
+                        $field: instructions!(@read $kind, code, offset)
+                    })?, )*
+                };
+
+                return Ok(instruction)
+            }
+        }
+    };
+}
+
+instructions! {
+    1 => CONSTANT, Constant { index: Constant } (0 => 1);
+    2 => TRUE, True (0 => 1);
+    3 => FALSE, False (0 => 1);
+    4 => ADD, Add (2 => 1);
+    5 => SUBSTRACT, Substract (2 => 1);
+    6 => MULTIPLY, Multiply (2 => 1);
+    7 => DIVIDE, Divide (2 => 1);
+    8 => EQUALS, Equals (2 => 1);
+    9 => NOT_EQUALS, NotEquals (2 => 1);
+    10 => GREATER, Greater (2 => 1);
+    11 => LESS, Less (2 => 1);
+    12 => NOT, Not (1 => 1);
+    13 => NEGATE, Negate (1 => 1);
+    14 => SET_GLOBAL, SetGlobal { slot: Global } (1 => 1);
+    15 => GET_GLOBAL, GetGlobal { slot: Global } (0 => 1);
+    16 => SET_LOCAL, SetLocal { slot: Local } (1 => 1);
+    17 => GET_LOCAL, GetLocal { slot: Local } (0 => 1);
+    18 => JUMP, Jump { offset: Jump } (0 => 0);
+    19 => JUMP_IF_FALSE, JumpIfFalse { offset: Jump } (1 => 1);
+    20 => LOOP, Loop { offset: Loop } (0 => 0);
+    21 => CALL, Call { arguments: Count } (arguments as usize + 1 => 1);
+    22 => BUILD_ARRAY, BuildArray { length: Count } (length as usize => 1);
+    23 => INDEX_ARRAY, IndexArray (2 => 1);
+    24 => RETURN, Return (1 => 0);
+    25 => POP, Pop (1 => 0);
+    26 => VOID, Void (0 => 1);
+    27 => BUILD_STRUCT, BuildStruct { fields: Count } (1 => 1);
+    28 => GET_FIELD, GetField { slot: Field } (1 => 1);
+    29 => SET_FIELD, SetField { slot: Field } (2 => 1);
+}
+
+impl Instruction {
+    // Offset the instruction jumps to, relative to the start of the chunk.
+    // It may be out of the chunk bounds if the bytecode is malformed.
+    pub fn jump_target(&self, offset: usize) -> Option<isize> {
+        let next = (offset + self.width()) as isize;
+
+        match self.operand() {
+            Some((OperandKind::Jump, jump)) => Some(next + jump as isize),
+            Some((OperandKind::Loop, jump)) => Some(next - jump as isize),
+            _ => None,
+        }
+    }
+
+    // Fuel consumed by executing the instruction, calls and allocations cost more
+    pub fn cost(&self) -> u64 {
+        match *self {
+            Instruction::Call { arguments } => 10 + arguments as u64,
+            Instruction::BuildArray { length } => 5 + length as u64,
+            Instruction::IndexArray => 2,
+            Instruction::BuildStruct { fields } => 5 + fields as u64,
+            _ => 1,
+        }
+    }
+}
+
+fn read_short(code: &[u8], offset: usize) -> u16 {
+    ((code[offset] as u16) << 8) | code[offset + 1] as u16
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self {
+            line,
+            column,
         }
     }
 }
 
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// Source range of a function, both ends inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+// Bytes from `offset` up to the next run come from `location`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocationRun {
+    pub offset: usize,
+    pub location: Location,
+}
+
+// Run-length encoded source locations of a chunk's bytecode
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LocationTable {
+    runs: Vec<LocationRun>,
+}
+
+impl LocationTable {
+    pub fn new() -> Self {
+        Self {
+            runs: vec![],
+        }
+    }
+
+    // Bytes from `offset` onwards come from `location`, offsets must be pushed in order
+    pub fn push(&mut self, offset: usize, location: Location) {
+        match self.runs.last() {
+            Some(run) if run.location == location => {},
+            Some(run) if run.offset == offset => {
+                self.runs.pop();
+                self.push(offset, location);
+            },
+            _ => self.runs.push(LocationRun { offset, location }),
+        }
+    }
+
+    pub fn get(&self, offset: usize) -> Option<Location> {
+        let index = match self.runs.binary_search_by_key(&offset, |run| run.offset) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+
+        return Some(self.runs[index].location)
+    }
+
+    pub fn last(&self) -> Option<Location> {
+        self.runs.last().map(|run| run.location)
+    }
+
+    pub fn runs(&self) -> &[LocationRun] {
+        &self.runs
+    }
+}
+
+// A local variable, holding a value in `slot` for offsets in [start, end)
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalVariable {
+    pub name: String,
+    pub slot: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct Chunk {
     pub code: Vec<u8>,
     pub contants: Vec<Value>,
-    pub lines: Vec<usize>,
+
+    // Debug info, mapping bytecode back to the source
+    pub locations: LocationTable,
+    pub span: Span,
+
+    // Names of the global slots, indexed by slot.
+    // Only the top-level chunk carries them, they are used for debugging.
+    pub globals: Vec<String>,
+
+    // Local variables, in the order their scope ends
+    pub locals: Vec<LocalVariable>,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chunk {
@@ -79,7 +368,10 @@ impl Chunk {
         Self {
             code: vec![],
             contants: vec![],
-            lines: vec![],
+            locations: LocationTable::new(),
+            span: Span::default(),
+            globals: vec![],
+            locals: vec![],
         }
     }
 
@@ -88,52 +380,107 @@ impl Chunk {
         return (self.contants.len() - 1) as u8;
     }
 
-    pub fn add_constant(&mut self, value: Value, line: usize) {
-        self.add_operation(OperationCode::CONSTANT, line);
-        let constant = self.push_constant(value);
-        self.add_instruction(constant, line);
+    pub fn add_constant(&mut self, value: Value, location: Location) {
+        let index = self.push_constant(value);
+        self.write(Instruction::Constant { index }, location);
+    }
+
+    pub fn write(&mut self, instruction: Instruction, location: Location) {
+        self.locations.push(self.code.len(), location);
+        instruction.encode(&mut self.code);
+    }
+
+    // Source location of the byte at the offset
+    pub fn location(&self, offset: usize) -> Option<Location> {
+        if offset >= self.code.len() {
+            return None
+        }
+
+        self.locations.get(offset)
+    }
+
+    pub fn line(&self, offset: usize) -> usize {
+        match self.location(offset) {
+            Some(location) => location.line,
+            None => 0,
+        }
+    }
+
+    // Local variables holding a value at the offset
+    pub fn live_locals(&self, offset: usize) -> impl Iterator<Item = &LocalVariable> {
+        self.locals.iter().filter(move |local| local.start <= offset && offset < local.end)
     }
 
-    pub fn add_instruction(&mut self, instruction: u8, line: usize) {
-        self.code.push(instruction as u8);
-        self.lines.push(line);
+    pub fn decode(&self, offset: usize) -> Result<Instruction, DecodeError> {
+        Instruction::decode(&self.code, offset)
     }
 
-    pub fn add_operation(&mut self, operation: OperationCode, line: usize) {
-        self.code.push(operation as u8);
-        self.lines.push(line);
+    // Decodes every instruction along with its offset, stopping at the first invalid one
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions {
+            chunk: self,
+            offset: 0,
+        }
     }
 
-    pub fn add_jump(&mut self, operation: OperationCode, line: usize) -> usize {
-        self.add_operation(operation, line);
-        self.add_instruction(u8::MAX, line);
-        self.add_instruction(u8::MAX, line);
+    pub fn add_jump(&mut self, operation: OperationCode, location: Location) -> usize {
+        let instruction = match operation {
+            OperationCode::JUMP => Instruction::Jump { offset: u16::MAX },
+            OperationCode::JUMP_IF_FALSE => Instruction::JumpIfFalse { offset: u16::MAX },
+            operation => panic!("{} is not a forward jump", operation.name()),
+        };
+
+        self.write(instruction, location);
         return self.code.len() - 2
     }
 
     pub fn patch_jump(&mut self, offset: usize) {
         // -2 is for the 2 u8 placeholders
-        println!("{}-{}", self.code.len(), offset);
         let jump = self.code.len() - offset - 2;
 
         if jump > u16::MAX as usize {
             panic!("Cannot jump over that much code");
         }
 
-        self.code[offset] = ((jump >> 8) as u8) & u8::MAX;
-        self.code[offset + 1] = jump as u8 & u8::MAX;
+        self.code[offset] = (jump >> 8) as u8;
+        self.code[offset + 1] = jump as u8;
     }
 
-    pub fn add_loop(&mut self, loop_start: usize, line: usize) {
-        self.add_operation(OperationCode::LOOP, line);
-
-        let offset = self.code.len() - loop_start + 2;
+    pub fn add_loop(&mut self, loop_start: usize, location: Location) {
+        // +3 is for the LOOP instruction itself
+        let offset = self.code.len() - loop_start + 3;
         if offset > u16::MAX as usize {
             panic!("Loop body is too large");
         }
 
-        self.add_instruction((offset >> 8) as u8, line);
-        self.add_instruction(offset as u8, line);
+        self.write(Instruction::Loop { offset: offset as u16 }, location);
     }
 
-}
\ No newline at end of file
+}
+
+pub struct Instructions<'a> {
+    chunk: &'a Chunk,
+    offset: usize,
+}
+
+impl Iterator for Instructions<'_> {
+    type Item = Result<(usize, Instruction), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.chunk.code.len() {
+            return None
+        }
+
+        let offset = self.offset;
+        match self.chunk.decode(offset) {
+            Ok(instruction) => {
+                self.offset += instruction.width();
+                Some(Ok((offset, instruction)))
+            },
+            Err(error) => {
+                self.offset = self.chunk.code.len();
+                Some(Err(error))
+            },
+        }
+    }
+}
@@ -0,0 +1,110 @@
+#[cfg(test)]
+mod tests {
+    use crate::backend::bytecode::{
+        Chunk,
+        DecodeError,
+        Instruction,
+        Location,
+        LocationTable,
+        OperandKind,
+        OperationCode,
+    };
+
+    #[test]
+    fn test_operation_code_round_trip() {
+        for operation in OperationCode::ALL {
+            let byte = *operation as u8;
+            assert_eq!(OperationCode::try_from(byte), Ok(*operation));
+            assert_eq!(OperationCode::from_name(operation.name()), Some(*operation));
+        }
+
+        assert_eq!(OperationCode::try_from(0), Err(0));
+        assert_eq!(OperationCode::try_from(u8::MAX), Err(u8::MAX));
+    }
+
+    #[test]
+    fn test_instruction_encoding() {
+        let instructions = [
+            Instruction::Constant { index: 3 },
+            Instruction::Add,
+            Instruction::GetGlobal { slot: 1 },
+            Instruction::JumpIfFalse { offset: 300 },
+            Instruction::Loop { offset: 12 },
+            Instruction::Call { arguments: 2 },
+            Instruction::Return,
+        ];
+
+        let mut chunk = Chunk::new();
+        for instruction in instructions {
+            chunk.write(instruction, Location::new(1, 1));
+        }
+
+        // Every instruction shares the same location, stored as a single run
+        assert_eq!(chunk.locations.runs().len(), 1);
+        assert_eq!(chunk.location(chunk.code.len() - 1), Some(Location::new(1, 1)));
+        assert_eq!(chunk.location(chunk.code.len()), None);
+
+        let decoded: Vec<Instruction> = chunk.instructions()
+            .map(|result| result.expect("Valid instruction").1)
+            .collect();
+
+        assert_eq!(decoded, instructions);
+    }
+
+    #[test]
+    fn test_instruction_operands() {
+        let instruction = Instruction::Jump { offset: 258 };
+
+        assert_eq!(instruction.operation(), OperationCode::JUMP);
+        assert_eq!(instruction.operand(), Some((OperandKind::Jump, 258)));
+        assert_eq!(instruction.width(), 3);
+
+        assert_eq!(Instruction::Pop.operand(), None);
+        assert_eq!(Instruction::Pop.width(), 1);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut chunk = Chunk::new();
+        chunk.code = vec![OperationCode::TRUE as u8, 0];
+
+        assert_eq!(
+            chunk.decode(1),
+            Err(DecodeError::UnknownOperation { offset: 1, byte: 0 })
+        );
+
+        chunk.code = vec![OperationCode::JUMP as u8, 0];
+        assert_eq!(
+            chunk.decode(0),
+            Err(DecodeError::TruncatedOperand { offset: 0, operation: OperationCode::JUMP })
+        );
+    }
+
+    #[test]
+    fn test_location_table() {
+        let mut table = LocationTable::new();
+        table.push(0, Location::new(1, 5));
+        table.push(2, Location::new(1, 5));
+        table.push(3, Location::new(2, 1));
+        table.push(5, Location::new(2, 9));
+
+        // Consecutive instructions from the same location share a run
+        assert_eq!(table.runs().len(), 3);
+
+        assert_eq!(table.get(0), Some(Location::new(1, 5)));
+        assert_eq!(table.get(2), Some(Location::new(1, 5)));
+        assert_eq!(table.get(3), Some(Location::new(2, 1)));
+        assert_eq!(table.get(4), Some(Location::new(2, 1)));
+        assert_eq!(table.get(9), Some(Location::new(2, 9)));
+        assert_eq!(table.last(), Some(Location::new(2, 9)));
+
+        // An empty run is replaced
+        table.push(6, Location::new(3, 1));
+        table.push(6, Location::new(3, 2));
+        assert_eq!(table.runs().len(), 4);
+        assert_eq!(table.get(6), Some(Location::new(3, 2)));
+
+        assert_eq!(LocationTable::new().get(0), None);
+    }
+
+}
@@ -1,14 +1,16 @@
 use std::array;
 
 use crate::backend::{
-    bytecode::{Chunk, OperationCode},
-    object::{FunctionObject, Object, StringObject},
+    bytecode::{Chunk, Instruction, LocalVariable, Location, OperationCode, Span},
+    interner::Interner,
+    object::{ArrayObject, FunctionObject, Object, StringObject},
     value::Value,
 };
 
-use crate::frontend::ast;
+use crate::frontend::{ast, typecheck::model::{BindingKind, SemanticModel}};
 
 const LOCALS_SIZE: usize = 256;
+const GLOBALS_SIZE: usize = 256;
 
 pub struct Compiler<'a> {
     // Top-level code is implicitly a function
@@ -17,6 +19,15 @@ pub struct Compiler<'a> {
     pub locals: [Option<Local>; LOCALS_SIZE],
     pub locals_count: usize,
     pub depth: usize,
+
+    // Global identifiers, interned to their slot
+    pub globals: Interner,
+
+    // What the typechecker learned about the tree, field slots and where variables live come from it
+    pub model: Option<&'a SemanticModel>,
+
+    // Code the compiler doesn't support, the chunk can't be run when there are any
+    pub errors: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -24,6 +35,13 @@ pub struct Local {
     pub name: String,
     pub depth: usize,
     pub is_initialized: bool,
+    pub start: usize, // Offset from which the variable holds a value
+}
+
+// Where the value of a variable lives
+enum Variable {
+    Local(usize),
+    Global(u8),
 }
 
 pub struct GlobalFunction {
@@ -40,6 +58,9 @@ impl<'a> Compiler<'a> {
             locals: array::from_fn(|_| None),
             locals_count: 0,
             depth: 0,
+            globals: Interner::new(),
+            model: None,
+            errors: vec![],
         }
     }
 
@@ -48,43 +69,83 @@ impl<'a> Compiler<'a> {
     }
 
     pub fn compile(&mut self, ast: &ast::File) -> &mut FunctionObject {
-        self.compile_file(&ast);
-        return &mut self.function
+        self.compile_file(ast);
+        self.function.chunk.globals = self.globals.strings().to_vec();
+        return self.function
     }
 
     fn compile_file(&mut self, file: &ast::File) {
-        for statement in &file.statements {
-            self.compile_statement(statement);
-        }
+        self.compile_statements(&file.statements, location(&file.node));
+
+        // Top-level code returns the value of its last expression
+        let last_location = self.get_last_location(location(&file.node));
+        self.function.chunk.write(Instruction::Return, last_location);
+
+        self.function.chunk.span = Span {
+            start: location(&file.node),
+            end: last_location,
+        };
     }
 
-    fn compile_statement(&mut self, statement: &ast::Statement) {
-        match statement {
-            ast::Statement::Let(let_statement) => self.compile_let_statement(let_statement),
-            ast::Statement::Expression(expression_statement) => {
-                self.compile_expression(&expression_statement.expression);
-            },
+    // Statements leave a single value on the stack: 
+    // the value of the last expression statement, or void.
+    fn compile_statements(&mut self, statements: &[ast::Statement], empty_location: Location) {
+        for (index, statement) in statements.iter().enumerate() {
+            let is_last = index == statements.len() - 1;
+
+            match statement {
+                ast::Statement::Let(let_statement) => {
+                    self.compile_let_statement(let_statement);
+
+                    if is_last {
+                        self.function.chunk.write(Instruction::Void, location(&let_statement.node));
+                    }
+                },
+                // Structs only exist for the typechecker, their instances are built by literals
+                ast::Statement::Struct(struct_statement) => {
+                    if is_last {
+                        self.function.chunk.write(Instruction::Void, location(&struct_statement.node));
+                    }
+                },
+                ast::Statement::Expression(expression_statement) => {
+                    self.compile_expression(&expression_statement.expression);
+
+                    if !is_last {
+                        self.function.chunk.write(Instruction::Pop, location(&expression_statement.node));
+                    }
+                },
+            }
+        }
+
+        if statements.is_empty() {
+            self.function.chunk.write(Instruction::Void, empty_location);
         }
     }
 
     fn compile_let_statement(&mut self, statement: &ast::LetStatement) {
-        if self.depth <= 0 {
-            panic!("Cannot declare variables at top-level.")
+        if self.is_global_declaration(&statement.identifier) {
+            let slot = self.resolve_global_slot(&statement.identifier.value);
+            self.compile_let_value(statement);
+
+            self.function.chunk.write(Instruction::SetGlobal { slot }, location(&statement.node));
+            self.function.chunk.write(Instruction::Pop, location(&statement.node));
+            return
         }
 
         let index = self.declare_local_variable(&statement.identifier);
+        self.compile_let_value(statement);
 
-        match &statement.expression {
-            Some(expression) => self.compile_expression(expression.as_ref()),
-            None => todo!(),
-        }
+        self.function.chunk.write(Instruction::SetLocal { slot: index as u8 }, location(&statement.node));
+        self.function.chunk.write(Instruction::Pop, location(&statement.node));
 
-        self.function.chunk.add_operation(OperationCode::SET_LOCAL, statement.node.token.line);
-        self.function.chunk.add_instruction(index as u8, statement.node.token.line);
+        self.mark_initialized(index);
+    }
 
-        match &mut self.locals[index] {
-            Some(local) => local.is_initialized = true,
-            None => panic!("Local variable not found after initialization"),
+    // Variables declared without a value hold void, the typechecker rejects reads before an assignment
+    fn compile_let_value(&mut self, statement: &ast::LetStatement) {
+        match &statement.expression {
+            Some(expression) => self.compile_expression(expression.as_ref()),
+            None => self.function.chunk.write(Instruction::Void, location(&statement.node)),
         }
     }
 
@@ -105,33 +166,21 @@ impl<'a> Compiler<'a> {
             ast::Expression::Call(expression) => self.compile_call_expression(expression),
             ast::Expression::Return(expression) => self.compile_return_expression(expression),
             ast::Expression::Index(expression) => self.compile_index_expression(expression),
-            _ => todo!()
+            ast::Expression::Struct(expression) => self.compile_struct_expression(expression),
+            ast::Expression::Field(expression) => self.compile_field_expression(expression),
+            ast::Expression::FieldAssign(expression) => self.compile_field_assignment_expression(expression),
+            ast::Expression::CharacterLiteral(literal) => self.error(&literal.node, "Characters aren't supported yet"),
+            ast::Expression::Break(expression) => self.error(&expression.node, "'break' isn't supported yet"),
         }
     }
 
     fn compile_identifier(&mut self, identifier: &ast::Identifier) {
-        let variable_index = self.get_local_variable_index(&identifier.value);
-
-        match variable_index {
-            Some(index) => {
-                self.function.chunk.add_operation(OperationCode::GET_LOCAL, identifier.node.token.line);
-                self.function.chunk.add_instruction(index as u8, identifier.node.token.line);
+        match self.resolve_variable(identifier) {
+            Variable::Local(index) => {
+                self.function.chunk.write(Instruction::GetLocal { slot: index as u8 }, location(&identifier.node));
             },
-            None => {
-                // Global variables
-                let constant_index = self.function.chunk.push_constant(
-                    Value::Object(
-                        Object::String(
-                            StringObject {
-                                length: identifier.value.len(),
-                                value: identifier.value.clone(),
-                            }
-                        )
-                    )
-                );
-
-                self.function.chunk.add_operation(OperationCode::GET_GLOBAL, identifier.node.token.line);
-                self.function.chunk.add_instruction(constant_index, identifier.node.token.line);
+            Variable::Global(slot) => {
+                self.function.chunk.write(Instruction::GetGlobal { slot }, location(&identifier.node));
             }
         }
     }
@@ -139,7 +188,7 @@ impl<'a> Compiler<'a> {
     fn compile_number_literal(&mut self, literal: &ast::NumberLiteral) {
         self.function.chunk.add_constant(
             Value::F64(literal.value as f64), 
-            literal.node.token.line
+            location(&literal.node)
         );
     }
 
@@ -153,58 +202,66 @@ impl<'a> Compiler<'a> {
             Value::Object(
                 Object::String(string_object)
             ), 
-            literal.node.token.line
+            location(&literal.node)
         );
     }
 
     // TODO: separate this function into closures vs top-level ones
     fn compile_function(&mut self, function: &ast::Function) {
 
-        let mut is_closure = false;
-        let index = if self.depth > 0 {
-            let index = self.declare_local_variable(&function.identifier);
-
-            match &mut self.locals[index] {
-                Some(local) => local.is_initialized = true,
-                None => panic!("Function not found after initialization"),
-            }
+        // Named functions are stored in their variable, anonymous ones are only left on the stack
+        let store = match &function.identifier {
+            Some(identifier) if self.is_global_declaration(identifier) => {
+                Some(Instruction::SetGlobal { slot: self.resolve_global_slot(&identifier.value) })
+            },
+            Some(identifier) => {
+                let index = self.declare_local_variable(identifier);
+                self.mark_initialized(index);
 
-            is_closure = true;
-            index as u8
-        } else {
-            self.function.chunk.push_constant(
-                Value::Object(
-                    Object::String(
-                        StringObject {
-                            length: function.identifier.value.len(),
-                            value: function.identifier.value.clone(),
-                        }
-                    )
-                )
-            )
+                Some(Instruction::SetLocal { slot: index as u8 })
+            },
+            None => None,
         };
 
         let function_object = &mut FunctionObject {
             chunk: Chunk::new(),
             arity: 0,
-            name: function.identifier.value.clone(),
+            name: function.name().to_string(),
         };
         let mut compiler = Compiler::new(function_object);
 
+        // Nested functions share the global slots of the program
+        compiler.globals = std::mem::take(&mut self.globals);
+        compiler.model = self.model;
+
         compiler.depth += 1;
         compiler.compile_function_parameters(function);
         compiler.depth -= 1;
 
         compiler.compile_expression(&function.body);
 
-        self.function.chunk.add_constant(Value::Object(Object::Function(function_object.clone())), function.node.token.line);
+        // Functions implicitly return the value of their body
+        let last_location = compiler.get_last_location(location(&function.node));
+        compiler.function.chunk.write(Instruction::Return, last_location);
+
+        compiler.function.chunk.span = Span {
+            start: location(&function.node),
+            end: match function.body.as_ref() {
+                ast::Expression::Block(block) => location(&block.end),
+                _ => last_location,
+            },
+        };
+
+        // Parameters live until the end of the function
+        compiler.end_scope(0);
+
+        self.globals = std::mem::take(&mut compiler.globals);
+        self.errors.append(&mut compiler.errors);
 
-        if !is_closure {
-            self.function.chunk.add_operation(OperationCode::SET_GLOBAL, function.node.token.line);
-            self.function.chunk.add_instruction(index, function.node.token.line);
-        } else {
-            self.function.chunk.add_operation(OperationCode::SET_LOCAL, function.node.token.line);
-            self.function.chunk.add_instruction(index, function.node.token.line);
+        self.function.chunk.add_constant(Value::Object(Object::Function(function_object.clone())), location(&function.node));
+
+        if let Some(instruction) = store {
+            self.function.chunk.write(instruction, location(&function.node));
         }
     }
 
@@ -212,22 +269,18 @@ impl<'a> Compiler<'a> {
         for parameter in &function.parameters {
             self.function.arity += 1;
             let index = self.declare_local_variable(&parameter.identifier);
-
-            match &mut self.locals[index] {
-                Some(local) => local.is_initialized = true,
-                None => panic!("Function not found after initialization"),
-            }
+            self.mark_initialized(index);
         }
     }
 
     fn compile_boolean_literal(&mut self, literal: &ast::BooleanLiteral) {
-        self.function.chunk.add_operation(
+        self.function.chunk.write(
             if literal.value {
-                OperationCode::TRUE
+                Instruction::True
             } else {
-                OperationCode::FALSE
+                Instruction::False
             }, 
-            literal.node.token.line
+            location(&literal.node)
         );
     }
 
@@ -235,17 +288,17 @@ impl<'a> Compiler<'a> {
         self.compile_expression(&expression.expression);
 
         match expression.operator.as_str() {
-            "!" => self.function.chunk.add_operation(OperationCode::NOT, expression.node.token.line),
-            "-" => self.function.chunk.add_operation(OperationCode::NEGATE, expression.node.token.line),
-            _ => todo!()
+            "!" => self.function.chunk.write(Instruction::Not, location(&expression.node)),
+            "-" => self.function.chunk.write(Instruction::Negate, location(&expression.node)),
+            operator => self.error(&expression.node, &format!("Unknown prefix operator '{operator}'")),
         }
     }
 
     fn compile_infix_expression(&mut self, expression: &ast::InfixExpression) {
         match expression.operator.as_str() {
-            "&&" => self.compile_and_expression(&expression),
-            "||" => self.compile_or_expression(&expression),
-            _ => self.compile_simple_infix_expression(&expression),
+            "&&" => self.compile_and_expression(expression),
+            "||" => self.compile_or_expression(expression),
+            _ => self.compile_simple_infix_expression(expression),
         }
     }
 
@@ -254,15 +307,15 @@ impl<'a> Compiler<'a> {
         self.compile_expression(&expression.right_expression);
 
         match expression.operator.as_str() {
-            "+" => self.function.chunk.add_operation(OperationCode::ADD, expression.node.token.line),
-            "-" => self.function.chunk.add_operation(OperationCode::SUBSTRACT, expression.node.token.line),
-            "*" => self.function.chunk.add_operation(OperationCode::MULTIPLY, expression.node.token.line),
-            "/" => self.function.chunk.add_operation(OperationCode::DIVIDE, expression.node.token.line),
-            "==" => self.function.chunk.add_operation(OperationCode::EQUALS, expression.node.token.line),
-            "!=" => self.function.chunk.add_operation(OperationCode::NOT_EQUALS, expression.node.token.line),
-            ">" => self.function.chunk.add_operation(OperationCode::GREATER, expression.node.token.line),
-            "<" => self.function.chunk.add_operation(OperationCode::LESS, expression.node.token.line),
-            operator => todo!("Operator {} not implemented yet.", operator),
+            "+" => self.function.chunk.write(Instruction::Add, location(&expression.node)),
+            "-" => self.function.chunk.write(Instruction::Substract, location(&expression.node)),
+            "*" => self.function.chunk.write(Instruction::Multiply, location(&expression.node)),
+            "/" => self.function.chunk.write(Instruction::Divide, location(&expression.node)),
+            "==" => self.function.chunk.write(Instruction::Equals, location(&expression.node)),
+            "!=" => self.function.chunk.write(Instruction::NotEquals, location(&expression.node)),
+            ">" => self.function.chunk.write(Instruction::Greater, location(&expression.node)),
+            "<" => self.function.chunk.write(Instruction::Less, location(&expression.node)),
+            operator => self.error(&expression.node, &format!("Unknown operator '{operator}'")),
         }
     }
 
@@ -271,10 +324,10 @@ impl<'a> Compiler<'a> {
 
         let end_jump = self.function.chunk.add_jump(
             OperationCode::JUMP_IF_FALSE, 
-            expression.node.token.line,
+            location(&expression.node),
         );
 
-        self.function.chunk.add_operation(OperationCode::POP, expression.node.token.line);
+        self.function.chunk.write(Instruction::Pop, location(&expression.node));
         self.compile_expression(&expression.right_expression);
 
         self.function.chunk.patch_jump(end_jump);
@@ -285,18 +338,18 @@ impl<'a> Compiler<'a> {
 
         let else_jump = self.function.chunk.add_jump(
             OperationCode::JUMP_IF_FALSE, 
-            expression.node.token.line
+            location(&expression.node)
         );
 
         let end_jump = self.function.chunk.add_jump(
             OperationCode::JUMP, 
-            expression.node.token.line
+            location(&expression.node)
         );
 
         self.function.chunk.patch_jump(else_jump);
-        self.function.chunk.add_operation(
-            OperationCode::POP, 
-            expression.node.token.line
+        self.function.chunk.write(
+            Instruction::Pop, 
+            location(&expression.node)
         );
 
         self.compile_expression(&expression.right_expression);
@@ -304,16 +357,19 @@ impl<'a> Compiler<'a> {
     }
 
     fn compile_assignment_expression(&mut self, expression: &ast::AssignmentExpression) {
-        let variable_index = self.get_local_variable_index(&expression.identifier.value);
-
         self.compile_expression(&expression.expression);
+        self.compile_store(&expression.identifier, location(&expression.node));
+    }
 
-        match variable_index {
-            Some(index) => {
-                self.function.chunk.add_operation(OperationCode::SET_LOCAL, expression.node.token.line);
-                self.function.chunk.add_instruction(index as u8, expression.node.token.line);
+    // Stores the value on top of the stack in a variable, leaving it there
+    fn compile_store(&mut self, identifier: &ast::Identifier, location: Location) {
+        match self.resolve_variable(identifier) {
+            Variable::Local(index) => {
+                self.function.chunk.write(Instruction::SetLocal { slot: index as u8 }, location);
+            },
+            Variable::Global(slot) => {
+                self.function.chunk.write(Instruction::SetGlobal { slot }, location);
             },
-            None => todo!() // TODO: We could assume this is a global variable if we support it.
         }
     }
 
@@ -326,33 +382,31 @@ impl<'a> Compiler<'a> {
             panic!("Array initialization cannot contain more than 255 items");
         }
         
-        self.function.chunk.add_operation(OperationCode::BUILD_ARRAY, expression.node.token.line);
-        self.function.chunk.add_instruction(expression.elements.len() as u8, expression.node.token.line);
+        self.function.chunk.write(Instruction::BuildArray { length: expression.elements.len() as u8 }, location(&expression.node));
     }
 
     fn compile_block_expression(&mut self, expression: &ast::BlockExpression) {
         self.depth += 1;
-
-        for statement in &expression.statements {
-            self.compile_statement(statement);
-        }
-
+        self.compile_statements(&expression.statements, location(&expression.node));
         self.depth -= 1;
+
+        self.end_scope(self.depth);
     }
 
     fn compile_if_expression(&mut self, expression: &ast::IfExpression) {
         self.compile_expression(&expression.condition);
 
-        let then_jump = self.function.chunk.add_jump(OperationCode::JUMP_IF_FALSE, expression.node.token.line);
-        self.function.chunk.add_operation(OperationCode::POP, expression.node.token.line);
+        let then_jump = self.function.chunk.add_jump(OperationCode::JUMP_IF_FALSE, location(&expression.node));
+        self.function.chunk.write(Instruction::Pop, location(&expression.node));
         self.compile_expression(&expression.consequence);
 
-        let alternative_jump = self.function.chunk.add_jump(OperationCode::JUMP, expression.node.token.line);
+        let alternative_jump = self.function.chunk.add_jump(OperationCode::JUMP, location(&expression.node));
         self.function.chunk.patch_jump(then_jump);
-        self.function.chunk.add_operation(OperationCode::POP, expression.node.token.line);
+        self.function.chunk.write(Instruction::Pop, location(&expression.node));
 
-        if let Some(alternative) = &expression.alternative {
-            self.compile_expression(alternative);
+        match &expression.alternative {
+            Some(alternative) => self.compile_expression(alternative),
+            None => self.function.chunk.write(Instruction::Void, location(&expression.node)),
         }
 
         self.function.chunk.patch_jump(alternative_jump);
@@ -365,21 +419,29 @@ impl<'a> Compiler<'a> {
 
         let exit_jump = self.function.chunk.add_jump(
             OperationCode::JUMP_IF_FALSE, 
-            expression.node.token.line
+            location(&expression.node)
         );
 
-        self.function.chunk.add_operation(
-            OperationCode::POP, 
-            expression.node.token.line
+        self.function.chunk.write(
+            Instruction::Pop, 
+            location(&expression.node)
         );
 
         self.compile_expression(&expression.iteration);
-        self.function.chunk.add_loop(loop_start, expression.node.token.line);
+        self.function.chunk.write(
+            Instruction::Pop, 
+            location(&expression.node)
+        );
+        self.function.chunk.add_loop(loop_start, location(&expression.node));
 
         self.function.chunk.patch_jump(exit_jump);
-        self.function.chunk.add_operation(
-            OperationCode::POP, 
-            expression.node.token.line
+        self.function.chunk.write(
+            Instruction::Pop, 
+            location(&expression.node)
+        );
+        self.function.chunk.write(
+            Instruction::Void, 
+            location(&expression.node)
         );
     }
 
@@ -390,26 +452,119 @@ impl<'a> Compiler<'a> {
             self.compile_expression(argument);
         }
 
-        self.function.chunk.add_operation(OperationCode::CALL, expression.node.token.line);
-        self.function.chunk.add_instruction(expression.arguments.len() as u8, expression.node.token.line);
+        self.function.chunk.write(Instruction::Call { arguments: expression.arguments.len() as u8 }, location(&expression.node));
     }
 
     fn compile_return_expression(&mut self, expression: &ast::ReturnExpression) {
         self.compile_expression(&expression.expression);
-        self.function.chunk.add_operation(OperationCode::RETURN, expression.node.token.line);
+        self.function.chunk.write(Instruction::Return, location(&expression.node));
     }
 
     fn compile_index_expression(&mut self, expression: &ast::IndexExpression) {
         self.compile_expression(&expression.indexed);
         self.compile_expression(&expression.index);
-        self.function.chunk.add_operation(
-            OperationCode::INDEX_ARRAY, 
-            expression.node.token.line
+        self.function.chunk.write(
+            Instruction::IndexArray, 
+            location(&expression.node)
         );
     }
 
+    // The struct starts with void fields, which are set in source order.
+    // Its layout constant holds the struct name followed by the field names in slot order.
+    fn compile_struct_expression(&mut self, expression: &ast::StructExpression) {
+        if expression.fields.len() > 255 {
+            panic!("Struct cannot contain more than 255 fields");
+        }
+
+        let mut names = vec![expression.identifier.value.clone(); expression.fields.len() + 1];
+        for field in &expression.fields {
+            names[self.field_slot(&field.identifier) as usize + 1] = field.identifier.value.clone();
+        }
+
+        let elements = names.into_iter()
+            .map(|name| Value::Object(Object::String(StringObject { length: name.len(), value: name })))
+            .collect();
+        self.function.chunk.add_constant(Value::Object(Object::Array(ArrayObject { elements })), location(&expression.node));
+
+        self.function.chunk.write(Instruction::BuildStruct { fields: expression.fields.len() as u8 }, location(&expression.node));
+
+        for field in &expression.fields {
+            self.compile_expression(&field.expression);
+
+            let slot = self.field_slot(&field.identifier);
+            self.function.chunk.write(Instruction::SetField { slot }, location(&field.identifier.node));
+        }
+    }
+
+    fn compile_field_expression(&mut self, expression: &ast::FieldExpression) {
+        self.compile_expression(&expression.expression);
+
+        let slot = self.field_slot(&expression.field);
+        self.function.chunk.write(Instruction::GetField { slot }, location(&expression.node));
+    }
+
+    // Structs are values: the structs from the variable down to the assigned one are read,
+    // each is set in the one holding it from the innermost up, and the variable is stored again.
+    fn compile_field_assignment_expression(&mut self, expression: &ast::FieldAssignmentExpression) {
+        let mut targets = vec![&expression.target];
+        while let ast::Expression::Field(target) = targets[targets.len() - 1].expression.as_ref() {
+            targets.push(target);
+        }
+
+        let root = match targets[targets.len() - 1].expression.as_ref() {
+            ast::Expression::Identifier(identifier) => identifier,
+            _ => panic!("Fields can only be assigned through a variable"),
+        };
+
+        for target in targets.iter().rev() {
+            self.compile_expression(&target.expression);
+        }
+
+        self.compile_expression(&expression.expression);
+
+        for target in &targets {
+            let slot = self.field_slot(&target.field);
+            self.function.chunk.write(Instruction::SetField { slot }, location(&expression.node));
+        }
+
+        self.compile_store(root, location(&expression.node));
+    }
+
     // Utils
 
+    fn error(&mut self, node: &ast::Node, message: &str) {
+        self.errors.push(format!("Compile error on line {}:{}: {message}", node.token.line, node.token.column));
+    }
+
+    fn field_slot(&self, field: &ast::Identifier) -> u8 {
+        match self.model.and_then(|model| model.field_slot(field)) {
+            Some(slot) => slot as u8,
+            None => panic!("No slot for the field '{}', structs must be typechecked before being compiled", field.value),
+        }
+    }
+
+    // Top-level declarations are globals, as the typechecker resolved them when there's a model
+    fn is_global_declaration(&self, identifier: &ast::Identifier) -> bool {
+        match self.model.and_then(|model| model.binding(identifier)) {
+            Some(binding) => binding.kind == BindingKind::Global,
+            None => self.depth == 0,
+        }
+    }
+
+    // Without a model, names that aren't locals are globals
+    fn resolve_variable(&mut self, identifier: &ast::Identifier) -> Variable {
+        let binding = self.model.and_then(|model| model.binding(identifier));
+
+        match (binding, self.get_local_variable_index(&identifier.value)) {
+            (Some(binding), _) if binding.kind == BindingKind::Global => {
+                Variable::Global(self.resolve_global_slot(&identifier.value))
+            },
+            (_, Some(index)) => Variable::Local(index),
+            (None, None) => Variable::Global(self.resolve_global_slot(&identifier.value)),
+            (Some(_), None) => panic!("No local slot for '{}', the typechecker resolved it to a local", identifier.value),
+        }
+    }
+
     fn declare_local_variable(&mut self, identifier: &ast::Identifier) -> usize {
 
         if self.depth == 0 {
@@ -425,6 +580,7 @@ impl<'a> Compiler<'a> {
                 name: identifier.value.clone(),
                 depth: self.depth,
                 is_initialized: false,
+                start: 0,
             }
         );
 
@@ -433,6 +589,40 @@ impl<'a> Compiler<'a> {
         return self.locals_count - 1
     }
 
+    fn mark_initialized(&mut self, index: usize) {
+        let start = self.function.chunk.code.len();
+
+        match &mut self.locals[index] {
+            Some(local) => {
+                local.is_initialized = true;
+                local.start = start;
+            },
+            None => panic!("Local variable not found after initialization"),
+        }
+    }
+
+    // Discards the locals declared deeper than `depth`, recording where they lived for debuggers
+    fn end_scope(&mut self, depth: usize) {
+        let end = self.function.chunk.code.len();
+
+        while self.locals_count > 0 {
+            let local = match &self.locals[self.locals_count - 1] {
+                Some(local) if local.depth > depth => local,
+                _ => break,
+            };
+
+            self.function.chunk.locals.push(LocalVariable {
+                name: local.name.clone(),
+                slot: self.locals_count - 1,
+                start: local.start,
+                end,
+            });
+
+            self.locals[self.locals_count - 1] = None;
+            self.locals_count -= 1;
+        }
+    }
+
     fn get_local_variable_index(&mut self, name: &str) -> Option<usize> {
         for index in (0..self.locals_count).rev() {
             let local_option = &self.locals[index];
@@ -450,4 +640,24 @@ impl<'a> Compiler<'a> {
         return None
     }
 
-}
\ No newline at end of file
+    fn get_last_location(&self, default: Location) -> Location {
+        match self.function.chunk.locations.last() {
+            Some(location) => location,
+            None => default,
+        }
+    }
+
+    fn resolve_global_slot(&mut self, name: &str) -> u8 {
+        let slot = self.globals.intern(name);
+        if slot >= GLOBALS_SIZE {
+            panic!("Exceeded global variable count");
+        }
+
+        return slot as u8
+    }
+
+}
+
+fn location(node: &ast::Node) -> Location {
+    Location::new(node.token.line, node.token.column)
+}
@@ -0,0 +1,254 @@
+pub mod tests;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+use crate::backend::{
+    bytecode::{Instruction, Location, OperationCode},
+    debug::disassemble_chunk,
+    object::{FunctionObject, Object},
+    value::Value,
+    vm::{
+        hooks::{HookAction, Hooks},
+        VM,
+    },
+};
+
+// A JUMP_IF_FALSE, counting how often the condition was true (falling through) and false (jumping)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BranchCoverage {
+    pub line: usize,
+    pub taken_true: u64,
+    pub taken_false: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionCoverage {
+    pub name: String,
+    pub line: usize,
+    pub calls: u64,
+
+    // Line of every instruction offset, and how often each offset was executed
+    pub offsets: BTreeMap<usize, usize>,
+    pub hits: HashMap<usize, u64>,
+    pub branches: BTreeMap<usize, BranchCoverage>,
+}
+
+// Coverage of a whole program, every function of it is known before it runs,
+// so lines that are never executed are reported too
+pub struct Coverage {
+    file: String,
+    functions: Vec<FunctionCoverage>,
+
+    // Functions are told apart by name and start, as nested functions may share names
+    indices: HashMap<(String, Location), usize>,
+    calls: Vec<Option<usize>>,
+}
+
+impl Coverage {
+    pub fn new(file: &str, program: &FunctionObject) -> Self {
+        let mut coverage = Self {
+            file: file.to_string(),
+            functions: vec![],
+            indices: HashMap::new(),
+            calls: vec![],
+        };
+
+        coverage.add_function(program);
+        coverage
+    }
+
+    fn add_function(&mut self, function: &FunctionObject) {
+        let mut coverage = FunctionCoverage {
+            name: function.name.clone(),
+            line: function.chunk.span.start.line,
+            ..FunctionCoverage::default()
+        };
+
+        for instruction in disassemble_chunk(&function.chunk) {
+            coverage.offsets.insert(instruction.offset, instruction.line);
+
+            if instruction.operation == OperationCode::JUMP_IF_FALSE {
+                coverage.branches.insert(instruction.offset, BranchCoverage {
+                    line: instruction.line,
+                    ..BranchCoverage::default()
+                });
+            }
+        }
+
+        let key = (function.name.clone(), function.chunk.span.start);
+        self.indices.insert(key, self.functions.len());
+        self.functions.push(coverage);
+
+        for constant in &function.chunk.contants {
+            if let Value::Object(Object::Function(function)) = constant {
+                self.add_function(function);
+            }
+        }
+    }
+
+    pub fn functions(&self) -> &[FunctionCoverage] {
+        &self.functions
+    }
+
+    // Hits of every line holding code, a line counts as executed as often as its most executed instruction
+    pub fn lines(&self) -> BTreeMap<usize, u64> {
+        let mut lines = BTreeMap::new();
+
+        for function in &self.functions {
+            for (offset, line) in &function.offsets {
+                let hits = function.hits.get(offset).copied().unwrap_or_default();
+                let entry = lines.entry(*line).or_insert(0);
+                *entry = (*entry).max(hits);
+            }
+        }
+
+        lines.remove(&0);
+        lines
+    }
+
+    // Branches sorted by line, each JUMP_IF_FALSE has two sides
+    pub fn branches(&self) -> Vec<BranchCoverage> {
+        let mut branches: Vec<BranchCoverage> = self.functions.iter()
+            .flat_map(|function| function.branches.values().copied())
+            .collect();
+
+        branches.sort_by_key(|branch| branch.line);
+        branches
+    }
+
+    pub fn report(&self) -> String {
+        let lines = self.lines();
+        let covered = lines.values().filter(|hits| **hits > 0).count();
+
+        let branches = self.branches();
+        let sides = branches.len() * 2;
+        let taken: usize = branches.iter()
+            .map(|branch| (branch.taken_true > 0) as usize + (branch.taken_false > 0) as usize)
+            .sum();
+
+        let mut output = String::new();
+        let _ = writeln!(output, "File: {}", self.file);
+        let _ = writeln!(output, "Lines: {covered}/{} ({})", lines.len(), percentage(covered, lines.len()));
+        let _ = writeln!(output, "Branches: {taken}/{sides} ({})", percentage(taken, sides));
+
+        let uncovered: Vec<String> = lines.iter()
+            .filter(|(_, hits)| **hits == 0)
+            .map(|(line, _)| line.to_string())
+            .collect();
+
+        if !uncovered.is_empty() {
+            let _ = writeln!(output, "Uncovered lines: {}", uncovered.join(", "));
+        }
+
+        for branch in branches.iter().filter(|branch| branch.taken_true == 0 || branch.taken_false == 0) {
+            let side = match (branch.taken_true, branch.taken_false) {
+                (0, 0) => "never reached",
+                (0, _) => "never true",
+                _ => "never false",
+            };
+
+            let _ = writeln!(output, "Partial branch on line {}: {side}", branch.line);
+        }
+
+        output
+    }
+
+    pub fn lcov(&self) -> String {
+        let mut output = String::new();
+        let _ = writeln!(output, "TN:");
+        let _ = writeln!(output, "SF:{}", self.file);
+
+        for function in &self.functions {
+            let _ = writeln!(output, "FN:{},{}", function.line, function.name);
+        }
+
+        for function in &self.functions {
+            let _ = writeln!(output, "FNDA:{},{}", function.calls, function.name);
+        }
+
+        let _ = writeln!(output, "FNF:{}", self.functions.len());
+        let _ = writeln!(output, "FNH:{}", self.functions.iter().filter(|function| function.calls > 0).count());
+
+        let branches = self.branches();
+        for (block, branch) in branches.iter().enumerate() {
+            // A branch whose condition never ran is reported as "-"
+            let reached = branch.taken_true + branch.taken_false > 0;
+            for (side, count) in [branch.taken_true, branch.taken_false].iter().enumerate() {
+                let taken = if reached { count.to_string() } else { String::from("-") };
+                let _ = writeln!(output, "BRDA:{},{block},{side},{taken}", branch.line);
+            }
+        }
+
+        let taken: usize = branches.iter()
+            .map(|branch| (branch.taken_true > 0) as usize + (branch.taken_false > 0) as usize)
+            .sum();
+
+        let _ = writeln!(output, "BRF:{}", branches.len() * 2);
+        let _ = writeln!(output, "BRH:{taken}");
+
+        let lines = self.lines();
+        for (line, hits) in &lines {
+            let _ = writeln!(output, "DA:{line},{hits}");
+        }
+
+        let _ = writeln!(output, "LF:{}", lines.len());
+        let _ = writeln!(output, "LH:{}", lines.values().filter(|hits| **hits > 0).count());
+        let _ = writeln!(output, "end_of_record");
+
+        output
+    }
+}
+
+impl Hooks for Coverage {
+    fn before_instruction(&mut self, vm: &VM, offset: usize, instruction: &Instruction) -> HookAction {
+        let function = match self.calls.last() {
+            Some(Some(index)) => &mut self.functions[*index],
+            _ => return HookAction::Continue,
+        };
+
+        *function.hits.entry(offset).or_default() += 1;
+
+        // The condition is still on the stack, the jump happens when it's false
+        if let Instruction::JumpIfFalse { .. } = instruction {
+            if let Some(branch) = function.branches.get_mut(&offset) {
+                match vm.stack().last() {
+                    Some(Value::Boolean(false)) => branch.taken_false += 1,
+                    _ => branch.taken_true += 1,
+                }
+            }
+        }
+
+        return HookAction::Continue
+    }
+
+    fn on_call(&mut self, vm: &VM) {
+        let frame = match vm.current_frame() {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        // Functions that aren't part of the program aren't covered
+        let key = (frame.function.name.clone(), frame.function.chunk.span.start);
+        let index = self.indices.get(&key).copied();
+
+        if let Some(index) = index {
+            self.functions[index].calls += 1;
+        }
+
+        self.calls.push(index);
+    }
+
+    fn on_return(&mut self, _vm: &VM, _value: &Value) {
+        self.calls.pop();
+    }
+}
+
+fn percentage(part: usize, total: usize) -> String {
+    match total {
+        0 => String::from("100.0%"),
+        _ => format!("{:.1}%", part as f64 * 100.0 / total as f64),
+    }
+}
@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::backend::{
+        bytecode::Chunk,
+        compiler::Compiler,
+        coverage::Coverage,
+        object::FunctionObject,
+        vm::{InterpretationResult, VM},
+    };
+
+    use crate::frontend::{
+        lexer::Lexer,
+        parser::{parse_file, Parser},
+        typecheck::check_program,
+    };
+
+    const PROGRAM: &str = "
+fn clamp(x: int) -> int {
+    if x > 10 {
+        return 10;
+    }
+
+    return x;
+}
+
+fn unused() -> int {
+    return 0;
+}
+
+clamp(3);
+clamp(5);
+";
+
+    fn compile(source: &str) -> FunctionObject {
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(&mut lexer);
+        let ast = parse_file(&mut parser);
+        check_program(&ast).unwrap();
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        let mut compiler = Compiler::new(function);
+        compiler.compile(&ast).clone()
+    }
+
+    fn cover(source: &str) -> Rc<RefCell<Coverage>> {
+        let mut function = compile(source);
+        let coverage = Rc::new(RefCell::new(Coverage::new("program.silk", &function)));
+
+        let mut vm = VM::new(&mut function);
+        vm.add_hooks(coverage.clone());
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+
+        return coverage
+    }
+
+    #[test]
+    fn test_line_hits() {
+        let coverage = cover(PROGRAM);
+        let lines = coverage.borrow().lines();
+
+        assert_eq!(lines.get(&3), Some(&2));
+        assert_eq!(lines.get(&4), Some(&0));
+        assert_eq!(lines.get(&7), Some(&2));
+        assert_eq!(lines.get(&11), Some(&0));
+        assert_eq!(lines.get(&15), Some(&1));
+
+        // Declaring a function runs code on its first line
+        assert_eq!(lines.get(&10), Some(&1));
+
+        // Lines without code aren't reported
+        assert_eq!(lines.get(&5), None);
+        assert_eq!(lines.get(&12), None);
+    }
+
+    #[test]
+    fn test_branches() {
+        let coverage = cover(PROGRAM);
+        let branches = coverage.borrow().branches();
+
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].line, 3);
+        assert_eq!(branches[0].taken_true, 0);
+        assert_eq!(branches[0].taken_false, 2);
+
+        let calls: Vec<(String, u64)> = coverage.borrow().functions().iter()
+            .map(|function| (function.name.clone(), function.calls))
+            .collect();
+
+        assert_eq!(calls, vec![
+            (String::from("Global"), 1),
+            (String::from("clamp"), 2),
+            (String::from("unused"), 0),
+        ]);
+    }
+
+    #[test]
+    fn test_text_report() {
+        let coverage = cover(PROGRAM);
+        let report = coverage.borrow().report();
+
+        assert!(report.starts_with("File: program.silk\n"), "{report}");
+        assert!(report.contains("Lines: 6/8 (75.0%)\n"), "{report}");
+        assert!(report.contains("Branches: 1/2 (50.0%)\n"), "{report}");
+        assert!(report.contains("Uncovered lines: 4, 11\n"), "{report}");
+        assert!(report.contains("Partial branch on line 3: never true\n"), "{report}");
+    }
+
+    #[test]
+    fn test_lcov_report() {
+        let coverage = cover(PROGRAM);
+        let lcov = coverage.borrow().lcov();
+
+        assert!(lcov.starts_with("TN:\nSF:program.silk\n"), "{lcov}");
+        assert!(lcov.contains("FN:2,clamp\n"), "{lcov}");
+        assert!(lcov.contains("FNDA:2,clamp\nFNDA:0,unused\nFNF:3\nFNH:2\n"), "{lcov}");
+        assert!(lcov.contains("BRDA:3,0,0,0\nBRDA:3,0,1,2\nBRF:2\nBRH:1\n"), "{lcov}");
+        assert!(lcov.contains("DA:4,0\n"), "{lcov}");
+        assert!(lcov.contains("DA:14,1\n"), "{lcov}");
+        assert!(lcov.ends_with("LF:8\nLH:6\nend_of_record\n"), "{lcov}");
+    }
+}
@@ -1,89 +1,357 @@
-use super::bytecode::{
-    OperationCode,
-    Chunk,
+pub mod tests;
+
+use std::{collections::BTreeSet, fmt};
+
+use super::{
+    bytecode::{
+        Chunk,
+        DecodeError,
+        Instruction,
+        OperandKind,
+        OperationCode,
+    },
+    object::{FunctionObject, Object},
+    value::Value,
 };
 
-pub fn disassemble_chunk(chunk: &Chunk, name: &str) {
-    println!("===== {} =====", name);
+const INDENT: &str = "    ";
 
-    let mut offset = 0;
-    while offset < chunk.code.len() {
-        offset = disassemble_instruction(chunk, offset)
+// What an operand refers to, resolved against the chunk
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedOperand {
+    Constant(String),   // formatted constant
+    Global(String),     // global name
+    Target(isize),      // jump target offset
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedInstruction {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub operation: OperationCode,
+    pub operands: Vec<usize>,
+    pub resolved: Option<ResolvedOperand>,
+}
+
+// A function and every function nested in its constants
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisassembledFunction {
+    pub name: String,
+    pub arity: usize,
+    pub instructions: Vec<DecodedInstruction>,
+    pub error: Option<DecodeError>, // set when decoding stopped early
+    pub functions: Vec<DisassembledFunction>,
+    pub assembly: String, // the function in the assembler syntax
+}
+
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> Result<DecodedInstruction, DecodeError> {
+    let instruction = chunk.decode(offset)?;
+    return Ok(decode_instruction(chunk, offset, &instruction))
+}
+
+// Decodes every instruction of the chunk, stopping at the first invalid one
+pub fn disassemble_chunk(chunk: &Chunk) -> Vec<DecodedInstruction> {
+    chunk.instructions()
+        .map_while(|result| result.ok())
+        .map(|(offset, instruction)| decode_instruction(chunk, offset, &instruction))
+        .collect()
+}
+
+pub fn disassemble_program(function: &FunctionObject) -> DisassembledFunction {
+    let chunk = &function.chunk;
+
+    let mut instructions = vec![];
+    let mut error = None;
+    for result in chunk.instructions() {
+        match result {
+            Ok((offset, instruction)) => instructions.push(decode_instruction(chunk, offset, &instruction)),
+            Err(decode_error) => error = Some(decode_error),
+        }
+    }
+
+    let functions = chunk.contants.iter()
+        .filter_map(|constant| match constant {
+            Value::Object(Object::Function(nested)) => Some(disassemble_program(nested)),
+            _ => None,
+        })
+        .collect();
+
+    return DisassembledFunction {
+        name: function.name.clone(),
+        arity: function.arity,
+        instructions,
+        error,
+        functions,
+        assembly: disassemble_function(function),
     }
 }
 
-pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
-    print!("> OFFSET {} - ", offset);
-    if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
-        print!(" | ");
-    } else {
-        print!(" {} ", chunk.lines[offset]);
+fn decode_instruction(chunk: &Chunk, offset: usize, instruction: &Instruction) -> DecodedInstruction {
+    let resolved = match instruction.operand() {
+        Some((OperandKind::Constant, index)) => {
+            chunk.contants.get(index).map(|constant| ResolvedOperand::Constant(format_value(constant)))
+        },
+        Some((OperandKind::Global, slot)) => chunk.globals.get(slot).cloned().map(ResolvedOperand::Global),
+        Some((OperandKind::Jump | OperandKind::Loop, _)) => instruction.jump_target(offset).map(ResolvedOperand::Target),
+        _ => None,
+    };
+
+    let location = chunk.location(offset).unwrap_or_default();
+
+    return DecodedInstruction {
+        offset,
+        line: location.line,
+        column: location.column,
+        operation: instruction.operation(),
+        operands: instruction.operand().map(|(_, operand)| operand).into_iter().collect(),
+        resolved,
     }
+}
 
-    let instruction = OperationCode::from_u8(chunk.code[offset]);
+impl fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = format!("{:04} {:>4} {}", self.offset, self.line, self.operation.name());
+        for operand in &self.operands {
+            text.push_str(&format!(" {operand}"));
+        }
 
-    match instruction {
-        OperationCode::CONSTANT => return handle_constant_instruction("CONSTANT", chunk, offset),
-        OperationCode::TRUE => return handle_simple_instruction("TRUE", offset),
-        OperationCode::FALSE => return handle_simple_instruction("FALSE", offset),
-        OperationCode::ADD => return handle_simple_instruction("ADD", offset),
-        OperationCode::SUBSTRACT => return handle_simple_instruction("SUBSTRACT", offset),
-        OperationCode::MULTIPLY => return handle_simple_instruction("MULTIPLY", offset),
-        OperationCode::DIVIDE => return handle_simple_instruction("DIVIDE", offset),
-        OperationCode::EQUALS => return handle_simple_instruction("EQUALS", offset),
-        OperationCode::NOT_EQUALS => return handle_simple_instruction("NOT_EQUALS", offset),
-        OperationCode::GREATER => return handle_simple_instruction("GREATER", offset),
-        OperationCode::LESS => return handle_simple_instruction("LESS", offset),
-        OperationCode::NOT => return handle_simple_instruction("NOT", offset),
-        OperationCode::NEGATE => return handle_simple_instruction("NEGATE", offset),
-        OperationCode::SET_GLOBAL => return handle_constant_instruction("SET_GLOBAL", chunk, offset),
-        OperationCode::GET_GLOBAL => return handle_constant_instruction("GET_GLOBAL", chunk, offset),
-        OperationCode::SET_LOCAL => return handle_byte_instruction("SET_LOCAL", chunk, offset),
-        OperationCode::GET_LOCAL => return handle_byte_instruction("GET_LOCAL", chunk, offset),
-        OperationCode::JUMP => return handle_jump_instruction("JUMP", chunk, offset, 1),
-        OperationCode::JUMP_IF_FALSE => return handle_jump_instruction("JUMP_IF_FALSE", chunk, offset, 1),
-        OperationCode::LOOP => return handle_jump_instruction("LOOP", chunk, offset, -1),
-        OperationCode::CALL => return handle_byte_instruction("CALL", chunk, offset),
-        OperationCode::BUILD_ARRAY => return handle_byte_instruction("BUILD_ARRAY", chunk, offset),
-        OperationCode::INDEX_ARRAY => return handle_byte_instruction("INDEX_ARRAY", chunk, offset),
-        OperationCode::RETURN => return handle_simple_instruction("RETURN", offset),
-        OperationCode::POP => return handle_simple_instruction("POP", offset),
-        OperationCode::UNKNOW => println!("UNKNOW {:?}", instruction),
+        match &self.resolved {
+            Some(ResolvedOperand::Constant(constant)) => write!(f, "{:<32} ; {constant}", text),
+            Some(ResolvedOperand::Global(name)) => write!(f, "{:<32} ; {name}", text),
+            Some(ResolvedOperand::Target(target)) => write!(f, "{:<32} ; -> {target:04}", text),
+            None => write!(f, "{text}"),
+        }
     }
+}
 
-    return offset + 1
+impl fmt::Display for DisassembledFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.assembly)
+    }
 }
 
-fn handle_simple_instruction(name: &str, offset: usize) -> usize {
-    println!("{name}");
-    return offset + 1
+impl DecodedInstruction {
+    pub fn to_json(&self) -> String {
+        let operands: Vec<String> = self.operands.iter().map(|operand| operand.to_string()).collect();
+
+        let resolved = match &self.resolved {
+            Some(ResolvedOperand::Constant(constant)) => format!(",\"constant\":{}", json_string(constant)),
+            Some(ResolvedOperand::Global(name)) => format!(",\"global\":{}", json_string(name)),
+            Some(ResolvedOperand::Target(target)) => format!(",\"target\":{target}"),
+            None => String::new(),
+        };
+
+        format!(
+            "{{\"offset\":{},\"line\":{},\"column\":{},\"opcode\":{},\"operands\":[{}]{}}}",
+            self.offset,
+            self.line,
+            self.column,
+            json_string(self.operation.name()),
+            operands.join(","),
+            resolved,
+        )
+    }
 }
 
-fn handle_byte_instruction(name: &str, chunk: &Chunk, offset: usize) -> usize {
-    let slot = chunk.code[offset + 1];
-    println!("{} {}", name, slot);
-    return offset + 2
+impl DisassembledFunction {
+    pub fn to_json(&self) -> String {
+        let instructions: Vec<String> = self.instructions.iter().map(DecodedInstruction::to_json).collect();
+        let functions: Vec<String> = self.functions.iter().map(DisassembledFunction::to_json).collect();
+
+        let error = match &self.error {
+            Some(error) => json_string(&error.to_string()),
+            None => String::from("null"),
+        };
+
+        format!(
+            "{{\"name\":{},\"arity\":{},\"instructions\":[{}],\"error\":{},\"functions\":[{}]}}",
+            json_string(&self.name),
+            self.arity,
+            instructions.join(","),
+            error,
+            functions.join(","),
+        )
+    }
 }
 
-fn handle_constant_instruction(name: &str, chunk: &Chunk, offset: usize) -> usize {
-    let constant_index = chunk.code[offset + 1];
-    
-    println!(
-        "{name} (VALUE: {:?}, index: {}) ", 
-        chunk.contants[constant_index as usize], 
-        constant_index
-    );
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
 
-    return offset + 2
+    for character in value.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            character if (character as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", character as u32)),
+            character => escaped.push(character),
+        }
+    }
+
+    escaped.push('"');
+    return escaped
 }
 
-fn handle_jump_instruction(name: &str, chunk: &Chunk, offset: usize, sign: isize) -> usize {
-    println!("jump {} {:?}", offset, chunk.code[offset + 1]);
-    let mut jump = (chunk.code[offset + 1] as u16) << 8;
+// Renders a function and its nested functions in the textual assembly format
+// read by backend::assembler.
+pub fn disassemble_function(function: &FunctionObject) -> String {
+    let mut output = String::new();
+    write_function(&mut output, function, 0);
+    return output
+}
+
+fn write_function(output: &mut String, function: &FunctionObject, depth: usize) {
+    let indent = INDENT.repeat(depth);
+    let chunk = &function.chunk;
+
+    output.push_str(&format!(".function {} {}\n", function.name, function.arity));
+    output.push_str(&format!("{indent}.span {} {}\n", chunk.span.start, chunk.span.end));
+
+    if !chunk.globals.is_empty() {
+        output.push_str(&format!("{indent}.globals\n"));
+        for (slot, name) in chunk.globals.iter().enumerate() {
+            output.push_str(&format!("{indent}{INDENT}{slot}: {name}\n"));
+        }
+    }
 
-    jump |= chunk.code[offset + 2] as u16;
+    if !chunk.contants.is_empty() {
+        output.push_str(&format!("{indent}.constants\n"));
+        for (index, constant) in chunk.contants.iter().enumerate() {
+            output.push_str(&format!("{indent}{INDENT}{index}: "));
 
-    let target = offset as isize + 3 + sign * jump as isize;
-    println!("- {} {} -> {}", name, offset, target);
-    return offset + 3
-}
\ No newline at end of file
+            match constant {
+                Value::Object(Object::Function(nested)) => write_function(output, nested, depth + 2),
+                constant => output.push_str(&format!("{}\n", format_value(constant))),
+            }
+        }
+    }
+
+    if !chunk.locals.is_empty() {
+        output.push_str(&format!("{indent}.locals\n"));
+        for local in &chunk.locals {
+            output.push_str(&format!("{indent}{INDENT}{} {} {} {}\n", local.name, local.slot, local.start, local.end));
+        }
+    }
+
+    output.push_str(&format!("{indent}.code\n"));
+
+    let labels = get_jump_labels(chunk);
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        if labels.contains(&offset) {
+            output.push_str(&format!("{indent}{}\n", format_label(offset)));
+        }
+
+        match chunk.decode(offset) {
+            Ok(instruction) => {
+                let line = format_instruction(chunk, offset, &instruction);
+                output.push_str(&format!("{indent}{INDENT}{line}\n"));
+                offset += instruction.width();
+            },
+            Err(error) => {
+                output.push_str(&format!("{indent}{INDENT}; {error}\n"));
+                offset += 1;
+            },
+        }
+    }
+
+    output.push_str(&format!("{indent}.end\n"));
+}
+
+// Formats an instruction as "line:column MNEMONIC operand ; comment", jumps pointing to labels
+fn format_instruction(chunk: &Chunk, offset: usize, instruction: &Instruction) -> String {
+    let decoded = decode_instruction(chunk, offset, instruction);
+    let name = decoded.operation.name();
+    let location = format!("{}:{}", decoded.line, decoded.column);
+
+    let text = match (&decoded.resolved, decoded.operands.first()) {
+        (Some(ResolvedOperand::Target(target)), _) => format!("{location:<7} {name} {}", format_label_name(*target)),
+        (_, Some(operand)) => format!("{location:<7} {name} {operand}"),
+        (_, None) => format!("{location:<7} {name}"),
+    };
+
+    let comment = match decoded.resolved {
+        Some(ResolvedOperand::Constant(_)) => match instruction.operand().and_then(|(_, index)| chunk.contants.get(index)) {
+            Some(Value::Object(Object::Function(function))) => Some(format!("<fn {}>", function.name)),
+            Some(constant) => Some(format_value(constant)),
+            None => None,
+        },
+        Some(ResolvedOperand::Global(name)) => Some(name),
+        _ => None,
+    };
+
+    match comment {
+        Some(comment) => format!("{:<30} ; {comment}", text),
+        None => text,
+    }
+}
+
+// Formats a constant the way the assembler parses it.
+// Functions are written as nested blocks and are not handled here.
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::F64(value) => format!("{value}"),
+        Value::Boolean(value) => format!("{value}"),
+        Value::Void => String::from("void"),
+        Value::Object(Object::String(string)) => format!("\"{}\"", escape_string(&string.value)),
+        Value::Object(Object::Array(array)) => {
+            let elements: Vec<String> = array.elements.iter().map(format_value).collect();
+            format!("[{}]", elements.join(", "))
+        },
+        Value::Object(Object::Struct(structure)) => {
+            let fields: Vec<String> = structure.layout.fields.iter()
+                .zip(&structure.fields)
+                .map(|(name, value)| format!("{name}: {}", format_value(value)))
+                .collect();
+
+            match fields.is_empty() {
+                true => format!("{} {{}}", structure.layout.name),
+                false => format!("{} {{ {} }}", structure.layout.name, fields.join(", ")),
+            }
+        },
+        Value::Object(Object::Function(function)) => format!("<fn {}>", function.name),
+        Value::Object(Object::Native(native)) => format!("<native fn {}>", native.name),
+    }
+}
+
+fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for character in value.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            character => escaped.push(character),
+        }
+    }
+
+    return escaped
+}
+
+fn get_jump_labels(chunk: &Chunk) -> BTreeSet<usize> {
+    let mut labels = BTreeSet::new();
+
+    for (offset, instruction) in chunk.instructions().flatten() {
+        if let Some(target) = instruction.jump_target(offset) {
+            if target >= 0 {
+                labels.insert(target as usize);
+            }
+        }
+    }
+
+    return labels
+}
+
+fn format_label(offset: usize) -> String {
+    format!("{}:", format_label_name(offset as isize))
+}
+
+fn format_label_name(offset: isize) -> String {
+    format!("L{offset}")
+}
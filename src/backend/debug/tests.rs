@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod tests {
+    use crate::backend::{
+        assembler::assemble,
+        bytecode::OperationCode,
+        debug::{disassemble_chunk, disassemble_function, disassemble_instruction, disassemble_program, ResolvedOperand},
+        object::FunctionObject,
+    };
+
+    fn assemble_or_panic(source: &str) -> FunctionObject {
+        match assemble(source) {
+            Ok(function) => function,
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    const PROGRAM: &str = "
+        .function Global 0
+        .globals
+            0: double
+        .constants
+            0: .function double 1
+                .constants
+                    0: 2
+                .code
+                    2    GET_LOCAL 0
+                         CONSTANT 0
+                         MULTIPLY
+                         RETURN
+                .end
+            1: \"done\"
+        .code
+            1    CONSTANT 0
+                 SET_GLOBAL 0
+                 POP
+            3    TRUE
+                 JUMP_IF_FALSE end
+                 POP
+                 CONSTANT 1
+                 POP
+        end:
+            4    VOID
+                 RETURN
+        .end
+    ";
+
+    #[test]
+    fn test_disassemble_chunk() {
+        let function = assemble_or_panic(PROGRAM);
+        let instructions = disassemble_chunk(&function.chunk);
+
+        let operations: Vec<OperationCode> = instructions.iter().map(|instruction| instruction.operation).collect();
+        assert_eq!(operations, vec![
+            OperationCode::CONSTANT,
+            OperationCode::SET_GLOBAL,
+            OperationCode::POP,
+            OperationCode::TRUE,
+            OperationCode::JUMP_IF_FALSE,
+            OperationCode::POP,
+            OperationCode::CONSTANT,
+            OperationCode::POP,
+            OperationCode::VOID,
+            OperationCode::RETURN,
+        ]);
+
+        assert_eq!(instructions[0].offset, 0);
+        assert_eq!(instructions[0].line, 1);
+        assert_eq!(instructions[0].operands, vec![0]);
+        assert_eq!(instructions[0].resolved, Some(ResolvedOperand::Constant(String::from("<fn double>"))));
+        assert_eq!(instructions[1].resolved, Some(ResolvedOperand::Global(String::from("double"))));
+        assert_eq!(instructions[2].operands, Vec::<usize>::new());
+        assert_eq!(instructions[2].resolved, None);
+
+        // JUMP_IF_FALSE at offset 6 jumps over POP, CONSTANT and POP
+        assert_eq!(instructions[4].offset, 6);
+        assert_eq!(instructions[4].operands, vec![4]);
+        assert_eq!(instructions[4].resolved, Some(ResolvedOperand::Target(13)));
+        assert_eq!(instructions[8].offset, 13);
+        assert_eq!(instructions[8].line, 4);
+
+        assert_eq!(disassemble_instruction(&function.chunk, 6), Ok(instructions[4].clone()));
+    }
+
+    #[test]
+    fn test_disassemble_program() {
+        let function = assemble_or_panic(PROGRAM);
+        let program = disassemble_program(&function);
+
+        assert_eq!(program.name, "Global");
+        assert_eq!(program.error, None);
+        assert_eq!(program.functions.len(), 1);
+        assert_eq!(program.functions[0].name, "double");
+        assert_eq!(program.functions[0].arity, 1);
+        assert_eq!(program.functions[0].instructions.len(), 4);
+
+        let text = program.to_string();
+        assert_eq!(text, disassemble_function(&function));
+        assert!(text.starts_with(".function Global 0\n"), "{text}");
+        assert!(text.contains(".function double 1\n"), "{text}");
+    }
+
+    #[test]
+    fn test_disassemble_invalid_code() {
+        let mut function = assemble_or_panic(PROGRAM);
+        function.chunk.code[2] = 0xFF;
+
+        let program = disassemble_program(&function);
+        assert_eq!(program.instructions.len(), 1);
+        assert!(program.error.is_some());
+        assert_eq!(disassemble_chunk(&function.chunk).len(), 1);
+        assert!(disassemble_instruction(&function.chunk, 2).is_err());
+    }
+
+    #[test]
+    fn test_disassemble_json() {
+        let function = assemble_or_panic(PROGRAM);
+        let json = disassemble_program(&function).to_json();
+
+        assert!(json.starts_with("{\"name\":\"Global\",\"arity\":0,\"instructions\":[{\"offset\":0,\"line\":1,\"column\":0,\"opcode\":\"CONSTANT\",\"operands\":[0],\"constant\":\"<fn double>\"}"), "{json}");
+        assert!(json.contains("{\"offset\":10,\"line\":3,\"column\":0,\"opcode\":\"CONSTANT\",\"operands\":[1],\"constant\":\"\\\"done\\\"\"}"), "{json}");
+        assert!(json.contains("\"opcode\":\"JUMP_IF_FALSE\",\"operands\":[4],\"target\":13}"), "{json}");
+        assert!(json.contains("\"global\":\"double\""), "{json}");
+        assert!(json.ends_with("\"error\":null,\"functions\":[]}]}"), "{json}");
+    }
+}
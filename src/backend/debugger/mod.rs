@@ -0,0 +1,400 @@
+pub mod tests;
+
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, Write},
+};
+
+use crate::backend::{
+    bytecode::Instruction,
+    debug::format_value,
+    object::{ArrayObject, Object, StringObject},
+    value::Value,
+    vm::{
+        hooks::{HookAction, Hooks},
+        CallFrame,
+        RuntimeError,
+        VM,
+    },
+};
+
+use crate::frontend::{
+    ast,
+    lexer::Lexer,
+    parser::{parse_file, Parser},
+};
+
+const HELP: &str = "\
+Commands:
+    break <line>, b       Stop when reaching a line
+    delete <line>, d      Remove a breakpoint
+    step, s               Run until the next line, entering calls
+    next, n               Run until the next line of this function or its callers
+    finish, f             Run until the current function returns
+    continue, c           Run until the next breakpoint
+    locals, l             Print the local variables of the current frame
+    print <name>, p       Print a local or global variable, or one of its fields as in p.x
+    backtrace, bt         Print the call frames
+    eval <expression>, e  Evaluate an expression in the current frame
+    quit, q               Stop the program";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Step,
+    Next { depth: usize, line: usize },
+    Finish { depth: usize },
+    Continue,
+}
+
+// Interactive debugger, reading commands from `input` whenever the program stops.
+// The program stops before its first line, on breakpoints, after steps and on errors.
+pub struct Debugger<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    source: Vec<String>,
+
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+
+    // Frame depth and line of the previous instruction, lines are entered when it changes
+    location: Option<(usize, usize)>,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            source: vec![],
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+            location: None,
+        }
+    }
+
+    // Source of the program, used to print the line the program stopped at
+    pub fn set_source(&mut self, source: &str) {
+        self.source = source.lines().map(String::from).collect();
+    }
+
+    pub fn add_breakpoint(&mut self, line: usize) {
+        self.breakpoints.insert(line);
+    }
+
+    fn should_stop(&self, depth: usize, line: usize) -> bool {
+        if self.breakpoints.contains(&line) {
+            return true
+        }
+
+        match self.mode {
+            Mode::Step => true,
+            // Coming back to the starting line after a call isn't a new line
+            Mode::Next { depth: start, line: start_line } => depth < start || (depth == start && line != start_line),
+            Mode::Finish { depth: start } => depth < start,
+            Mode::Continue => false,
+        }
+    }
+
+    // Reads and runs commands until one resumes the program
+    fn prompt(&mut self, vm: &VM, offset: usize) -> HookAction {
+        loop {
+            let _ = write!(self.output, "(silk) ");
+            let _ = self.output.flush();
+
+            let mut command = String::new();
+            match self.input.read_line(&mut command) {
+                Ok(0) | Err(_) => return HookAction::Stop,
+                Ok(_) => {},
+            }
+
+            let command = command.trim();
+            let (name, argument) = match command.split_once(' ') {
+                Some((name, argument)) => (name, argument.trim()),
+                None => (command, ""),
+            };
+
+            let depth = vm.frames().count();
+            let line = self.location.map(|(_, line)| line).unwrap_or_default();
+
+            match name {
+                "" => continue,
+                "step" | "s" => self.mode = Mode::Step,
+                "next" | "n" => self.mode = Mode::Next { depth, line },
+                "finish" | "f" => self.mode = Mode::Finish { depth },
+                "continue" | "c" => self.mode = Mode::Continue,
+                "quit" | "q" => return HookAction::Stop,
+                "break" | "b" => {
+                    match argument.parse::<usize>() {
+                        Ok(line) => {
+                            self.breakpoints.insert(line);
+                            let _ = writeln!(self.output, "Breakpoint set at line {line}");
+                        },
+                        Err(_) => { let _ = writeln!(self.output, "Expected a line number"); },
+                    }
+
+                    continue;
+                },
+                "delete" | "d" => {
+                    match argument.parse::<usize>() {
+                        Ok(line) if self.breakpoints.remove(&line) => {
+                            let _ = writeln!(self.output, "Breakpoint removed at line {line}");
+                        },
+                        _ => { let _ = writeln!(self.output, "No breakpoint at line {argument}"); },
+                    }
+
+                    continue;
+                },
+                "locals" | "l" => {
+                    self.print_locals(vm, offset);
+                    continue;
+                },
+                "print" | "p" => {
+                    let message = match lookup_path(vm, offset, argument) {
+                        Ok(value) => format!("{argument} = {}", format_value(&value)),
+                        Err(error) => error,
+                    };
+
+                    let _ = writeln!(self.output, "{message}");
+                    continue;
+                },
+                "backtrace" | "bt" => {
+                    self.print_backtrace(vm, offset);
+                    continue;
+                },
+                "eval" | "e" => {
+                    let message = match evaluate(vm, offset, argument) {
+                        Ok(value) => format_value(&value),
+                        Err(error) => error,
+                    };
+
+                    let _ = writeln!(self.output, "{message}");
+                    continue;
+                },
+                "help" | "h" => {
+                    let _ = writeln!(self.output, "{HELP}");
+                    continue;
+                },
+                _ => {
+                    let _ = writeln!(self.output, "Unknown command '{name}', type 'help' for the list of commands");
+                    continue;
+                },
+            }
+
+            return HookAction::Continue
+        }
+    }
+
+    fn print_location(&mut self, frame: &CallFrame, line: usize) {
+        let _ = writeln!(self.output, "Stopped in '{}' at line {line}", frame.function.name);
+
+        if let Some(text) = line.checked_sub(1).and_then(|index| self.source.get(index)) {
+            let _ = writeln!(self.output, "{:>4} | {}", line, text.trim_end());
+        }
+    }
+
+    fn print_locals(&mut self, vm: &VM, offset: usize) {
+        let frame = match vm.current_frame() {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        let mut locals: Vec<_> = frame.function.chunk.live_locals(offset).collect();
+        locals.sort_by_key(|local| local.slot);
+
+        let mut empty = true;
+        for local in locals {
+            if let Some(value) = frame.slots.get(local.slot) {
+                let _ = writeln!(self.output, "{} = {}", local.name, format_value(value));
+                empty = false;
+            }
+        }
+
+        if empty {
+            let _ = writeln!(self.output, "No local variables");
+        }
+    }
+
+    fn print_backtrace(&mut self, vm: &VM, offset: usize) {
+        let frames: Vec<&CallFrame> = vm.frames().collect();
+
+        for (index, frame) in frames.iter().rev().enumerate() {
+            // The current frame hasn't executed its instruction yet
+            let line = match index {
+                0 => frame.function.chunk.line(offset),
+                _ => frame.line(),
+            };
+
+            let _ = writeln!(self.output, "#{index} {} at line {line}", frame.function.name);
+        }
+    }
+}
+
+impl<R: BufRead, W: Write> Hooks for Debugger<R, W> {
+    fn before_instruction(&mut self, vm: &VM, offset: usize, _instruction: &Instruction) -> HookAction {
+        let frame = match vm.current_frame() {
+            Some(frame) => frame,
+            None => return HookAction::Continue,
+        };
+
+        let depth = vm.frames().count();
+        let line = frame.function.chunk.line(offset);
+
+        // Only the first instruction of a line can stop the program
+        let location = Some((depth, line));
+        if self.location == location {
+            return HookAction::Continue
+        }
+
+        self.location = location;
+
+        if !self.should_stop(depth, line) {
+            return HookAction::Continue
+        }
+
+        self.print_location(frame, line);
+        return self.prompt(vm, offset)
+    }
+
+    fn on_error(&mut self, vm: &VM, error: &RuntimeError) {
+        let _ = writeln!(self.output, "{error}");
+
+        // The failing frame can still be inspected, resuming ends the session
+        if let Some(frame) = vm.current_frame() {
+            let offset = frame.ip.saturating_sub(1);
+            self.prompt(vm, offset);
+        }
+    }
+}
+
+// Locals of the current frame shadow globals
+fn lookup_variable(vm: &VM, offset: usize, name: &str) -> Option<Value> {
+    let frame = vm.current_frame()?;
+
+    let local = frame.function.chunk.live_locals(offset)
+        .filter(|local| local.name == name)
+        .max_by_key(|local| local.start);
+
+    match local {
+        Some(local) => frame.slots.get(local.slot).cloned(),
+        None => vm.global(name).cloned(),
+    }
+}
+
+// A variable followed by the fields to read from it, e.g. 'line.from.x'
+fn lookup_path(vm: &VM, offset: usize, path: &str) -> Result<Value, String> {
+    let mut names = path.split('.');
+    let name = names.next().unwrap_or_default();
+
+    let mut value = match lookup_variable(vm, offset, name) {
+        Some(value) => value,
+        None => return Err(format!("Unknown variable '{name}'")),
+    };
+
+    for field in names {
+        value = read_field(value, field)?;
+    }
+
+    return Ok(value)
+}
+
+fn read_field(value: Value, field: &str) -> Result<Value, String> {
+    let structure = match value {
+        Value::Object(Object::Struct(structure)) => structure,
+        value => return Err(format!("Cannot read field '{field}' of {}", format_value(&value))),
+    };
+
+    match structure.layout.fields.iter().position(|name| name == field) {
+        Some(slot) => Ok(structure.fields[slot].clone()),
+        None => Err(format!("Struct '{}' has no field '{field}'", structure.layout.name)),
+    }
+}
+
+// Evaluates a side-effect free expression: literals, variables, arrays, fields, indexing and operators
+fn evaluate(vm: &VM, offset: usize, source: &str) -> Result<Value, String> {
+    if source.is_empty() {
+        return Err(String::from("Expected an expression"));
+    }
+
+    let code = format!("{source};");
+    let mut lexer = Lexer::new(&code);
+    let mut parser = Parser::new(&mut lexer);
+    let file = parse_file(&mut parser);
+
+    if let Some(error) = parser.errors.first() {
+        return Err(error.clone());
+    }
+
+    match file.statements.as_slice() {
+        [ast::Statement::Expression(statement)] => evaluate_expression(vm, offset, &statement.expression),
+        _ => Err(String::from("Expected a single expression")),
+    }
+}
+
+fn evaluate_expression(vm: &VM, offset: usize, expression: &ast::Expression) -> Result<Value, String> {
+    match expression {
+        ast::Expression::Identifier(identifier) => match lookup_variable(vm, offset, &identifier.value) {
+            Some(value) => Ok(value),
+            None => Err(format!("Unknown variable '{}'", identifier.value)),
+        },
+        ast::Expression::NumberLiteral(literal) => Ok(Value::F64(literal.value as f64)),
+        ast::Expression::BooleanLiteral(literal) => Ok(Value::Boolean(literal.value)),
+        ast::Expression::StringLiteral(literal) => Ok(Value::Object(Object::String(StringObject {
+            length: literal.value.len(),
+            value: literal.value.clone(),
+        }))),
+        ast::Expression::Array(array) => {
+            let mut elements = vec![];
+            for element in &array.elements {
+                elements.push(evaluate_expression(vm, offset, element)?);
+            }
+
+            Ok(Value::Object(Object::Array(ArrayObject { elements })))
+        },
+        ast::Expression::Index(expression) => {
+            let indexed = evaluate_expression(vm, offset, &expression.indexed)?;
+            let index = evaluate_expression(vm, offset, &expression.index)?;
+
+            match (indexed, index) {
+                (Value::Object(Object::Array(array)), Value::F64(index)) => match array.elements.get(index as usize) {
+                    Some(value) if index >= 0.0 => Ok(value.clone()),
+                    _ => Err(format!("Index {} is out of bounds", index)),
+                },
+                _ => Err(String::from("Only arrays can be indexed by numbers")),
+            }
+        },
+        ast::Expression::Field(expression) => {
+            let value = evaluate_expression(vm, offset, &expression.expression)?;
+            read_field(value, &expression.field.value)
+        },
+        ast::Expression::Prefix(expression) => {
+            let value = evaluate_expression(vm, offset, &expression.expression)?;
+
+            match (expression.operator.as_str(), value) {
+                ("!", Value::Boolean(value)) => Ok(Value::Boolean(!value)),
+                ("-", Value::F64(value)) => Ok(Value::F64(-value)),
+                (operator, value) => Err(format!("Cannot apply '{operator}' to {}", format_value(&value))),
+            }
+        },
+        ast::Expression::Infix(expression) => {
+            let left = evaluate_expression(vm, offset, &expression.left_expression)?;
+            let right = evaluate_expression(vm, offset, &expression.right_expression)?;
+
+            match (expression.operator.as_str(), left, right) {
+                ("+", Value::F64(a), Value::F64(b)) => Ok(Value::F64(a + b)),
+                ("-", Value::F64(a), Value::F64(b)) => Ok(Value::F64(a - b)),
+                ("*", Value::F64(a), Value::F64(b)) => Ok(Value::F64(a * b)),
+                ("/", Value::F64(a), Value::F64(b)) => Ok(Value::F64(a / b)),
+                (">", Value::F64(a), Value::F64(b)) => Ok(Value::Boolean(a > b)),
+                ("<", Value::F64(a), Value::F64(b)) => Ok(Value::Boolean(a < b)),
+                ("&&", Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(a && b)),
+                ("||", Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(a || b)),
+                ("==", a, b) => Ok(Value::Boolean(a == b)),
+                ("!=", a, b) => Ok(Value::Boolean(a != b)),
+                (operator, a, b) => {
+                    Err(format!("Cannot apply '{operator}' to {} and {}", format_value(&a), format_value(&b)))
+                },
+            }
+        },
+        _ => Err(String::from("Only literals, variables, fields, indexing and operators can be evaluated")),
+    }
+}
@@ -0,0 +1,159 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Cursor, rc::Rc};
+
+    use crate::backend::{
+        bytecode::Chunk,
+        compiler::Compiler,
+        debugger::Debugger,
+        object::FunctionObject,
+        vm::{InterpretationResult, VM},
+    };
+
+    use crate::frontend::{
+        lexer::Lexer,
+        parser::{parse_file, Parser},
+        typecheck::check_program,
+    };
+
+    const PROGRAM: &str = "
+fn add(a: int, b: int) -> int {
+    let sum = a + b;
+    return sum;
+}
+
+{
+    let x = 10;
+    let y = add(x, 5);
+    y * 2;
+}
+";
+
+    fn compile(source: &str) -> FunctionObject {
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(&mut lexer);
+        let ast = parse_file(&mut parser);
+        let model = check_program(&ast).unwrap();
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        let mut compiler = Compiler::new(function);
+        compiler.model = Some(&model);
+        compiler.compile(&ast).clone()
+    }
+
+    fn debug(source: &str, commands: &str) -> (InterpretationResult, String) {
+        let mut function = compile(source);
+
+        let output = Rc::new(RefCell::new(Debugger::new(Cursor::new(commands.as_bytes().to_vec()), Vec::<u8>::new())));
+        output.borrow_mut().set_source(source);
+
+        let mut vm = VM::new(&mut function);
+        vm.add_hooks(output.clone());
+        let result = vm.run();
+
+        let text = String::from_utf8(output.borrow().output.clone()).unwrap();
+        return (result, text)
+    }
+
+    #[test]
+    fn test_breakpoint_and_inspection() {
+        let (result, output) = debug(PROGRAM, "b 4\nc\nbt\nl\np sum\np add\ne sum * 2 + a\ne [a, b][1] == 5\nc\n");
+
+        assert!(matches!(result, InterpretationResult::OK));
+        assert!(output.starts_with("Stopped in 'Global' at line 2\n   2 | fn add(a: int, b: int) -> int {\n"), "{output}");
+        assert!(output.contains("Breakpoint set at line 4\n"), "{output}");
+        assert!(output.contains("Stopped in 'add' at line 4\n   4 |     return sum;\n"), "{output}");
+        assert!(output.contains("#0 add at line 4\n#1 Global at line 9\n"), "{output}");
+        assert!(output.contains("a = 10\nb = 5\nsum = 15\n"), "{output}");
+        assert!(output.contains("sum = 15\n"), "{output}");
+        assert!(output.contains("add = <fn add>\n"), "{output}");
+        assert!(output.contains("(silk) 40\n"), "{output}");
+        assert!(output.contains("(silk) true\n"), "{output}");
+    }
+
+    fn get_stops(output: &str) -> Vec<&str> {
+        output.lines()
+            .filter_map(|line| line.split("Stopped in ").nth(1))
+            .collect()
+    }
+
+    #[test]
+    fn test_step_over() {
+        let (result, output) = debug(PROGRAM, "b 8\nc\nn\np x\nn\np y\nq\n");
+
+        assert!(matches!(result, InterpretationResult::INTERRUPTED));
+        assert!(output.contains("x = 10\n"), "{output}");
+        assert!(output.contains("y = 15\n"), "{output}");
+        assert_eq!(get_stops(&output), vec![
+            "'Global' at line 2",
+            "'Global' at line 8",
+            "'Global' at line 9",
+            "'Global' at line 10",
+        ]);
+    }
+
+    #[test]
+    fn test_step_into_and_out() {
+        let (result, output) = debug(PROGRAM, "b 9\nc\ns\ns\nf\nn\nc\n");
+
+        assert!(matches!(result, InterpretationResult::OK));
+        assert_eq!(get_stops(&output), vec![
+            "'Global' at line 2",
+            "'Global' at line 9",
+            "'add' at line 3",
+            "'add' at line 4",
+            "'Global' at line 9",
+            "'Global' at line 10",
+        ]);
+    }
+
+    #[test]
+    fn test_runtime_error_inspection() {
+        let source = "
+fn get(values: [int], index: int) -> int {
+    return values[index];
+}
+
+get([1, 2], 7);
+";
+        let (result, output) = debug(source, "c\np index\nbt\nc\n");
+
+        assert!(matches!(result, InterpretationResult::RUNTIME_ERROR));
+        assert!(output.contains("Runtime error on line 3 in 'get': Out of bounds error: array length is 2, but index is 7\n"), "{output}");
+        assert!(output.contains("index = 7\n"), "{output}");
+        assert!(output.contains("#0 get at line 3\n#1 Global at line 6\n"), "{output}");
+    }
+
+    #[test]
+    fn test_struct_inspection() {
+        let source = "
+struct Point { x: int, y: int }
+struct Line { from: Point, to: Point }
+
+let line = Line { to: Point { y: 4, x: 3 }, from: Point { x: 1, y: 2 } };
+line;
+";
+        let (_, output) = debug(source, "b 6\nc\np line\np line.to.x\ne line.from.y * 10\np line.size\ne line.to.x.y\nc\n");
+
+        assert!(output.contains("line = Line { from: Point { x: 1, y: 2 }, to: Point { x: 3, y: 4 } }\n"), "{output}");
+        assert!(output.contains("line.to.x = 3\n"), "{output}");
+        assert!(output.contains("(silk) 20\n"), "{output}");
+        assert!(output.contains("Struct 'Line' has no field 'size'\n"), "{output}");
+        assert!(output.contains("Cannot read field 'y' of 3\n"), "{output}");
+    }
+
+    #[test]
+    fn test_unknown_commands_and_variables() {
+        let (_, output) = debug(PROGRAM, "jump\np nothing\ne 1 + true\nd 3\nq\n");
+
+        assert!(output.contains("Unknown command 'jump'"), "{output}");
+        assert!(output.contains("Unknown variable 'nothing'"), "{output}");
+        assert!(output.contains("Cannot apply '+' to 1 and true"), "{output}");
+        assert!(output.contains("No breakpoint at line 3"), "{output}");
+    }
+}
@@ -0,0 +1,52 @@
+pub mod tests;
+
+use std::collections::HashMap;
+
+// Maps each distinct string to a stable index, in insertion order.
+// The compiler uses it to resolve global identifiers to slots.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    indices: HashMap<String, usize>,
+    strings: Vec<String>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self {
+            indices: HashMap::new(),
+            strings: vec![],
+        }
+    }
+
+    pub fn intern(&mut self, string: &str) -> usize {
+        if let Some(index) = self.indices.get(string) {
+            return *index
+        }
+
+        let index = self.strings.len();
+        self.indices.insert(string.to_string(), index);
+        self.strings.push(string.to_string());
+
+        return index
+    }
+
+    pub fn get(&self, string: &str) -> Option<usize> {
+        self.indices.get(string).copied()
+    }
+
+    pub fn resolve(&self, index: usize) -> &str {
+        return &self.strings[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub fn strings(&self) -> &[String] {
+        &self.strings
+    }
+}
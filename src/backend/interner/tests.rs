@@ -0,0 +1,28 @@
+#[cfg(test)]
+mod tests {
+    use crate::backend::interner::Interner;
+
+    #[test]
+    fn test_intern_returns_same_index() {
+        let mut interner = Interner::new();
+
+        let first = interner.intern("main");
+        let second = interner.intern("helper");
+
+        assert_eq!(first, 0);
+        assert_eq!(second, 1);
+        assert_eq!(interner.intern("main"), first);
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_interned_string() {
+        let mut interner = Interner::new();
+
+        let index = interner.intern("counter");
+
+        assert_eq!(interner.resolve(index), "counter");
+        assert_eq!(interner.get("counter"), Some(index));
+        assert_eq!(interner.get("unknown"), None);
+    }
+}
@@ -1,6 +1,13 @@
 pub mod value;
 pub mod object;
 pub mod bytecode;
+pub mod interner;
 pub mod debug;
+pub mod assembler;
+pub mod verifier;
 pub mod compiler;
-pub mod vm;
\ No newline at end of file
+pub mod serialize;
+pub mod vm;
+pub mod debugger;
+pub mod profiler;
+pub mod coverage;
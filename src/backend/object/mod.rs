@@ -1,10 +1,14 @@
+use std::{fmt, rc::Rc};
+
 use super::{bytecode::Chunk, value::Value};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Object {
     String(StringObject),
     Array(ArrayObject),
+    Struct(StructObject),
     Function(FunctionObject),
+    Native(NativeObject),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -18,6 +22,20 @@ pub struct ArrayObject {
     pub elements: Vec<Value>,
 }
 
+// Fields are addressed by slot, the layout names them for printing
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructObject {
+    pub layout: Rc<StructLayout>,
+    pub fields: Vec<Value>,
+}
+
+// Shared by every instance built by the same literal
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructLayout {
+    pub name: String,
+    pub fields: Vec<String>, // In slot order
+}
+
 #[derive(Debug, Clone)]
 pub struct FunctionObject {
     pub arity: usize,
@@ -29,4 +47,36 @@ impl PartialEq for FunctionObject {
     fn eq(&self, _: &Self) -> bool {
         return false
     }
-}
\ No newline at end of file
+}
+
+pub type NativeFunction = Rc<dyn Fn(&[Value]) -> Result<Value, String>>;
+
+// Function implemented in Rust, it only exists at runtime and is never part of a chunk
+#[derive(Clone)]
+pub struct NativeObject {
+    pub arity: usize,
+    pub name: String,
+    pub function: NativeFunction,
+}
+
+impl NativeObject {
+    pub fn new(name: &str, arity: usize, function: impl Fn(&[Value]) -> Result<Value, String> + 'static) -> Self {
+        Self {
+            arity,
+            name: name.to_string(),
+            function: Rc::new(function),
+        }
+    }
+}
+
+impl fmt::Debug for NativeObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NativeObject {{ name: {:?}, arity: {} }}", self.name, self.arity)
+    }
+}
+
+impl PartialEq for NativeObject {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.function, &other.function)
+    }
+}
@@ -0,0 +1,221 @@
+pub mod tests;
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use crate::backend::{
+    bytecode::Instruction,
+    value::Value,
+    vm::{
+        hooks::{HookAction, Hooks},
+        RuntimeError,
+        VM,
+    },
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionProfile {
+    pub name: String,
+    pub calls: u64,
+    pub self_instructions: u64,
+    pub total_instructions: u64,
+    pub self_time: Duration,
+    pub total_time: Duration,
+}
+
+// A call still running, its totals are added to the function once it returns
+struct ActiveCall {
+    name: String,
+    started: Instant,
+    instructions: u64,
+    children_time: Duration,
+}
+
+// Instrumenting profiler, counting calls, instructions, time and line hits of every function.
+// Recursive calls only count once towards totals, so a total never exceeds the whole run.
+pub struct Profiler {
+    functions: HashMap<String, FunctionProfile>,
+    lines: HashMap<(String, usize), u64>,
+    stacks: HashMap<String, u64>,
+    calls: Vec<ActiveCall>,
+
+    // Current call stack joined with ';', the key of collapsed stacks
+    stack: String,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            functions: HashMap::new(),
+            lines: HashMap::new(),
+            stacks: HashMap::new(),
+            calls: vec![],
+            stack: String::new(),
+        }
+    }
+
+    // Functions sorted by self instructions, the most expensive first
+    pub fn functions(&self) -> Vec<&FunctionProfile> {
+        let mut functions: Vec<&FunctionProfile> = self.functions.values().collect();
+        functions.sort_by(|a, b| {
+            b.self_instructions.cmp(&a.self_instructions).then_with(|| a.name.cmp(&b.name))
+        });
+
+        functions
+    }
+
+    pub fn function(&self, name: &str) -> Option<&FunctionProfile> {
+        self.functions.get(name)
+    }
+
+    // (function, line, hits) sorted by hits, the hottest first
+    pub fn lines(&self) -> Vec<(&str, usize, u64)> {
+        let mut lines: Vec<(&str, usize, u64)> = self.lines.iter()
+            .map(|((name, line), hits)| (name.as_str(), *line, *hits))
+            .collect();
+
+        lines.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| (a.0, a.1).cmp(&(b.0, b.1))));
+        lines
+    }
+
+    pub fn report(&self) -> String {
+        let mut output = String::new();
+
+        let _ = writeln!(
+            output,
+            "{:<20} {:>8} {:>12} {:>12} {:>12} {:>12}",
+            "Function", "Calls", "Self instr", "Total instr", "Self ms", "Total ms"
+        );
+
+        for function in self.functions() {
+            let _ = writeln!(
+                output,
+                "{:<20} {:>8} {:>12} {:>12} {:>12.3} {:>12.3}",
+                function.name,
+                function.calls,
+                function.self_instructions,
+                function.total_instructions,
+                function.self_time.as_secs_f64() * 1000.0,
+                function.total_time.as_secs_f64() * 1000.0,
+            );
+        }
+
+        let _ = writeln!(output, "\n{:<20} {:>8} {:>12}", "Function", "Line", "Hits");
+        for (name, line, hits) in self.lines() {
+            let _ = writeln!(output, "{:<20} {:>8} {:>12}", name, line, hits);
+        }
+
+        output
+    }
+
+    // One "outer;inner count" line per call stack, counting instructions, as flamegraph tools expect
+    pub fn collapsed_stacks(&self) -> String {
+        let mut stacks: Vec<(&String, &u64)> = self.stacks.iter().collect();
+        stacks.sort();
+
+        let mut output = String::new();
+        for (stack, count) in stacks {
+            let _ = writeln!(output, "{stack} {count}");
+        }
+
+        output
+    }
+
+    fn enter(&mut self, name: &str) {
+        self.functions.entry(name.to_string())
+            .or_insert_with(|| FunctionProfile { name: name.to_string(), ..FunctionProfile::default() })
+            .calls += 1;
+
+        if !self.stack.is_empty() {
+            self.stack.push(';');
+        }
+        self.stack.push_str(name);
+
+        self.calls.push(ActiveCall {
+            name: name.to_string(),
+            started: Instant::now(),
+            instructions: 0,
+            children_time: Duration::ZERO,
+        });
+    }
+
+    fn leave(&mut self) {
+        let call = match self.calls.pop() {
+            Some(call) => call,
+            None => return,
+        };
+
+        let elapsed = call.started.elapsed();
+        let recursive = self.calls.iter().any(|active| active.name == call.name);
+
+        if let Some(function) = self.functions.get_mut(&call.name) {
+            function.self_time += elapsed.saturating_sub(call.children_time);
+            if !recursive {
+                function.total_instructions += call.instructions;
+                function.total_time += elapsed;
+            }
+        }
+
+        if let Some(parent) = self.calls.last_mut() {
+            parent.instructions += call.instructions;
+            parent.children_time += elapsed;
+        }
+
+        let length = self.stack.rfind(';').unwrap_or(0);
+        self.stack.truncate(length);
+    }
+}
+
+impl Hooks for Profiler {
+    fn before_instruction(&mut self, vm: &VM, offset: usize, _instruction: &Instruction) -> HookAction {
+        let frame = match vm.current_frame() {
+            Some(frame) => frame,
+            None => return HookAction::Continue,
+        };
+
+        let name = &frame.function.name;
+        if let Some(function) = self.functions.get_mut(name) {
+            function.self_instructions += 1;
+        }
+
+        if let Some(call) = self.calls.last_mut() {
+            call.instructions += 1;
+        }
+
+        let line = frame.function.chunk.line(offset);
+        *self.lines.entry((name.clone(), line)).or_default() += 1;
+
+        match self.stacks.get_mut(&self.stack) {
+            Some(count) => *count += 1,
+            None => { self.stacks.insert(self.stack.clone(), 1); },
+        }
+
+        return HookAction::Continue
+    }
+
+    fn on_call(&mut self, vm: &VM) {
+        if let Some(frame) = vm.current_frame() {
+            self.enter(&frame.function.name);
+        }
+    }
+
+    fn on_return(&mut self, _vm: &VM, _value: &Value) {
+        self.leave();
+    }
+
+    // The program is over, calls still running end here
+    fn on_error(&mut self, _vm: &VM, _error: &RuntimeError) {
+        while !self.calls.is_empty() {
+            self.leave();
+        }
+    }
+}
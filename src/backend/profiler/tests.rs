@@ -0,0 +1,148 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::backend::{
+        bytecode::Chunk,
+        compiler::Compiler,
+        object::FunctionObject,
+        profiler::Profiler,
+        vm::{InterpretationResult, VM},
+    };
+
+    use crate::frontend::{
+        lexer::Lexer,
+        parser::{parse_file, Parser},
+        typecheck::check_program,
+    };
+
+    const PROGRAM: &str = "
+fn add(a: int, b: int) -> int {
+    return a + b;
+}
+
+fn sum(n: int) -> int {
+    if n == 0 {
+        return 0;
+    }
+
+    return add(n, sum(n - 1));
+}
+
+sum(3);
+";
+
+    fn compile(source: &str) -> FunctionObject {
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(&mut lexer);
+        let ast = parse_file(&mut parser);
+        check_program(&ast).unwrap();
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        let mut compiler = Compiler::new(function);
+        compiler.compile(&ast).clone()
+    }
+
+    fn profile(source: &str) -> (InterpretationResult, Rc<RefCell<Profiler>>) {
+        let mut function = compile(source);
+        let profiler = Rc::new(RefCell::new(Profiler::new()));
+
+        let mut vm = VM::new(&mut function);
+        vm.add_hooks(profiler.clone());
+        let result = vm.run();
+
+        return (result, profiler)
+    }
+
+    #[test]
+    fn test_call_and_instruction_counts() {
+        let (result, profiler) = profile(PROGRAM);
+        assert!(matches!(result, InterpretationResult::OK));
+
+        let profiler = profiler.borrow();
+        let global = profiler.function("Global").unwrap();
+        let sum = profiler.function("sum").unwrap();
+        let add = profiler.function("add").unwrap();
+
+        assert_eq!(global.calls, 1);
+        assert_eq!(sum.calls, 4);
+        assert_eq!(add.calls, 3);
+
+        // Every instruction runs in exactly one function
+        let executed: u64 = profiler.functions().iter().map(|function| function.self_instructions).sum();
+        assert_eq!(global.total_instructions, executed);
+
+        // Recursive calls don't count twice
+        assert_eq!(sum.total_instructions, executed - global.self_instructions);
+        assert!(add.total_instructions == add.self_instructions && add.self_instructions > 0);
+        assert!(global.total_time >= sum.total_time);
+    }
+
+    #[test]
+    fn test_line_hits() {
+        let (_, profiler) = profile(PROGRAM);
+        let profiler = profiler.borrow();
+        let lines = profiler.lines();
+
+        let hits = |name: &str, line: usize| {
+            lines.iter().find(|(function, at, _)| *function == name && *at == line).map(|(_, _, hits)| *hits)
+        };
+
+        // "return a + b;" runs GET_LOCAL, GET_LOCAL, ADD and RETURN on each of the 3 calls
+        assert_eq!(hits("add", 3), Some(12));
+        assert_eq!(hits("sum", 8), Some(2));
+
+        // The implicit return after an explicit one never runs
+        assert_eq!(hits("add", 4), None);
+        assert!(lines.windows(2).all(|pair| pair[0].2 >= pair[1].2));
+    }
+
+    #[test]
+    fn test_collapsed_stacks() {
+        let (_, profiler) = profile(PROGRAM);
+        let profiler = profiler.borrow();
+        let stacks = profiler.collapsed_stacks();
+
+        assert!(stacks.contains("\nGlobal;sum;sum;sum;sum "), "{stacks}");
+        assert!(stacks.contains("\nGlobal;sum;sum;sum;add "), "{stacks}");
+        assert!(stacks.starts_with("Global "), "{stacks}");
+
+        let counted: u64 = stacks.lines()
+            .map(|line| line.rsplit_once(' ').unwrap().1.parse::<u64>().unwrap())
+            .sum();
+        assert_eq!(counted, profiler.function("Global").unwrap().total_instructions);
+    }
+
+    #[test]
+    fn test_report_sorted() {
+        let (_, profiler) = profile(PROGRAM);
+        let report = profiler.borrow().report();
+
+        let rows: Vec<&str> = report.lines().skip(1).take(3).collect();
+        assert!(rows[0].starts_with("sum "), "{report}");
+        assert!(report.starts_with("Function"), "{report}");
+        assert!(report.contains("add                         3"), "{report}");
+    }
+
+    #[test]
+    fn test_calls_closed_on_error() {
+        let (result, profiler) = profile("
+fn get(values: [int]) -> int {
+    return values[3];
+}
+
+get([1]);
+");
+
+        assert!(matches!(result, InterpretationResult::RUNTIME_ERROR));
+
+        let profiler = profiler.borrow();
+        assert_eq!(profiler.function("get").unwrap().calls, 1);
+        assert!(profiler.function("Global").unwrap().total_instructions > 0);
+    }
+}
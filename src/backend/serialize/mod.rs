@@ -0,0 +1,453 @@
+pub mod tests;
+
+use std::{fmt, fs, path::Path};
+
+use super::{
+    bytecode::{Chunk, LocalVariable, Location, Span},
+    object::{ArrayObject, FunctionObject, Object, StringObject},
+    value::Value,
+};
+
+// Layout of a .silkc file:
+//   magic "SILK", format version (u16), top-level function, CRC-32 of everything before it (u32)
+// Integers are little endian, strings and lists are prefixed by their length (u32).
+pub const MAGIC: &[u8; 4] = b"SILK";
+pub const FORMAT_VERSION: u16 = 4;
+pub const EXTENSION: &str = "silkc";
+
+const TAG_F64: u8 = 0;
+const TAG_BOOLEAN: u8 = 1;
+const TAG_VOID: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_ARRAY: u8 = 4;
+const TAG_FUNCTION: u8 = 5;
+
+// Arrays and functions nested deeper than this are rejected, instead of overflowing the stack
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    Io(String),
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    InvalidChecksum { expected: u32, found: u32 },
+    UnexpectedEnd { offset: usize },
+    InvalidValueTag { offset: usize, tag: u8 },
+    InvalidString { offset: usize },
+    UnorderedLocations { offset: usize },
+    TooDeep { offset: usize },
+    TrailingBytes { offset: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "Couldn't read compiled file: {error}"),
+            LoadError::InvalidMagic => write!(f, "Not a compiled silk file"),
+            LoadError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported format version {}, expected {}", version, FORMAT_VERSION)
+            },
+            LoadError::InvalidChecksum { expected, found } => {
+                write!(f, "Corrupted file: checksum is {:#010x}, expected {:#010x}", found, expected)
+            },
+            LoadError::UnexpectedEnd { offset } => write!(f, "Unexpected end of file at byte {offset}"),
+            LoadError::InvalidValueTag { offset, tag } => {
+                write!(f, "Invalid constant tag {} at byte {}", tag, offset)
+            },
+            LoadError::InvalidString { offset } => write!(f, "Invalid UTF-8 string at byte {offset}"),
+            LoadError::UnorderedLocations { offset } => {
+                write!(f, "Locations out of order at byte {offset}, code offsets must increase")
+            },
+            LoadError::TooDeep { offset } => {
+                write!(f, "Constant at byte {} is nested deeper than {} levels", offset, MAX_DEPTH)
+            },
+            LoadError::TrailingBytes { offset } => write!(f, "Unexpected data after the program at byte {offset}"),
+        }
+    }
+}
+
+// Values only built at runtime have no encoding
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveError {
+    Struct,
+    Native(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Struct => write!(f, "Structs can't be saved, they are only built at runtime"),
+            SaveError::Native(name) => {
+                write!(f, "Native function '{name}' can't be saved, natives only exist at runtime")
+            },
+        }
+    }
+}
+
+pub fn save(function: &FunctionObject) -> Result<Vec<u8>, SaveError> {
+    let mut writer = Writer { bytes: vec![] };
+
+    writer.bytes.extend_from_slice(MAGIC);
+    writer.write_u16(FORMAT_VERSION);
+    writer.write_function(function)?;
+
+    let checksum = crc32(&writer.bytes);
+    writer.write_u32(checksum);
+
+    return Ok(writer.bytes)
+}
+
+pub fn load(bytes: &[u8]) -> Result<FunctionObject, LoadError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(LoadError::InvalidMagic);
+    }
+
+    let mut reader = Reader {
+        bytes,
+        offset: MAGIC.len(),
+        depth: 0,
+    };
+
+    let version = reader.read_u16()?;
+    if version != FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+
+    if bytes.len() < reader.offset + 4 {
+        return Err(LoadError::UnexpectedEnd { offset: bytes.len() });
+    }
+
+    let payload_end = bytes.len() - 4;
+    let expected = u32::from_le_bytes([
+        bytes[payload_end],
+        bytes[payload_end + 1],
+        bytes[payload_end + 2],
+        bytes[payload_end + 3],
+    ]);
+
+    let found = crc32(&bytes[..payload_end]);
+    if expected != found {
+        return Err(LoadError::InvalidChecksum { expected, found });
+    }
+
+    reader.bytes = &bytes[..payload_end];
+    let function = reader.read_function()?;
+
+    if reader.offset != payload_end {
+        return Err(LoadError::TrailingBytes { offset: reader.offset });
+    }
+
+    return Ok(function)
+}
+
+pub fn save_file(path: &Path, function: &FunctionObject) -> Result<(), String> {
+    let bytes = save(function).map_err(|error| error.to_string())?;
+
+    match fs::write(path, bytes) {
+        Ok(()) => Ok(()),
+        Err(error) => Err(format!("Couldn't write {}: {error}", path.display())),
+    }
+}
+
+pub fn load_file(path: &Path) -> Result<FunctionObject, LoadError> {
+    match fs::read(path) {
+        Ok(bytes) => load(&bytes),
+        Err(error) => Err(LoadError::Io(error.to_string())),
+    }
+}
+
+pub fn is_compiled(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_length(&mut self, length: usize) {
+        if length > u32::MAX as usize {
+            panic!("Cannot serialize more than {} items", u32::MAX);
+        }
+
+        self.write_u32(length as u32);
+    }
+
+    fn write_string(&mut self, value: &str) {
+        self.write_length(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn write_location(&mut self, location: Location) {
+        self.write_length(location.line);
+        self.write_length(location.column);
+    }
+
+    fn write_function(&mut self, function: &FunctionObject) -> Result<(), SaveError> {
+        self.write_string(&function.name);
+        self.write_length(function.arity);
+        self.write_chunk(&function.chunk)
+    }
+
+    fn write_chunk(&mut self, chunk: &Chunk) -> Result<(), SaveError> {
+        self.write_length(chunk.code.len());
+        self.bytes.extend_from_slice(&chunk.code);
+
+        // Locations are stored as (offset, line, column) runs
+        let runs = chunk.locations.runs();
+        self.write_length(runs.len());
+        for run in runs {
+            self.write_length(run.offset);
+            self.write_location(run.location);
+        }
+
+        self.write_location(chunk.span.start);
+        self.write_location(chunk.span.end);
+
+        self.write_length(chunk.contants.len());
+        for constant in &chunk.contants {
+            self.write_value(constant)?;
+        }
+
+        self.write_length(chunk.globals.len());
+        for global in &chunk.globals {
+            self.write_string(global);
+        }
+
+        self.write_length(chunk.locals.len());
+        for local in &chunk.locals {
+            self.write_string(&local.name);
+            self.write_length(local.slot);
+            self.write_length(local.start);
+            self.write_length(local.end);
+        }
+
+        return Ok(())
+    }
+
+    fn write_value(&mut self, value: &Value) -> Result<(), SaveError> {
+        match value {
+            Value::F64(value) => {
+                self.write_u8(TAG_F64);
+                self.bytes.extend_from_slice(&value.to_le_bytes());
+            },
+            Value::Boolean(value) => {
+                self.write_u8(TAG_BOOLEAN);
+                self.write_u8(*value as u8);
+            },
+            Value::Void => self.write_u8(TAG_VOID),
+            Value::Object(Object::String(string)) => {
+                self.write_u8(TAG_STRING);
+                self.write_string(&string.value);
+            },
+            Value::Object(Object::Array(array)) => {
+                self.write_u8(TAG_ARRAY);
+                self.write_length(array.elements.len());
+                for element in &array.elements {
+                    self.write_value(element)?;
+                }
+            },
+            Value::Object(Object::Function(function)) => {
+                self.write_u8(TAG_FUNCTION);
+                self.write_function(function)?;
+            },
+            Value::Object(Object::Struct(_)) => return Err(SaveError::Struct),
+            Value::Object(Object::Native(native)) => return Err(SaveError::Native(native.name.clone())),
+        }
+
+        return Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    depth: usize, // Arrays and functions being read
+}
+
+impl Reader<'_> {
+    fn read_bytes(&mut self, length: usize) -> Result<&[u8], LoadError> {
+        if self.offset + length > self.bytes.len() {
+            return Err(LoadError::UnexpectedEnd { offset: self.bytes.len() });
+        }
+
+        let bytes = &self.bytes[self.offset..self.offset + length];
+        self.offset += length;
+
+        return Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, LoadError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, LoadError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_length(&mut self) -> Result<usize, LoadError> {
+        Ok(self.read_u32()? as usize)
+    }
+
+    fn read_string(&mut self) -> Result<String, LoadError> {
+        let length = self.read_length()?;
+        let offset = self.offset;
+
+        match String::from_utf8(self.read_bytes(length)?.to_vec()) {
+            Ok(string) => Ok(string),
+            Err(_) => Err(LoadError::InvalidString { offset }),
+        }
+    }
+
+    fn read_location(&mut self) -> Result<Location, LoadError> {
+        let line = self.read_length()?;
+        let column = self.read_length()?;
+
+        return Ok(Location::new(line, column))
+    }
+
+    fn read_function(&mut self) -> Result<FunctionObject, LoadError> {
+        let name = self.read_string()?;
+        let arity = self.read_length()?;
+        let chunk = self.read_chunk()?;
+
+        return Ok(FunctionObject {
+            arity,
+            chunk,
+            name,
+        })
+    }
+
+    fn read_chunk(&mut self) -> Result<Chunk, LoadError> {
+        let mut chunk = Chunk::new();
+
+        let code_length = self.read_length()?;
+        chunk.code = self.read_bytes(code_length)?.to_vec();
+
+        // Locations are looked up by binary search, so runs must be sorted
+        let runs_count = self.read_length()?;
+        let mut previous = None;
+        for _ in 0..runs_count {
+            let position = self.offset;
+            let offset = self.read_length()?;
+            if previous.is_some_and(|previous| offset <= previous) {
+                return Err(LoadError::UnorderedLocations { offset: position });
+            }
+
+            let location = self.read_location()?;
+            chunk.locations.push(offset, location);
+            previous = Some(offset);
+        }
+
+        chunk.span = Span {
+            start: self.read_location()?,
+            end: self.read_location()?,
+        };
+
+        let constants_count = self.read_length()?;
+        for _ in 0..constants_count {
+            let constant = self.read_value()?;
+            chunk.contants.push(constant);
+        }
+
+        let globals_count = self.read_length()?;
+        for _ in 0..globals_count {
+            let global = self.read_string()?;
+            chunk.globals.push(global);
+        }
+
+        let locals_count = self.read_length()?;
+        for _ in 0..locals_count {
+            let local = LocalVariable {
+                name: self.read_string()?,
+                slot: self.read_length()?,
+                start: self.read_length()?,
+                end: self.read_length()?,
+            };
+
+            chunk.locals.push(local);
+        }
+
+        return Ok(chunk)
+    }
+
+    fn read_value(&mut self) -> Result<Value, LoadError> {
+        let offset = self.offset;
+
+        if self.depth >= MAX_DEPTH {
+            return Err(LoadError::TooDeep { offset });
+        }
+
+        self.depth += 1;
+        let value = self.read_tagged_value(offset);
+        self.depth -= 1;
+
+        return value
+    }
+
+    fn read_tagged_value(&mut self, offset: usize) -> Result<Value, LoadError> {
+        let value = match self.read_u8()? {
+            TAG_F64 => {
+                let bytes = self.read_bytes(8)?;
+                let mut buffer = [0; 8];
+                buffer.copy_from_slice(bytes);
+                Value::F64(f64::from_le_bytes(buffer))
+            },
+            TAG_BOOLEAN => Value::Boolean(self.read_u8()? != 0),
+            TAG_VOID => Value::Void,
+            TAG_STRING => {
+                let value = self.read_string()?;
+                Value::Object(Object::String(StringObject {
+                    length: value.len(),
+                    value,
+                }))
+            },
+            TAG_ARRAY => {
+                let length = self.read_length()?;
+                let mut elements = vec![];
+                for _ in 0..length {
+                    elements.push(self.read_value()?);
+                }
+
+                Value::Object(Object::Array(ArrayObject { elements }))
+            },
+            TAG_FUNCTION => Value::Object(Object::Function(self.read_function()?)),
+            tag => return Err(LoadError::InvalidValueTag { offset, tag }),
+        };
+
+        return Ok(value)
+    }
+}
+
+// CRC-32 (IEEE 802.3), computed bit by bit
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    return !crc
+}
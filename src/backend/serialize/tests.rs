@@ -0,0 +1,144 @@
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::backend::{
+        bytecode::{Chunk, Location},
+        compiler::Compiler,
+        object::{ArrayObject, FunctionObject, Object, StructLayout, StructObject},
+        serialize::{crc32, load, save, LoadError, SaveError, FORMAT_VERSION, MAX_DEPTH},
+        value::Value,
+        vm::{InterpretationResult, VM},
+    };
+
+    use crate::frontend::{
+        lexer::Lexer,
+        parser::{parse_file, Parser},
+        typecheck::check_program,
+    };
+
+    fn compile(source: &str) -> FunctionObject {
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(&mut lexer);
+
+        let ast = parse_file(&mut parser);
+        check_program(&ast).unwrap();
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        let mut compiler = Compiler::new(function);
+        compiler.compile(&ast).clone()
+    }
+
+    const PROGRAM: &str = "
+        fn sum(values: [int]) -> int {
+            return values[0] + values[1];
+        }
+
+        {
+            let name = \"silk\";
+            let flag = true;
+            if flag {
+                sum([1, 2]);
+            };
+        }
+    ";
+
+    #[test]
+    fn test_save_load_round_trip() {
+        let function = compile(PROGRAM);
+        let bytes = save(&function).unwrap();
+
+        let loaded = match load(&bytes) {
+            Ok(loaded) => loaded,
+            Err(error) => panic!("{error}"),
+        };
+
+        assert_eq!(loaded.name, function.name);
+        assert_eq!(loaded.arity, function.arity);
+        assert_eq!(loaded.chunk.code, function.chunk.code);
+        assert_eq!(loaded.chunk.locations, function.chunk.locations);
+        assert_eq!(loaded.chunk.span, function.chunk.span);
+        assert_eq!(loaded.chunk.globals, function.chunk.globals);
+        assert_eq!(loaded.chunk.contants.len(), function.chunk.contants.len());
+
+        let nested = loaded.chunk.contants.iter().find_map(|constant| match constant {
+            Value::Object(Object::Function(function)) => Some(function),
+            _ => None,
+        });
+        match nested {
+            Some(nested) => {
+                assert_eq!(nested.name, "sum");
+                assert_eq!(nested.arity, 1);
+            },
+            None => panic!("Expected nested function constant"),
+        }
+
+        // Saving the loaded program must produce the exact same bytes
+        assert_eq!(save(&loaded).unwrap(), bytes);
+
+        let mut loaded = loaded;
+        let mut vm = VM::new(&mut loaded);
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_files() {
+        let bytes = save(&compile("1 + 2;")).unwrap();
+
+        assert_eq!(load(b"NOPE"), Err(LoadError::InvalidMagic));
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(load(&wrong_version), Err(LoadError::UnsupportedVersion(FORMAT_VERSION + 1)));
+
+        let mut corrupted = bytes.clone();
+        corrupted[10] ^= 0xFF;
+        assert!(matches!(load(&corrupted), Err(LoadError::InvalidChecksum { .. })));
+
+        let truncated = &bytes[..5];
+        assert!(matches!(load(truncated), Err(LoadError::UnexpectedEnd { .. })));
+    }
+
+    #[test]
+    fn test_load_rejects_crafted_chunks() {
+        let mut chunk = Chunk::new();
+        chunk.locations.push(4, Location::new(2, 1));
+        chunk.locations.push(2, Location::new(1, 1));
+        let function = FunctionObject { chunk, arity: 0, name: String::from("Global") };
+        assert!(matches!(load(&save(&function).unwrap()), Err(LoadError::UnorderedLocations { .. })));
+
+        let mut value = Value::Void;
+        for _ in 0..MAX_DEPTH {
+            value = Value::Object(Object::Array(ArrayObject { elements: vec![value] }));
+        }
+
+        let mut function = FunctionObject { chunk: Chunk::new(), arity: 0, name: String::from("Global") };
+        function.chunk.contants.push(value);
+        assert!(matches!(load(&save(&function).unwrap()), Err(LoadError::TooDeep { .. })));
+    }
+
+    #[test]
+    fn test_save_rejects_runtime_values() {
+        let mut function = FunctionObject { chunk: Chunk::new(), arity: 0, name: String::from("Global") };
+        function.chunk.contants.push(Value::Object(Object::Array(ArrayObject {
+            elements: vec![Value::Object(Object::Struct(StructObject {
+                layout: Rc::new(StructLayout { name: String::from("Point"), fields: vec![] }),
+                fields: vec![],
+            }))],
+        })));
+
+        assert_eq!(save(&function), Err(SaveError::Struct));
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+}
@@ -5,6 +5,7 @@ pub enum Value {
     F64(f64),
     Boolean(bool),
     Object(Object),
+    Void,
 }
 
 impl Default for Value {
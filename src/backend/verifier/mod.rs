@@ -0,0 +1,302 @@
+pub mod tests;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use super::{
+    bytecode::{DecodeError, Instruction, OperandKind},
+    object::{FunctionObject, Object},
+    value::Value,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationErrorKind {
+    Decode(DecodeError),
+    JumpOutOfBounds { target: isize },
+    JumpInsideInstruction { target: usize },
+    ConstantOutOfRange { index: usize, constants: usize },
+    GlobalOutOfRange { slot: usize, globals: usize },
+    UninitializedLocal { slot: usize },
+    StackUnderflow { height: usize, popped: usize },
+    StackMismatch { expected: usize, found: usize },
+    MissingReturn,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationError {
+    pub function: String,
+    pub offset: usize,
+    pub kind: VerificationErrorKind,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid bytecode in '{}' at offset {}: ", self.function, self.offset)?;
+
+        match &self.kind {
+            VerificationErrorKind::Decode(error) => write!(f, "{error}"),
+            VerificationErrorKind::JumpOutOfBounds { target } => {
+                write!(f, "jump target {} is outside of the chunk", target)
+            },
+            VerificationErrorKind::JumpInsideInstruction { target } => {
+                write!(f, "jump target {} is not the start of an instruction", target)
+            },
+            VerificationErrorKind::ConstantOutOfRange { index, constants } => {
+                write!(f, "constant {} is out of range, the chunk has {} constants", index, constants)
+            },
+            VerificationErrorKind::GlobalOutOfRange { slot, globals } => {
+                write!(f, "global slot {} is out of range, the program has {} globals", slot, globals)
+            },
+            VerificationErrorKind::UninitializedLocal { slot } => {
+                write!(f, "local slot {} may be read before being set", slot)
+            },
+            VerificationErrorKind::StackUnderflow { height, popped } => {
+                write!(f, "instruction pops {} values, but the stack only holds {}", popped, height)
+            },
+            VerificationErrorKind::StackMismatch { expected, found } => {
+                write!(f, "stack height is {} on one path and {} on another", expected, found)
+            },
+            VerificationErrorKind::MissingReturn => {
+                write!(f, "execution can run past the end of the chunk")
+            },
+        }
+    }
+}
+
+// Verifies the top-level function and every function nested in its constants
+pub fn verify_program(function: &FunctionObject) -> Result<(), VerificationError> {
+    verify_function(function, function.chunk.globals.len())
+}
+
+pub fn verify_function(function: &FunctionObject, globals: usize) -> Result<(), VerificationError> {
+    let verifier = Verifier {
+        function,
+        globals,
+    };
+
+    verifier.verify()?;
+
+    for constant in &function.chunk.contants {
+        if let Value::Object(Object::Function(nested)) = constant {
+            verify_function(nested, globals)?;
+        }
+    }
+
+    return Ok(())
+}
+
+// Set of local slots that are initialized
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Slots([u64; 4]);
+
+impl Slots {
+    fn with_parameters(arity: usize) -> Self {
+        let mut slots = Slots([0; 4]);
+        for slot in 0..arity.min(256) {
+            slots.insert(slot);
+        }
+
+        return slots
+    }
+
+    fn insert(&mut self, slot: usize) {
+        self.0[slot / 64] |= 1 << (slot % 64);
+    }
+
+    fn contains(&self, slot: usize) -> bool {
+        self.0[slot / 64] & (1 << (slot % 64)) != 0
+    }
+
+    fn intersect(&self, other: &Slots) -> Slots {
+        let mut slots = *self;
+        for index in 0..4 {
+            slots.0[index] &= other.0[index];
+        }
+
+        return slots
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct State {
+    height: usize,
+    slots: Slots,
+}
+
+struct Verifier<'a> {
+    function: &'a FunctionObject,
+    globals: usize,
+}
+
+impl Verifier<'_> {
+
+    fn verify(&self) -> Result<(), VerificationError> {
+        let instructions = self.decode()?;
+
+        for (offset, instruction) in &instructions {
+            self.check_operand(*offset, instruction, &instructions)?;
+        }
+
+        self.check_flow(&instructions)
+    }
+
+    fn decode(&self) -> Result<BTreeMap<usize, Instruction>, VerificationError> {
+        let mut instructions = BTreeMap::new();
+
+        for result in self.function.chunk.instructions() {
+            match result {
+                Ok((offset, instruction)) => {
+                    instructions.insert(offset, instruction);
+                },
+                Err(error) => {
+                    let offset = match &error {
+                        DecodeError::UnknownOperation { offset, .. } => *offset,
+                        DecodeError::TruncatedOperand { offset, .. } => *offset,
+                    };
+
+                    return Err(self.error(offset, VerificationErrorKind::Decode(error)));
+                },
+            }
+        }
+
+        return Ok(instructions)
+    }
+
+    fn check_operand(
+        &self,
+        offset: usize,
+        instruction: &Instruction,
+        instructions: &BTreeMap<usize, Instruction>
+    ) -> Result<(), VerificationError> {
+        let chunk = &self.function.chunk;
+
+        match instruction.operand() {
+            Some((OperandKind::Constant, index)) if index >= chunk.contants.len() => {
+                return Err(self.error(offset, VerificationErrorKind::ConstantOutOfRange {
+                    index,
+                    constants: chunk.contants.len()
+                }));
+            },
+            Some((OperandKind::Global, slot)) if slot >= self.globals => {
+                return Err(self.error(offset, VerificationErrorKind::GlobalOutOfRange {
+                    slot,
+                    globals: self.globals
+                }));
+            },
+            Some((OperandKind::Jump | OperandKind::Loop, _)) => {
+                let target = instruction.jump_target(offset).unwrap_or_default();
+                if target < 0 || target as usize >= chunk.code.len() {
+                    return Err(self.error(offset, VerificationErrorKind::JumpOutOfBounds { target }));
+                }
+
+                if !instructions.contains_key(&(target as usize)) {
+                    return Err(self.error(offset, VerificationErrorKind::JumpInsideInstruction {
+                        target: target as usize
+                    }));
+                }
+            },
+            _ => {},
+        }
+
+        return Ok(())
+    }
+
+    // Walks every reachable path, checking the stack height and initialized locals
+    fn check_flow(&self, instructions: &BTreeMap<usize, Instruction>) -> Result<(), VerificationError> {
+        let code_length = self.function.chunk.code.len();
+        if code_length == 0 {
+            return Err(self.error(0, VerificationErrorKind::MissingReturn));
+        }
+
+        let mut states = HashMap::<usize, State>::new();
+        let mut pending = vec![(0, State {
+            height: 0,
+            slots: Slots::with_parameters(self.function.arity),
+        })];
+
+        while let Some((offset, incoming)) = pending.pop() {
+            let state = match states.get(&offset) {
+                Some(existing) => {
+                    if existing.height != incoming.height {
+                        return Err(self.error(offset, VerificationErrorKind::StackMismatch {
+                            expected: existing.height,
+                            found: incoming.height
+                        }));
+                    }
+
+                    let slots = existing.slots.intersect(&incoming.slots);
+                    if slots == existing.slots {
+                        continue;
+                    }
+
+                    State { height: existing.height, slots }
+                },
+                None => incoming,
+            };
+
+            states.insert(offset, state);
+
+            let instruction = instructions[&offset];
+            let outgoing = self.step(offset, &instruction, state)?;
+
+            let next = offset + instruction.width();
+            let successors = match instruction {
+                Instruction::Return => vec![],
+                Instruction::Jump { .. } | Instruction::Loop { .. } => {
+                    vec![instruction.jump_target(offset).unwrap_or_default() as usize]
+                },
+                Instruction::JumpIfFalse { .. } => {
+                    vec![next, instruction.jump_target(offset).unwrap_or_default() as usize]
+                },
+                _ => vec![next],
+            };
+
+            for successor in successors {
+                if successor >= code_length {
+                    return Err(self.error(offset, VerificationErrorKind::MissingReturn));
+                }
+
+                pending.push((successor, outgoing));
+            }
+        }
+
+        return Ok(())
+    }
+
+    fn step(&self, offset: usize, instruction: &Instruction, state: State) -> Result<State, VerificationError> {
+        let (popped, pushed) = instruction.stack_effect();
+        if state.height < popped {
+            return Err(self.error(offset, VerificationErrorKind::StackUnderflow {
+                height: state.height,
+                popped
+            }));
+        }
+
+        let mut slots = state.slots;
+        match instruction {
+            Instruction::GetLocal { slot } if !slots.contains(*slot as usize) => {
+                return Err(self.error(offset, VerificationErrorKind::UninitializedLocal {
+                    slot: *slot as usize
+                }));
+            },
+            Instruction::SetLocal { slot } => slots.insert(*slot as usize),
+            _ => {},
+        }
+
+        return Ok(State {
+            height: state.height - popped + pushed,
+            slots,
+        })
+    }
+
+    fn error(&self, offset: usize, kind: VerificationErrorKind) -> VerificationError {
+        VerificationError {
+            function: self.function.name.clone(),
+            offset,
+            kind,
+        }
+    }
+
+}
@@ -0,0 +1,185 @@
+#[cfg(test)]
+mod tests {
+    use crate::backend::{
+        bytecode::{Chunk, Instruction, Location},
+        compiler::Compiler,
+        object::FunctionObject,
+        value::Value,
+        verifier::{verify_program, VerificationErrorKind},
+        vm::{InterpretationResult, VM},
+    };
+
+    use crate::frontend::{
+        lexer::Lexer,
+        parser::{parse_file, Parser},
+        typecheck::check_program,
+    };
+
+    fn compile(source: &str) -> FunctionObject {
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(&mut lexer);
+
+        let ast = parse_file(&mut parser);
+        check_program(&ast).unwrap();
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        let mut compiler = Compiler::new(function);
+        compiler.compile(&ast).clone()
+    }
+
+    fn build(instructions: &[Instruction]) -> FunctionObject {
+        let mut chunk = Chunk::new();
+        for instruction in instructions {
+            chunk.write(*instruction, Location::new(1, 1));
+        }
+
+        FunctionObject {
+            chunk,
+            arity: 0,
+            name: String::from("Global"),
+        }
+    }
+
+    fn verification_error(function: &FunctionObject) -> VerificationErrorKind {
+        match verify_program(function) {
+            Ok(()) => panic!("Expected verification to fail"),
+            Err(error) => error.kind,
+        }
+    }
+
+    #[test]
+    fn test_verify_compiled_programs() {
+        let sources = [
+            "1 + 2;",
+            "true && (2 < 3);",
+            "
+                {
+                    let x = 0;
+                    while x < 10 {
+                        x = x + 1;
+                    };
+                }
+            ",
+            "
+                if false {
+                    true;
+                } else {
+                    if 2 > 1 {
+                        false;
+                    };
+                };
+            ",
+            "
+                fn add(a: int, b: int) -> int {
+                    return a + b;
+                }
+
+                add(1, 2);
+            ",
+        ];
+
+        for source in sources {
+            let function = compile(source);
+            if let Err(error) = verify_program(&function) {
+                panic!("{error}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_jump_targets() {
+        let function = build(&[
+            Instruction::Jump { offset: 40 },
+            Instruction::Void,
+            Instruction::Return,
+        ]);
+        assert_eq!(verification_error(&function), VerificationErrorKind::JumpOutOfBounds { target: 43 });
+
+        // Jumps in the middle of the CONSTANT instruction
+        let mut function = build(&[
+            Instruction::Jump { offset: 1 },
+            Instruction::Constant { index: 0 },
+            Instruction::Return,
+        ]);
+        function.chunk.contants.push(Value::F64(1.0));
+        assert_eq!(verification_error(&function), VerificationErrorKind::JumpInsideInstruction { target: 4 });
+    }
+
+    #[test]
+    fn test_verify_indices() {
+        let function = build(&[
+            Instruction::Constant { index: 2 },
+            Instruction::Return,
+        ]);
+        assert_eq!(
+            verification_error(&function),
+            VerificationErrorKind::ConstantOutOfRange { index: 2, constants: 0 }
+        );
+
+        let function = build(&[
+            Instruction::GetGlobal { slot: 0 },
+            Instruction::Return,
+        ]);
+        assert_eq!(
+            verification_error(&function),
+            VerificationErrorKind::GlobalOutOfRange { slot: 0, globals: 0 }
+        );
+
+        let function = build(&[
+            Instruction::GetLocal { slot: 3 },
+            Instruction::Return,
+        ]);
+        assert_eq!(verification_error(&function), VerificationErrorKind::UninitializedLocal { slot: 3 });
+    }
+
+    #[test]
+    fn test_verify_stack_height() {
+        let function = build(&[
+            Instruction::Add,
+            Instruction::Return,
+        ]);
+        assert_eq!(verification_error(&function), VerificationErrorKind::StackUnderflow { height: 0, popped: 2 });
+
+        // The then branch leaves two values, the else branch only one
+        let function = build(&[
+            Instruction::True,
+            Instruction::JumpIfFalse { offset: 2 },
+            Instruction::Void,
+            Instruction::Void,
+            Instruction::Return,
+        ]);
+        assert_eq!(
+            verification_error(&function),
+            VerificationErrorKind::StackMismatch { expected: 1, found: 3 }
+        );
+    }
+
+    #[test]
+    fn test_verify_missing_return() {
+        let function = build(&[
+            Instruction::Void,
+        ]);
+        assert_eq!(verification_error(&function), VerificationErrorKind::MissingReturn);
+    }
+
+    #[test]
+    fn test_run_keeps_verification_error() {
+        let mut function = build(&[
+            Instruction::Pop,
+            Instruction::Return,
+        ]);
+
+        let mut vm = VM::new(&mut function);
+        assert!(matches!(vm.run(), InterpretationResult::COMPILE_ERROR));
+
+        let error = vm.verification_error().expect("Expected a verification error");
+        assert_eq!(error.kind, VerificationErrorKind::StackUnderflow { height: 0, popped: 1 });
+        assert_eq!(vm.error(), None);
+    }
+
+}
@@ -0,0 +1,441 @@
+use std::{fmt, marker::PhantomData};
+
+use crate::backend::{
+    object::{ArrayObject, FunctionObject, NativeObject, Object, StringObject},
+    value::Value,
+};
+
+use crate::frontend::typecheck::{types::Type, FunctionSignature};
+
+use super::{memory::VALUE_SIZE, heap_size, InterpretationResult, RuntimeError, VM};
+
+#[derive(Debug)]
+pub enum CallError {
+    UndefinedFunction(String),
+    NotCallable(String),
+    Arity { function: String, expected: usize, found: usize },
+    Argument { function: String, index: usize, expected: String, found: String },
+    Type { expected: String, found: String },
+    Runtime(RuntimeError),
+    Stopped(InterpretationResult), // Out of fuel, past the deadline or interrupted by a hook
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::UndefinedFunction(name) => write!(f, "Undefined function '{name}'"),
+            CallError::NotCallable(name) => write!(f, "'{name}' is not a function"),
+            CallError::Arity { function, expected, found } => {
+                write!(f, "'{function}' expects {expected} arguments, instead got {found}")
+            },
+            CallError::Argument { function, index, expected, found } => {
+                write!(f, "Argument {} of '{function}' should be {expected}, instead got {found}", index + 1)
+            },
+            CallError::Type { expected, found } => write!(f, "Expected {expected}, instead got {found}"),
+            CallError::Runtime(error) => write!(f, "{error}"),
+            CallError::Stopped(result) => write!(f, "Call stopped before returning: {result:?}"),
+        }
+    }
+}
+
+// Converts Rust values to silk values
+pub trait ToValue {
+    fn to_value(self) -> Value;
+}
+
+// Converts silk values back to Rust values, failing when the types don't match
+pub trait FromValue: Sized {
+    fn from_value(value: Value) -> Result<Self, CallError>;
+}
+
+// Arguments of a call, a tuple of convertible values
+pub trait ToArguments {
+    fn to_arguments(self) -> Vec<Value>;
+}
+
+impl VM {
+    // Arguments passed to the global function are checked against its parameter types.
+    // Without a signature, as for programs loaded from compiled files, only the arity is checked.
+    pub fn set_signature(&mut self, signature: FunctionSignature) {
+        self.signatures.insert(signature.name.clone(), signature);
+    }
+
+    // Calls a global function once the program ran, converting its arguments and return value
+    pub fn call_function<R: FromValue>(&mut self, name: &str, arguments: impl ToArguments) -> Result<R, CallError> {
+        let value = self.call_global(name, arguments.to_arguments())?;
+        R::from_value(value)
+    }
+
+    pub fn call_global(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, CallError> {
+        let callee = match self.global(name) {
+            Some(callee) => callee.clone(),
+            None => return Err(CallError::UndefinedFunction(name.to_string())),
+        };
+
+        match callee {
+            Value::Object(Object::Function(_) | Object::Native(_)) => self.apply(callee, arguments),
+            _ => Err(CallError::NotCallable(name.to_string())),
+        }
+    }
+
+    // Runs a function value to completion, the VM is left as it was before the call, even on errors
+    pub fn apply(&mut self, callee: Value, arguments: Vec<Value>) -> Result<Value, CallError> {
+        let (name, arity) = match &callee {
+            Value::Object(Object::Function(function)) => (function.name.clone(), function.arity),
+            Value::Object(Object::Native(native)) => (native.name.clone(), native.arity),
+            value => return Err(CallError::NotCallable(type_name(value))),
+        };
+
+        if arguments.len() != arity {
+            return Err(CallError::Arity { function: name, expected: arity, found: arguments.len() });
+        }
+
+        if let Some(signature) = self.signature_of(&callee) {
+            for (index, (argument, parameter)) in arguments.iter().zip(&signature.parameters).enumerate() {
+                if !has_type(argument, parameter) {
+                    return Err(CallError::Argument {
+                        function: name,
+                        index,
+                        expected: parameter.to_string(),
+                        found: type_name(argument),
+                    });
+                }
+            }
+        }
+
+        let depth = self.frames_count;
+        let height = self.stack.len();
+        let exit_depth = self.exit_depth;
+
+        // The value of the program is kept for VM::result
+        let program_result = self.returned.take();
+
+        self.error = None;
+        self.exit_depth = depth;
+        let result = self.execute_call(callee, arguments);
+        self.exit_depth = exit_depth;
+
+        let returned = std::mem::replace(&mut self.returned, program_result);
+        let error = match result {
+            Ok(InterpretationResult::OK) => match returned {
+                Some(value) => return Ok(value),
+                // Natives return without a frame, leaving their result on the stack
+                None => return Ok(self.stack_pop()),
+            },
+            Ok(result) => CallError::Stopped(result),
+            Err(message) => {
+                let error = match self.current_frame().filter(|_| self.frames_count > depth) {
+                    Some(frame) => RuntimeError {
+                        message,
+                        function: frame.function.name.clone(),
+                        line: frame.line(),
+                    },
+                    None => RuntimeError { message, function: name, line: 0 },
+                };
+
+                self.set_error(error.clone());
+                CallError::Runtime(error)
+            },
+        };
+
+        self.unwind(depth, height);
+        return Err(error)
+    }
+
+    fn execute_call(&mut self, callee: Value, arguments: Vec<Value>) -> Result<InterpretationResult, String> {
+        let count = arguments.len();
+
+        self.stack_push(callee.clone())?;
+        for argument in arguments {
+            self.stack_push(argument)?;
+        }
+
+        self.call_value(callee, count as u8)?;
+        if self.frames_count == self.exit_depth {
+            return Ok(InterpretationResult::OK)
+        }
+
+        return self.execute()
+    }
+
+    // Signature of a global function, a function value is the global when it was declared at the same place
+    fn signature_of(&self, callee: &Value) -> Option<&FunctionSignature> {
+        let name = match callee {
+            Value::Object(Object::Function(function)) => &function.name,
+            Value::Object(Object::Native(native)) => &native.name,
+            _ => return None,
+        };
+
+        let is_global = match (callee, self.global(name)?) {
+            (Value::Object(Object::Function(function)), Value::Object(Object::Function(global))) => {
+                function.chunk.span == global.chunk.span
+            },
+            (Value::Object(Object::Native(native)), Value::Object(Object::Native(global))) => native == global,
+            _ => false,
+        };
+
+        match is_global {
+            true => self.signatures.get(name),
+            false => None,
+        }
+    }
+
+    // Drops the frames and values of a call that didn't return
+    fn unwind(&mut self, depth: usize, height: usize) {
+        while self.frames_count > depth {
+            if let Some(frame) = self.frames[self.frames_count - 1].take() {
+                for slot in &frame.slots {
+                    self.release(heap_size(slot), VALUE_SIZE);
+                }
+            }
+
+            self.frames_count -= 1;
+        }
+
+        while self.stack.len() > height {
+            self.stack_pop();
+        }
+    }
+}
+
+fn type_name(value: &Value) -> String {
+    let name = match value {
+        Value::F64(value) if value.fract() == 0.0 => "int",
+        Value::F64(_) => "number",
+        Value::Boolean(_) => "bool",
+        Value::Void => "void",
+        Value::Object(Object::String(_)) => "string",
+        Value::Object(Object::Array(_)) => "array",
+        Value::Object(Object::Struct(_)) => "struct",
+        Value::Object(Object::Function(_) | Object::Native(_)) => "function",
+    };
+
+    name.to_string()
+}
+
+// Whether a value can be passed where the type is expected
+fn has_type(value: &Value, expected: &Type) -> bool {
+    match (value, expected) {
+        (Value::F64(number), Type::Integer) => number.fract() == 0.0,
+        (Value::Boolean(_), Type::Boolean) | (Value::Void, Type::Void) => true,
+        (Value::Object(Object::String(_)), Type::String) => true,
+        (Value::Object(Object::Array(array)), Type::Array(element)) => {
+            array.elements.iter().all(|value| has_type(value, element))
+        },
+        (Value::Object(Object::Struct(structure)), Type::Struct(name)) => &structure.layout.name == name,
+        (Value::Object(Object::Function(function)), Type::Function(parameters, _)) => function.arity == parameters.len(),
+        (Value::Object(Object::Native(native)), Type::Function(parameters, _)) => native.arity == parameters.len(),
+        _ => false,
+    }
+}
+
+fn type_error(expected: &str, found: &Value) -> CallError {
+    CallError::Type { expected: expected.to_string(), found: type_name(found) }
+}
+
+// Conversions
+
+impl ToValue for Value {
+    fn to_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, CallError> {
+        Ok(value)
+    }
+}
+
+// Integers are stored as f64
+impl ToValue for i64 {
+    fn to_value(self) -> Value {
+        Value::F64(self as f64)
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Value) -> Result<Self, CallError> {
+        match value {
+            Value::F64(number) if number.fract() == 0.0 => Ok(number as i64),
+            value => Err(type_error("int", &value)),
+        }
+    }
+}
+
+impl ToValue for f64 {
+    fn to_value(self) -> Value {
+        Value::F64(self)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Result<Self, CallError> {
+        match value {
+            Value::F64(number) => Ok(number),
+            value => Err(type_error("number", &value)),
+        }
+    }
+}
+
+impl ToValue for bool {
+    fn to_value(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self, CallError> {
+        match value {
+            Value::Boolean(boolean) => Ok(boolean),
+            value => Err(type_error("bool", &value)),
+        }
+    }
+}
+
+impl ToValue for String {
+    fn to_value(self) -> Value {
+        Value::Object(Object::String(StringObject {
+            length: self.len(),
+            value: self,
+        }))
+    }
+}
+
+impl ToValue for &str {
+    fn to_value(self) -> Value {
+        self.to_string().to_value()
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, CallError> {
+        match value {
+            Value::Object(Object::String(string)) => Ok(string.value),
+            value => Err(type_error("string", &value)),
+        }
+    }
+}
+
+impl FromValue for () {
+    fn from_value(value: Value) -> Result<Self, CallError> {
+        match value {
+            Value::Void => Ok(()),
+            value => Err(type_error("void", &value)),
+        }
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(self) -> Value {
+        Value::Object(Object::Array(ArrayObject {
+            elements: self.into_iter().map(ToValue::to_value).collect(),
+        }))
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, CallError> {
+        match value {
+            Value::Object(Object::Array(array)) => array.elements.into_iter().map(T::from_value).collect(),
+            value => Err(type_error("array", &value)),
+        }
+    }
+}
+
+// Natives working on silk values directly, see NativeObject::new
+impl ToValue for NativeObject {
+    fn to_value(self) -> Value {
+        Value::Object(Object::Native(self))
+    }
+}
+
+// A Rust closure passed as a function, its arguments and result are converted like those of VM::call_function.
+// The argument types are part of the type, so Closure::new(|x: i64| x * 2) is a function of one int.
+pub struct Closure<F, Arguments> {
+    name: String,
+    function: F,
+    arguments: PhantomData<Arguments>,
+}
+
+impl<F, Arguments> Closure<F, Arguments> {
+    pub fn new(name: &str, function: F) -> Self {
+        Self {
+            name: name.to_string(),
+            function,
+            arguments: PhantomData,
+        }
+    }
+}
+
+macro_rules! closure_values {
+    ($( ($($name:ident),*) ),*) => {
+        $(
+            impl<F, R, $($name),*> ToValue for Closure<F, ($($name,)*)>
+            where
+                F: Fn($($name),*) -> R + 'static,
+                R: ToValue,
+                $($name: FromValue,)*
+            {
+                #[allow(non_snake_case, unused_mut, unused_variables)]
+                fn to_value(self) -> Value {
+                    let function = self.function;
+                    let arity = <[&str]>::len(&[$(stringify!($name)),*]);
+
+                    // The VM checked the arity before calling
+                    let native = NativeObject::new(&self.name, arity, move |arguments| {
+                        let mut arguments = arguments.iter().cloned();
+                        $(
+                            let value = arguments.next().unwrap_or(Value::Void);
+                            let $name = $name::from_value(value).map_err(|error| error.to_string())?;
+                        )*
+
+                        Ok(function($($name),*).to_value())
+                    });
+
+                    native.to_value()
+                }
+            }
+        )*
+    };
+}
+
+closure_values!((), (A), (A, B), (A, B, C), (A, B, C, D), (A, B, C, D, E));
+
+impl ToValue for FunctionObject {
+    fn to_value(self) -> Value {
+        Value::Object(Object::Function(self))
+    }
+}
+
+// Functions returned by silk code can be called back with VM::apply
+impl FromValue for FunctionObject {
+    fn from_value(value: Value) -> Result<Self, CallError> {
+        match value {
+            Value::Object(Object::Function(function)) => Ok(function),
+            value => Err(type_error("function", &value)),
+        }
+    }
+}
+
+impl ToArguments for Vec<Value> {
+    fn to_arguments(self) -> Vec<Value> {
+        self
+    }
+}
+
+macro_rules! tuple_arguments {
+    ($( ($($name:ident),*) ),*) => {
+        $(
+            impl<$($name: ToValue),*> ToArguments for ($($name,)*) {
+                #[allow(non_snake_case)]
+                fn to_arguments(self) -> Vec<Value> {
+                    let ($($name,)*) = self;
+                    vec![$($name.to_value()),*]
+                }
+            }
+        )*
+    };
+}
+
+tuple_arguments!((), (A), (A, B), (A, B, C), (A, B, C, D), (A, B, C, D, E));
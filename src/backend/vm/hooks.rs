@@ -0,0 +1,112 @@
+use std::{cell::RefCell, io::{self, Write}, rc::Rc};
+
+use crate::backend::{
+    bytecode::Instruction,
+    debug::disassemble_instruction,
+    value::Value,
+};
+
+use super::{RuntimeError, VM};
+
+// Returned before each instruction, Stop interrupts the run before the instruction executes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookAction {
+    Continue,
+    Stop,
+}
+
+// Callbacks the VM invokes while running, registered with VM::add_hooks.
+// Every callback has a no-op default, so a hook only implements what it needs.
+pub trait Hooks {
+    // Called before the instruction at `offset` of the current frame is executed
+    fn before_instruction(&mut self, _vm: &VM, _offset: usize, _instruction: &Instruction) -> HookAction {
+        HookAction::Continue
+    }
+
+    // Called once the callee frame is pushed, including the top-level frame when a run starts
+    fn on_call(&mut self, _vm: &VM) {}
+
+    // Called before the current frame is popped, with its return value
+    fn on_return(&mut self, _vm: &VM, _value: &Value) {}
+
+    // Called when a runtime error stops the program, frames are left as they were
+    fn on_error(&mut self, _vm: &VM, _error: &RuntimeError) {}
+}
+
+// Lets callers keep a handle on a hook, to read its results once the VM is done
+impl<T: Hooks> Hooks for Rc<RefCell<T>> {
+    fn before_instruction(&mut self, vm: &VM, offset: usize, instruction: &Instruction) -> HookAction {
+        self.borrow_mut().before_instruction(vm, offset, instruction)
+    }
+
+    fn on_call(&mut self, vm: &VM) {
+        self.borrow_mut().on_call(vm)
+    }
+
+    fn on_return(&mut self, vm: &VM, value: &Value) {
+        self.borrow_mut().on_return(vm, value)
+    }
+
+    fn on_error(&mut self, vm: &VM, error: &RuntimeError) {
+        self.borrow_mut().on_error(vm, error)
+    }
+}
+
+// Prints every executed instruction, calls and returns, and optionally the stack
+pub struct Tracer<W: Write> {
+    pub output: W,
+    pub trace_stack: bool,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(output: W, trace_stack: bool) -> Self {
+        Self {
+            output,
+            trace_stack,
+        }
+    }
+}
+
+impl Tracer<io::Stdout> {
+    pub fn stdout(trace_stack: bool) -> Self {
+        Self::new(io::stdout(), trace_stack)
+    }
+}
+
+// Trace output is best effort, write errors are ignored
+impl<W: Write> Hooks for Tracer<W> {
+    fn before_instruction(&mut self, vm: &VM, offset: usize, _instruction: &Instruction) -> HookAction {
+        let frame = match vm.current_frame() {
+            Some(frame) => frame,
+            None => return HookAction::Continue,
+        };
+
+        if self.trace_stack {
+            let values: Vec<String> = vm.stack().iter().map(|value| format!("[ {:?} ]", value)).collect();
+            let _ = writeln!(self.output, "          {}", values.join(""));
+        }
+
+        let _ = match disassemble_instruction(&frame.function.chunk, offset) {
+            Ok(instruction) => writeln!(self.output, "{:<10}{instruction}", frame.function.name),
+            Err(error) => writeln!(self.output, "{:<10}{error}", frame.function.name),
+        };
+
+        return HookAction::Continue
+    }
+
+    fn on_call(&mut self, vm: &VM) {
+        if let Some(frame) = vm.current_frame() {
+            let _ = writeln!(self.output, "=> call {} with {:?}", frame.function.name, frame.slots);
+        }
+    }
+
+    fn on_return(&mut self, vm: &VM, value: &Value) {
+        if let Some(frame) = vm.current_frame() {
+            let _ = writeln!(self.output, "<= return {} with {:?}", frame.function.name, value);
+        }
+    }
+
+    fn on_error(&mut self, _vm: &VM, error: &RuntimeError) {
+        let _ = writeln!(self.output, "!! {error}");
+    }
+}
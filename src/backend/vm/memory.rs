@@ -0,0 +1,52 @@
+use std::{fmt, mem};
+
+use crate::backend::{
+    bytecode::Chunk,
+    object::Object,
+    value::Value,
+};
+
+// Size of a value stored on the stack, in a frame slot or in a global
+pub const VALUE_SIZE: usize = mem::size_of::<Value>();
+
+// Bytes held by a running program, values are copied so every copy counts
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemoryUsage {
+    pub heap: usize, // Strings, arrays, structs and functions, and globals
+    pub stack: usize, // Stack values and frame slots
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.heap + self.stack
+    }
+}
+
+impl fmt::Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes ({} heap, {} stack)", self.total(), self.heap, self.stack)
+    }
+}
+
+// Bytes a value owns outside of its own slot
+pub fn heap_size(value: &Value) -> usize {
+    match value {
+        Value::Object(Object::String(string)) => string.value.len(),
+        Value::Object(Object::Array(array)) => {
+            array.elements.iter().map(|element| VALUE_SIZE + heap_size(element)).sum()
+        },
+        // The layout is shared with the literal's constant, it isn't counted again
+        Value::Object(Object::Struct(structure)) => {
+            structure.fields.iter().map(|field| VALUE_SIZE + heap_size(field)).sum()
+        },
+        Value::Object(Object::Function(function)) => function.name.len() + chunk_size(&function.chunk),
+        Value::Object(Object::Native(native)) => native.name.len(),
+        _ => 0,
+    }
+}
+
+// Functions are copied along with their chunk, only code and constants are counted
+fn chunk_size(chunk: &Chunk) -> usize {
+    let constants: usize = chunk.contants.iter().map(|constant| VALUE_SIZE + heap_size(constant)).sum();
+    chunk.code.len() + constants
+}
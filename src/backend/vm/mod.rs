@@ -1,34 +1,77 @@
 pub mod tests;
+pub mod hooks;
+pub mod memory;
+pub mod embed;
 
-use std::{
-    array, 
-    collections::HashMap
-};
+use std::{array, collections::HashMap, fmt, rc::Rc, time::Instant};
 
 use super::{
-    bytecode::OperationCode, 
-    debug::disassemble_instruction, 
-    object::{self, FunctionObject, Object}, 
-    value::Value
+    bytecode::{Instruction, Location},
+    object::{self, FunctionObject, NativeObject, Object},
+    value::Value,
+    verifier::{verify_program, VerificationError},
 };
 
+use crate::frontend::typecheck::FunctionSignature;
+
+use hooks::{HookAction, Hooks};
+use memory::{heap_size, MemoryUsage, VALUE_SIZE};
+
 const FRAMES_SIZE: usize = 64;
 const STACK_SIZE: usize = 64 * 128;
 
-type Globals = HashMap<String, Value>;
+// Number of instructions executed between two deadline checks
+const DEADLINE_INTERVAL: u32 = 1024;
+
+// Globals are resolved to slots by the compiler
+type Globals = Vec<Option<Value>>;
 
 #[derive(Debug)]
 #[allow(non_camel_case_types)]
 pub enum InterpretationResult {
     OK,
     COMPILE_ERROR,
-    RUNTIME_ERROR
+    RUNTIME_ERROR,
+    INTERRUPTED, // Stopped by a hook, running again resumes the program
+    OUT_OF_FUEL, // Adding fuel and running again resumes the program
+    TIMED_OUT, // Past the deadline, moving it and running again resumes the program
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub function: String,
+    pub line: usize,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Runtime error on line {} in '{}': {}", self.line, self.function, self.message)
+    }
+}
+
+type RunResult = Result<(), String>;
+
 pub struct CallFrame {
     pub function: FunctionObject,
     pub ip: usize, // TODO: For the moment we use array indexing, but we may use pointer dereferencing instead of performance
     pub slots: Vec<Value>,
+    pub base: usize, // Stack index of the callee, discarded with the arguments on return
+}
+
+impl CallFrame {
+    // Location of the instruction being executed
+    pub fn location(&self) -> Location {
+        let chunk = &self.function.chunk;
+        match chunk.location(self.ip.saturating_sub(1)) {
+            Some(location) => location,
+            None => chunk.locations.last().unwrap_or_default(),
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        self.location().line
+    }
 }
 
 pub struct VM {
@@ -38,262 +81,556 @@ pub struct VM {
     stack: Vec<Value>,
 
     globals: Globals,
+    global_names: Vec<String>,
+    signatures: HashMap<String, FunctionSignature>, // Of global functions, checked on calls from the host
+
+    verified: bool,
+    started: bool,
+    interrupted: bool, // The hooks already saw the next instruction, and stopped before it
+
+    hooks: Vec<Box<dyn Hooks>>,
+    error: Option<RuntimeError>,
+    verification_error: Option<VerificationError>,
+
+    // Execution limits, none by default
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    deadline_countdown: u32,
+
+    track_memory: bool,
+    memory: MemoryUsage,
+    peak_memory: MemoryUsage,
+    memory_limit: Option<usize>,
+
+    // Running stops once returning to this many frames, with the returned value kept aside
+    exit_depth: usize,
+    returned: Option<Value>,
 }
 
 impl VM {
     pub fn new(function: &mut FunctionObject) -> Self {
+        let global_names = function.chunk.globals.clone();
+
         let mut frames: [Option<CallFrame>; FRAMES_SIZE] = array::from_fn(|_| None);
         frames[0] = Some(CallFrame {
             function: function.clone(),
             ip: 0,
-            slots: vec![]
+            slots: vec![],
+            base: 0,
         });
 
         Self {
             frames,
             frames_count: 1,
             stack: Vec::with_capacity(STACK_SIZE),
-            globals: Globals::new(),
+            globals: vec![None; global_names.len()],
+            global_names,
+            signatures: HashMap::new(),
+            verified: false,
+            started: false,
+            interrupted: false,
+            hooks: vec![],
+            error: None,
+            verification_error: None,
+            fuel: None,
+            deadline: None,
+            deadline_countdown: 0,
+            track_memory: false,
+            memory: MemoryUsage::default(),
+            peak_memory: MemoryUsage::default(),
+            memory_limit: None,
+            exit_depth: 0,
+            returned: None,
         }
     }
 
+    // Replaces the program with one sharing its globals, such as the next entry of a REPL.
+    // Whatever an unfinished run left on the stack and in its frames is dropped.
+    pub fn load(&mut self, function: &FunctionObject) {
+        for index in 0..self.frames_count {
+            if let Some(frame) = self.frames[index].take() {
+                for slot in &frame.slots {
+                    self.release(self.value_size(slot), VALUE_SIZE);
+                }
+            }
+        }
+
+        self.reset_stack();
+
+        self.frames[0] = Some(CallFrame {
+            function: function.clone(),
+            ip: 0,
+            slots: vec![],
+            base: 0,
+        });
+        self.frames_count = 1;
+
+        // The new program knows every global of the previous ones, in the same slots
+        self.global_names = function.chunk.globals.clone();
+        if self.globals.len() < self.global_names.len() {
+            self.globals.resize(self.global_names.len(), None);
+        }
+
+        self.verified = false;
+        self.started = false;
+        self.interrupted = false;
+        self.error = None;
+        self.verification_error = None;
+        self.returned = None;
+    }
+
+    // Registers callbacks notified while the program runs, in registration order
+    pub fn add_hooks(&mut self, hooks: impl Hooks + 'static) {
+        self.hooks.push(Box::new(hooks));
+    }
+
+    // Limits the instructions the program can run, each one consumes its cost
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    // Tops up the fuel of a limited program, typically after it ran out
+    pub fn add_fuel(&mut self, fuel: u64) {
+        if let Some(remaining) = &mut self.fuel {
+            *remaining = remaining.saturating_add(fuel);
+        }
+    }
+
+    // Remaining fuel, None when unlimited
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    // Stops the program once the deadline is past, it's checked every few instructions
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+        self.deadline_countdown = 0;
+    }
+
+    // Stops the program with a runtime error once it holds more bytes than the limit
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+        if limit.is_some() {
+            self.track_memory = true;
+        }
+    }
+
+    // Counts the bytes the program holds, off by default since sizing a value walks all of it.
+    // It's turned on before running, values stored earlier aren't counted.
+    pub fn set_memory_tracking(&mut self, enabled: bool) {
+        self.track_memory = enabled;
+    }
+
+    pub fn memory(&self) -> MemoryUsage {
+        self.memory
+    }
+
+    // Usage when the program held the most bytes
+    pub fn peak_memory(&self) -> MemoryUsage {
+        self.peak_memory
+    }
+
     pub fn reset_stack(&mut self) {
-        self.stack = Vec::with_capacity(STACK_SIZE)
+        let stack = std::mem::replace(&mut self.stack, Vec::with_capacity(STACK_SIZE));
+        for value in &stack {
+            self.release(self.value_size(value), VALUE_SIZE);
+        }
     }
 
-    pub fn stack_push(&mut self, value: Value) {
+    pub fn stack_push(&mut self, value: Value) -> RunResult {
         if self.stack.len() >= STACK_SIZE {
-            panic!("Tried to push value {:?} to stack, but went out of bounds.", value);
+            return Err(String::from("Stack overflow"));
         }
 
+        self.allocate(self.value_size(&value), VALUE_SIZE)?;
         self.stack.push(value);
+        return Ok(())
     }
 
     pub fn stack_pop(&mut self) -> Value {
-        self.stack.pop().expect("Tried to pop non existing value.")
+        let value = self.stack.pop().expect("Tried to pop non existing value.");
+        self.release(self.value_size(&value), VALUE_SIZE);
+        value
     }
 
     pub fn stack_peek(&mut self, distance: usize) -> Value {
         return self.stack[self.stack.len() - distance - 1].clone()
     }
 
+    // Inspection, mostly used by hooks
+
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    // Active call frames, from the outermost to the current one
+    pub fn frames(&self) -> impl DoubleEndedIterator<Item = &CallFrame> {
+        self.frames[..self.frames_count].iter().flatten()
+    }
+
+    pub fn current_frame(&self) -> Option<&CallFrame> {
+        self.frames().next_back()
+    }
+
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        let slot = self.global_names.iter().position(|global| global == name)?;
+        match self.globals.get(slot) {
+            Some(Some(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    // Sets a global before the program runs, only globals the program uses have a slot
+    pub fn define_global(&mut self, name: &str, value: Value) -> Result<bool, String> {
+        match self.global_names.iter().position(|global| global == name) {
+            Some(slot) => self.store_global(slot, value).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    // Value of the last expression of the program, once it finished
+    pub fn result(&self) -> Option<&Value> {
+        match self.frames_count {
+            0 => self.returned.as_ref(),
+            _ => None,
+        }
+    }
+
+    // Error that stopped the last run, if any
+    pub fn error(&self) -> Option<&RuntimeError> {
+        self.error.as_ref()
+    }
+
+    // Invalid bytecode that kept the last run from starting, if any
+    pub fn verification_error(&self) -> Option<&VerificationError> {
+        self.verification_error.as_ref()
+    }
+
+    // Checks the bytecode of the loaded program is well formed
+    pub fn verify(&mut self) -> Result<(), VerificationError> {
+        let function = match &self.frames[0] {
+            Some(frame) => &frame.function,
+            None => panic!("Couldn't find any frame"),
+        };
+
+        verify_program(function)?;
+        self.verified = true;
+
+        return Ok(())
+    }
+
     pub fn run(&mut self) -> InterpretationResult {
+        if !self.verified {
+            if let Err(error) = self.verify() {
+                self.verification_error = Some(error);
+                return InterpretationResult::COMPILE_ERROR
+            }
+        }
+
+        self.error = None;
+
+        if !self.started {
+            self.started = true;
+            self.notify(|hooks, vm| hooks.on_call(vm));
+        }
+
+        match self.execute() {
+            Ok(result) => result,
+            Err(message) => {
+                let frame = self.get_current_frame();
+                let error = RuntimeError {
+                    message,
+                    function: frame.function.name.clone(),
+                    line: frame.line(),
+                };
+
+                self.set_error(error);
+                InterpretationResult::RUNTIME_ERROR
+            },
+        }
+    }
+
+    fn execute(&mut self) -> Result<InterpretationResult, String> {
         loop {
-            #[cfg(feature = "debug_trace_stack")]
-            {
-                for index in 0..self.stack.len() {
-                    println!("------ STACK: [ {:?} ]", self.stack[index]);
+            let mut cost = 0;
+            if self.fuel.is_some() || self.deadline.is_some() {
+                match self.check_limits() {
+                    Ok(next_cost) => cost = next_cost,
+                    Err(result) => return Ok(result),
                 }
             }
 
-            #[cfg(feature = "debug_trace_execution")]
-            {
-                let frame = self.get_current_frame();
-                println!("{} - {}", frame.ip, frame.function.chunk.code.len());
-                disassemble_instruction(&frame.function.chunk, frame.ip);
+            // Resuming runs the instruction the hooks stopped at without notifying them twice
+            if self.interrupted {
+                self.interrupted = false;
+            } else if !self.hooks.is_empty() && self.notify_before_instruction() == HookAction::Stop {
+                self.interrupted = true;
+                return Ok(InterpretationResult::INTERRUPTED)
+            }
+
+            // Fuel is only spent once the instruction is sure to run
+            if let Some(fuel) = &mut self.fuel {
+                *fuel -= cost;
             }
 
-            let instruction = OperationCode::from_u8(self.read_byte());
+            let instruction = self.read_instruction()?;
 
             match instruction {
-                OperationCode::RETURN => {
-                    if self.run_return_operation() {
-                        return InterpretationResult::OK
+                Instruction::Return => {
+                    if self.run_return_operation()? {
+                        return Ok(InterpretationResult::OK)
                     }
                 },
-                OperationCode::TRUE => self.stack_push(Value::Boolean(true)),
-                OperationCode::FALSE => self.stack_push(Value::Boolean(false)),
-                OperationCode::ADD => self.run_binary_operation(|a, b| a + b),
-                OperationCode::SUBSTRACT => self.run_binary_operation(|a, b| a - b),
-                OperationCode::MULTIPLY => self.run_binary_operation(|a, b| a * b),
-                OperationCode::DIVIDE => self.run_binary_operation(|a, b| a / b),
-                OperationCode::EQUALS => self.run_equality_operation(|a, b| a == b),
-                OperationCode::NOT_EQUALS => self.run_equality_operation(|a, b| a != b),
-                OperationCode::GREATER => self.run_comparison_operation(|a, b| a > b),
-                OperationCode::LESS => self.run_comparison_operation(|a, b| a < b),
-                OperationCode::NOT => self.run_not_operation(),
-                OperationCode::NEGATE => self.run_negate_operation(),
-                OperationCode::CONSTANT => self.run_constant_operation(),
-                OperationCode::SET_GLOBAL => self.run_set_global_operation(),
-                OperationCode::GET_GLOBAL => self.run_get_global_operation(),
-                OperationCode::GET_LOCAL => self.run_get_local_operation(),
-                OperationCode::SET_LOCAL => self.run_set_local_operation(),
-                OperationCode::JUMP => self.run_jump_operation(),
-                OperationCode::JUMP_IF_FALSE => self.run_jump_if_false_operation(),
-                OperationCode::LOOP => self.run_loop(),
-                OperationCode::CALL => self.run_call_operation(),
-                OperationCode::BUILD_ARRAY => self.run_build_array_operation(),
-                OperationCode::INDEX_ARRAY => self.run_index_array_operation(),
-                OperationCode::POP => { self.stack_pop(); },
-                OperationCode::UNKNOW => panic!("Unknow instruction"),
+                Instruction::True => self.stack_push(Value::Boolean(true))?,
+                Instruction::False => self.stack_push(Value::Boolean(false))?,
+                Instruction::Add => self.run_binary_operation(|a, b| a + b)?,
+                Instruction::Substract => self.run_binary_operation(|a, b| a - b)?,
+                Instruction::Multiply => self.run_binary_operation(|a, b| a * b)?,
+                Instruction::Divide => self.run_binary_operation(|a, b| a / b)?,
+                Instruction::Equals => self.run_equality_operation(|a, b| a == b)?,
+                Instruction::NotEquals => self.run_equality_operation(|a, b| a != b)?,
+                Instruction::Greater => self.run_comparison_operation(|a, b| a > b)?,
+                Instruction::Less => self.run_comparison_operation(|a, b| a < b)?,
+                Instruction::Not => self.run_not_operation()?,
+                Instruction::Negate => self.run_negate_operation()?,
+                Instruction::Constant { index } => self.run_constant_operation(index)?,
+                Instruction::SetGlobal { slot } => self.run_set_global_operation(slot)?,
+                Instruction::GetGlobal { slot } => self.run_get_global_operation(slot)?,
+                Instruction::GetLocal { slot } => self.run_get_local_operation(slot)?,
+                Instruction::SetLocal { slot } => self.run_set_local_operation(slot)?,
+                Instruction::Jump { offset } => self.run_jump_operation(offset),
+                Instruction::JumpIfFalse { offset } => self.run_jump_if_false_operation(offset)?,
+                Instruction::Loop { offset } => self.run_loop(offset),
+                Instruction::Call { arguments } => self.run_call_operation(arguments)?,
+                Instruction::BuildArray { length } => self.run_build_array_operation(length)?,
+                Instruction::IndexArray => self.run_index_array_operation()?,
+                Instruction::BuildStruct { fields } => self.run_build_struct_operation(fields)?,
+                Instruction::GetField { slot } => self.run_get_field_operation(slot)?,
+                Instruction::SetField { slot } => self.run_set_field_operation(slot)?,
+                Instruction::Pop => { self.stack_pop(); },
+                Instruction::Void => self.stack_push(Value::Void)?,
             };
 
             let frame = self.get_current_frame();
             if frame.ip >= frame.function.chunk.code.len() {
-                #[cfg(feature = "debug_trace_stack")] {
-                    println!("");
-                    for index in 0..self.stack.len() {
-                        println!(">> END STACK: [ {:?} ]", self.stack[index]);
-                    }
+                return Ok(InterpretationResult::OK)
+            }
+        }
+    }
+
+    // Cost of the next instruction, it isn't executed if it can't be paid for
+    fn check_limits(&mut self) -> Result<u64, InterpretationResult> {
+        if let Some(deadline) = self.deadline {
+            if self.deadline_countdown == 0 {
+                if Instant::now() >= deadline {
+                    return Err(InterpretationResult::TIMED_OUT)
                 }
 
-                return InterpretationResult::OK
+                self.deadline_countdown = DEADLINE_INTERVAL;
+            }
+
+            self.deadline_countdown -= 1;
+        }
+
+        if let Some(fuel) = self.fuel {
+            let frame = self.get_current_frame();
+            let cost = match frame.function.chunk.decode(frame.ip) {
+                Ok(instruction) => instruction.cost(),
+                Err(_) => return Ok(0),
+            };
+
+            if cost > fuel {
+                return Err(InterpretationResult::OUT_OF_FUEL)
             }
+
+            return Ok(cost)
         }
+
+        return Ok(0)
     }
 
-    fn run_binary_operation(&mut self, operation: fn(f64, f64) -> f64) {
-        let b = self.stack_pop();
-        let a = self.stack_pop();
+    // Every hook sees the instruction, even when an earlier one asks to stop
+    fn notify_before_instruction(&mut self) -> HookAction {
+        let frame = self.get_current_frame();
+        let offset = frame.ip;
+        let instruction = match frame.function.chunk.decode(offset) {
+            Ok(instruction) => instruction,
+            Err(_) => return HookAction::Continue,
+        };
 
-        if let Value::F64(a) = a {
-            if let Value::F64(b) = b {
-                return self.stack_push(Value::F64(operation(a, b)));
+        let mut action = HookAction::Continue;
+        self.notify(|hooks, vm| {
+            if hooks.before_instruction(vm, offset, &instruction) == HookAction::Stop {
+                action = HookAction::Stop;
             }
+        });
 
-            panic!("Expected right to be f64, instead got {:?}", b);
+        return action
+    }
+
+    // Hooks get a shared view of the VM, so they are detached while being notified
+    fn notify(&mut self, mut callback: impl FnMut(&mut dyn Hooks, &VM)) {
+        if self.hooks.is_empty() {
+            return
+        }
+
+        let mut hooks = std::mem::take(&mut self.hooks);
+        for hook in hooks.iter_mut() {
+            callback(hook.as_mut(), self);
         }
 
-        panic!("Expected left to be f64, instead got {:?}", a);
+        self.hooks = hooks;
     }
 
-    fn run_equality_operation(&mut self, operation: fn(Value, Value) -> bool) {
+    fn run_binary_operation(&mut self, operation: fn(f64, f64) -> f64) -> RunResult {
         let b = self.stack_pop();
         let a = self.stack_pop();
 
-        if !is_same_value_type(&a, &b) {
-            panic!("Type mismatch between {:?} and {:?}", a, b);
+        match (a, b) {
+            (Value::F64(a), Value::F64(b)) => self.stack_push(Value::F64(operation(a, b))),
+            (Value::F64(_), b) => Err(format!("Expected right to be f64, instead got {:?}", b)),
+            (a, _) => Err(format!("Expected left to be f64, instead got {:?}", a)),
         }
-
-        self.stack_push(Value::Boolean(operation(a, b)));
     }
 
-    fn run_comparison_operation(&mut self, operation: fn(f64, f64) -> bool) {
+    fn run_equality_operation(&mut self, operation: fn(Value, Value) -> bool) -> RunResult {
         let b = self.stack_pop();
         let a = self.stack_pop();
 
-        if let Value::F64(a) = a {
-            if let Value::F64(b) = b {
-                return self.stack_push(Value::Boolean(operation(a, b)));
-            }
-
-            panic!("Expected right to be f64, instead got {:?}", b);
+        if !is_same_value_type(&a, &b) {
+            return Err(format!("Type mismatch between {:?} and {:?}", a, b));
         }
 
-        panic!("Expected left to be f64, instead got {:?}", a);
+        self.stack_push(Value::Boolean(operation(a, b)))
     }
 
-    fn run_not_operation(&mut self) {
-        let value = self.stack_pop();
-        if let Value::Boolean(value) = value {
-            return self.stack_push(Value::Boolean(!value));
+    fn run_comparison_operation(&mut self, operation: fn(f64, f64) -> bool) -> RunResult {
+        let b = self.stack_pop();
+        let a = self.stack_pop();
+
+        match (a, b) {
+            (Value::F64(a), Value::F64(b)) => self.stack_push(Value::Boolean(operation(a, b))),
+            (Value::F64(_), b) => Err(format!("Expected right to be f64, instead got {:?}", b)),
+            (a, _) => Err(format!("Expected left to be f64, instead got {:?}", a)),
         }
+    }
 
-        panic!("Expected left to be boolean, instead got {:?}", value);
+    fn run_not_operation(&mut self) -> RunResult {
+        match self.stack_pop() {
+            Value::Boolean(value) => self.stack_push(Value::Boolean(!value)),
+            value => Err(format!("Expected operand to be boolean, instead got {:?}", value)),
+        }
     }
 
-    fn run_negate_operation(&mut self) {
-        let value = self.stack_pop();
-        if let Value::F64(value) = value {
-            return self.stack_push(Value::F64(-value));
+    fn run_negate_operation(&mut self) -> RunResult {
+        match self.stack_pop() {
+            Value::F64(value) => self.stack_push(Value::F64(-value)),
+            value => Err(format!("Expected operand to be f64, instead got {:?}", value)),
         }
+    }
 
-        panic!("Expected left to be f64, instead got {:?}", value);
+    fn run_constant_operation(&mut self, index: u8) -> RunResult {
+        let frame = self.get_current_frame();
+        let constant = frame.function.chunk.contants[index as usize].clone();
+        self.stack_push(constant)
     }
 
-    fn run_constant_operation(&mut self) {
-        let constant = self.read_constant();
-        self.stack_push(constant);
+    fn run_set_global_operation(&mut self, slot: u8) -> RunResult {
+        let value = self.stack_peek(0);
+        self.store_global(slot as usize, value)
     }
 
-    fn run_set_global_operation(&mut self) {
-        let constant = self.read_constant();
+    fn store_global(&mut self, slot: usize, value: Value) -> RunResult {
+        if slot >= self.globals.len() {
+            self.globals.resize(slot + 1, None);
+        }
 
-        if let Value::Object(object) = constant {
-            if let Object::String(string_object) = object {
-                let value = self.stack_peek(0);
-                self.globals.insert(string_object.value.clone(), value);
-                return;
-            }
+        match &self.globals[slot] {
+            Some(previous) => self.release(self.value_size(previous), 0),
+            None => self.allocate(VALUE_SIZE, 0)?,
         }
 
-        panic!("Expected string identifier");
+        self.allocate(self.value_size(&value), 0)?;
+        self.globals[slot] = Some(value);
+        return Ok(())
     }
 
-    fn run_get_global_operation(&mut self) {
-        let constant = self.read_constant();
-        
-        if let Value::Object(object) = constant {
-            if let Object::String(string_object) = object {
-                let value = match self.globals.get(&string_object.value) {
-                    Some(value) => value.clone(),
-                    None => panic!("Undefined global {}", &string_object.value),
-                };
+    fn run_get_global_operation(&mut self, slot: u8) -> RunResult {
+        let slot = slot as usize;
 
-                return self.stack_push(value);
-            }
-        }
+        let value = match self.globals.get(slot) {
+            Some(Some(value)) => value.clone(),
+            _ => return Err(format!("Undefined global {}", self.get_global_name(slot))),
+        };
 
-        panic!("Expected string identifier");
+        self.stack_push(value)
     }
 
-    fn run_get_local_operation(&mut self) {
-        let slot = self.read_byte();
+    fn run_get_local_operation(&mut self, slot: u8) -> RunResult {
         let frame = self.get_current_frame();
         let value = frame.slots[slot as usize].clone();
-        self.stack_push(value);
+        self.stack_push(value)
     }
 
-    fn run_set_local_operation(&mut self) {
-        let slot = self.read_byte();
+    fn run_set_local_operation(&mut self, slot: u8) -> RunResult {
+        let slot = slot as usize;
         let peek_value = self.stack_peek(0);
+
+        if self.track_memory {
+            let frame = self.get_current_frame();
+            let (added, previous) = match frame.slots.get(slot) {
+                Some(previous) => (0, heap_size(previous)),
+                None => (slot + 1 - frame.slots.len(), 0),
+            };
+
+            self.release(previous, 0);
+            self.allocate(heap_size(&peek_value), added * VALUE_SIZE)?;
+        }
+
         let frame = self.get_current_frame();
-        if frame.slots.len() <= slot as usize {
-            frame.slots.push(peek_value);
-        } else {
-            frame.slots[slot as usize] = peek_value;
+        if frame.slots.len() <= slot {
+            frame.slots.resize(slot + 1, Value::Void);
         }
+
+        frame.slots[slot] = peek_value;
+        return Ok(())
     }
 
-    fn run_jump_operation(&mut self) {
-        let offset = self.read_short();
+    fn run_jump_operation(&mut self, offset: u16) {
         let frame = self.get_current_frame();
         frame.ip += offset as usize;
     }
 
-    fn run_jump_if_false_operation(&mut self) {
-        let offset = self.read_short();
-        let condition_value = self.stack_peek(0);
-
-        match condition_value {
+    fn run_jump_if_false_operation(&mut self, offset: u16) -> RunResult {
+        match self.stack_peek(0) {
             Value::Boolean(condition) => {
                 if !condition {
                     let frame = self.get_current_frame();
-                    return frame.ip += offset as usize
+                    frame.ip += offset as usize;
                 }
+
+                Ok(())
             },
-            _ => panic!("Expected condition to be bool, instead got {:?}", condition_value)
+            condition => Err(format!("Expected condition to be bool, instead got {:?}", condition)),
         }
     }
 
-    fn run_loop(&mut self) {
-        let offset = self.read_short();
+    fn run_loop(&mut self, offset: u16) {
         let frame = self.get_current_frame();
         frame.ip -= offset as usize;
     }
 
-    fn run_call_operation(&mut self) {
-        let arguments_count = self.read_byte();
+    fn run_call_operation(&mut self, arguments_count: u8) -> RunResult {
         let peek = self.stack_peek(arguments_count as usize);
-        self.call_value(peek, arguments_count);
+        self.call_value(peek, arguments_count)
     }
 
-    fn run_build_array_operation(&mut self) {
+    fn run_build_array_operation(&mut self, length: u8) -> RunResult {
         let mut array = object::ArrayObject {
             elements: vec![]
         };
 
-        let array_length = self.read_byte() as usize;
+        let array_length = length as usize;
         for index in 0..array_length {
             array.elements.push(
                 self.stack_peek(array_length - index - 1)
@@ -308,70 +645,225 @@ impl VM {
             Value::Object(
                 Object::Array(array)
             )
-        );
+        )
     }
 
-    fn run_index_array_operation(&mut self) {
+    fn run_index_array_operation(&mut self) -> RunResult {
         let index = match self.stack_pop() {
             Value::F64(index) => index as usize,
-            unexpected => panic!("Expected index to be int, instead got {:?}", unexpected),
+            unexpected => return Err(format!("Expected index to be int, instead got {:?}", unexpected)),
         };
 
         let array = match self.stack_pop() {
-            Value::Object(object) => {
-                match object {
-                    Object::Array(array) => array,
-                    unexpected => panic!("Expected array, instead got {:?}", unexpected),
-                }
-            },
-            unexpected => panic!("Expected array, instead got {:?}", unexpected),
+            Value::Object(Object::Array(array)) => array,
+            unexpected => return Err(format!("Expected array, instead got {:?}", unexpected)),
         };
 
         if index >= array.elements.len() {
-            panic!("Out of bounds error: array length is {}, but index is {}", array.elements.len(), index);
+            return Err(format!("Out of bounds error: array length is {}, but index is {}", array.elements.len(), index));
+        }
+
+        self.stack_push(array.elements[index].clone())
+    }
+
+    // Fields are void until set, the compiler sets every one of them right after.
+    // The layout on the stack is the struct name followed by its field names.
+    fn run_build_struct_operation(&mut self, fields: u8) -> RunResult {
+        let names = match self.stack_pop() {
+            Value::Object(Object::Array(array)) => array.elements,
+            unexpected => return Err(format!("Expected struct layout, instead got {:?}", unexpected)),
+        };
+
+        if names.len() != fields as usize + 1 {
+            return Err(format!("Struct layout has {} names, but the struct has {} fields", names.len(), fields));
+        }
+
+        let mut names = names.into_iter().map(|name| match name {
+            Value::Object(Object::String(string)) => Ok(string.value),
+            unexpected => Err(format!("Expected field name, instead got {:?}", unexpected)),
+        });
+
+        let name = names.next().unwrap()?;
+        let layout = object::StructLayout {
+            name,
+            fields: names.collect::<Result<_, _>>()?,
+        };
+
+        self.stack_push(
+            Value::Object(
+                Object::Struct(object::StructObject {
+                    layout: Rc::new(layout),
+                    fields: vec![Value::Void; fields as usize],
+                })
+            )
+        )
+    }
+
+    fn run_get_field_operation(&mut self, slot: u8) -> RunResult {
+        let structure = self.pop_struct()?;
+
+        match structure.fields.get(slot as usize) {
+            Some(value) => self.stack_push(value.clone()),
+            None => Err(format!("Struct has {} fields, but the field slot is {}", structure.fields.len(), slot)),
+        }
+    }
+
+    // Pushes the struct back with the field set, to be stored where it was read from
+    fn run_set_field_operation(&mut self, slot: u8) -> RunResult {
+        let value = self.stack_pop();
+        let mut structure = self.pop_struct()?;
+
+        match structure.fields.get_mut(slot as usize) {
+            Some(field) => *field = value,
+            None => return Err(format!("Struct has {} fields, but the field slot is {}", structure.fields.len(), slot)),
         }
 
-        self.stack_push(array.elements[index].clone());
+        self.stack_push(Value::Object(Object::Struct(structure)))
+    }
+
+    fn pop_struct(&mut self) -> Result<object::StructObject, String> {
+        match self.stack_pop() {
+            Value::Object(Object::Struct(structure)) => Ok(structure),
+            unexpected => Err(format!("Expected struct, instead got {:?}", unexpected)),
+        }
     }
 
-    fn run_return_operation(&mut self) -> bool {
+    // Returns whether the program is over
+    fn run_return_operation(&mut self) -> Result<bool, String> {
         let value = self.stack_pop();
+        self.notify(|hooks, vm| hooks.on_return(vm, &value));
+
+        let base = self.get_current_frame().base;
+
+        if let Some(frame) = self.frames[self.frames_count - 1].take() {
+            for slot in &frame.slots {
+                self.release(self.value_size(slot), VALUE_SIZE);
+            }
+        }
+
         self.frames_count -= 1;
-        if self.frames_count <= 0 {
+
+        while self.stack.len() > base {
             self.stack_pop();
-            return true
         }
 
-        self.stack_push(value);
-        return false
+        if self.frames_count == self.exit_depth {
+            self.returned = Some(value);
+            return Ok(true)
+        }
+
+        self.stack_push(value)?;
+        return Ok(false)
     }
 
-    fn call_value(&mut self, callee: Value, arguments_count: u8) {
-        if let Value::Object(callee_object) = callee {
-            if let Object::Function(function) = callee_object {
-                return self.call(function, arguments_count);
-            }
+    fn call_value(&mut self, callee: Value, arguments_count: u8) -> RunResult {
+        match callee {
+            Value::Object(Object::Function(function)) => self.call(function, arguments_count),
+            Value::Object(Object::Native(native)) => self.call_native(native, arguments_count),
+            callee => Err(format!("Couldn't call value {:?}", callee)),
+        }
+    }
+
+    // Natives run right away, their result replaces the callee and the arguments
+    fn call_native(&mut self, native: NativeObject, arguments_count: u8) -> RunResult {
+        let arguments_count = arguments_count as usize;
+        if arguments_count != native.arity {
+            return Err(format!("Expected {} arguments for '{}', instead got {}", native.arity, native.name, arguments_count));
         }
 
-        panic!("Couldn't call value.")
+        let arguments = self.stack[(self.stack.len() - arguments_count)..].to_vec();
+        let result = (native.function)(&arguments)?;
+
+        for _ in 0..=arguments_count {
+            self.stack_pop();
+        }
+
+        self.stack_push(result)
     }
 
-    fn call(&mut self, function: FunctionObject, arguments_count: u8) {
+    fn call(&mut self, function: FunctionObject, arguments_count: u8) -> RunResult {
+        if self.frames_count >= FRAMES_SIZE {
+            return Err(format!("Stack overflow: exceeded {} nested calls", FRAMES_SIZE));
+        }
+
         let mut slots = vec![];
         slots.extend_from_slice(&self.stack[(self.stack.len() - arguments_count as usize)..self.stack.len()]);
 
+        for slot in &slots {
+            self.allocate(self.value_size(slot), VALUE_SIZE)?;
+        }
+
         let call_frame = CallFrame {
             function,
             ip: 0,
             slots,
+            base: self.stack.len() - arguments_count as usize - 1,
         };
 
         self.frames[self.frames_count] = Some(call_frame);
         self.frames_count += 1;
+
+        self.notify(|hooks, vm| hooks.on_call(vm));
+        return Ok(())
+    }
+
+    // Notifies hooks of the error, it's then kept until the next run
+    fn set_error(&mut self, error: RuntimeError) {
+        self.notify(|hooks, vm| hooks.on_error(vm, &error));
+        self.error = Some(error);
+    }
+
+    // Memory accounting
+
+    // Bytes the value owns outside of its slot, when memory is tracked
+    fn value_size(&self, value: &Value) -> usize {
+        match self.track_memory {
+            true => heap_size(value),
+            false => 0,
+        }
+    }
+
+    // Refused allocations aren't counted, the value they were for isn't stored
+    fn allocate(&mut self, heap: usize, stack: usize) -> RunResult {
+        if !self.track_memory {
+            return Ok(())
+        }
+
+        let used = self.memory.total() + heap + stack;
+        if let Some(limit) = self.memory_limit {
+            if used > limit {
+                return Err(format!("Out of memory: {used} bytes needed, the limit is {limit} bytes"))
+            }
+        }
+
+        self.memory.heap += heap;
+        self.memory.stack += stack;
+
+        if used > self.peak_memory.total() {
+            self.peak_memory = self.memory;
+        }
+
+        return Ok(())
+    }
+
+    fn release(&mut self, heap: usize, stack: usize) {
+        if !self.track_memory {
+            return
+        }
+
+        self.memory.heap = self.memory.heap.saturating_sub(heap);
+        self.memory.stack = self.memory.stack.saturating_sub(stack);
     }
 
     // Utils
 
+    fn get_global_name(&self, slot: usize) -> &str {
+        match self.global_names.get(slot) {
+            Some(name) => name,
+            None => "<unknown>",
+        }
+    }
+
     fn get_current_frame(&mut self) -> &mut CallFrame {
         match &mut self.frames[self.frames_count - 1] {
             Some(frame) => frame,
@@ -379,34 +871,25 @@ impl VM {
         }
     }
 
-    fn read_byte(&mut self) -> u8 {
-        let frame = self.get_current_frame();
-        frame.ip += 1;
-        frame.function.chunk.code[frame.ip - 1]
-    }
-
-    fn read_short(&mut self) -> u16 {
+    fn read_instruction(&mut self) -> Result<Instruction, String> {
         let frame = self.get_current_frame();
-        let value = ((frame.function.chunk.code[frame.ip] as u16) << 8) 
-            | (frame.function.chunk.code[frame.ip + 1] as u16);
-        frame.ip += 2;
-        return value
-    }
+        let instruction = match frame.function.chunk.decode(frame.ip) {
+            Ok(instruction) => instruction,
+            Err(error) => return Err(error.to_string()),
+        };
 
-    fn read_constant(&mut self) -> Value {
-        let frame = self.get_current_frame();
-        frame.ip += 1;
-        let byte = frame.function.chunk.code[frame.ip - 1];
-        frame.function.chunk.contants[byte as usize].clone()
+        frame.ip += instruction.width();
+        return Ok(instruction)
     }
 
 }
 
 fn is_same_value_type(a: &Value, b: &Value) -> bool {
-    match (a, b) {
-        (Value::Boolean(_), Value::Boolean(_)) => true,
-        (Value::F64(_), Value::F64(_)) => true,
-        (Value::Object(_), Value::Object(_)) => true,
-        _ => false,
-    }
-}
\ No newline at end of file
+    matches!(
+        (a, b),
+        (Value::Boolean(_), Value::Boolean(_))
+            | (Value::F64(_), Value::F64(_))
+            | (Value::Object(_), Value::Object(_))
+            | (Value::Void, Value::Void)
+    )
+}
@@ -3,14 +3,25 @@ mod tests {
     use crate::backend::{
         bytecode::{
             Chunk,
+            Location,
             OperationCode
         }, 
-        object::FunctionObject, 
+        object::{FunctionObject, NativeObject, Object}, 
         value::Value, 
-        vm::VM, 
+        vm::{
+            embed::{CallError, Closure, ToValue},
+            hooks::{HookAction, Hooks, Tracer},
+            memory::VALUE_SIZE,
+            InterpretationResult,
+            RuntimeError,
+            VM,
+        },
         compiler::Compiler,
+        bytecode::Instruction,
     };
 
+    use std::{cell::RefCell, rc::Rc, time::{Duration, Instant}};
+
     use crate::frontend::{
         lexer::Lexer, 
         parser::{parse_file, Parser}, 
@@ -26,7 +37,7 @@ mod tests {
         let ast = parse_file(&mut parser);
         println!("Parsing completed.");
     
-        check_program(&ast);
+        check_program(&ast).unwrap();
         println!("Typechecking completed.");
 
         let function = &mut FunctionObject {
@@ -39,7 +50,8 @@ mod tests {
         let function = compiler.compile(&ast);
     
         let mut vm = VM::new(function);
-        vm.run();
+        let result = vm.run();
+        assert!(matches!(result, InterpretationResult::OK), "Expected OK, instead got {:?}", result);
     }
 
     #[test]
@@ -183,4 +195,624 @@ mod tests {
         ");
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_compile_global_functions() {
+        println!("\n======== Testing global functions ========\n");
+        test_compilation("
+            fn double(x: int) -> int {
+                return add(x, x);
+            }
+
+            fn add(a: int, b: int) -> int {
+                return a + b;
+            }
+
+            double(4);
+        ");
+    }
+
+    #[test]
+    fn test_globals_resolved_to_slots() {
+        let mut lexer = Lexer::new("
+            fn first() -> int {
+                return second();
+            }
+
+            fn second() -> int {
+                return 2;
+            }
+
+            first();
+        ");
+        let mut parser = Parser::new(&mut lexer);
+        let ast = parse_file(&mut parser);
+        check_program(&ast).unwrap();
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        let mut compiler = Compiler::new(function);
+        let function = compiler.compile(&ast);
+
+        assert_eq!(function.chunk.globals, vec!["first", "second"]);
+
+        // Global names are no longer stored in the constant pool
+        for constant in &function.chunk.contants {
+            assert!(!matches!(constant, Value::Object(Object::String(_))));
+        }
+
+        let global_access = [OperationCode::GET_GLOBAL as u8, 0];
+        assert!(function.chunk.code.windows(2).any(|window| window == global_access));
+    }
+
+    #[test]
+    fn test_top_level_variables_are_globals() {
+        let mut function = compile("
+            let count = 1;
+
+            fn increment() -> void {
+                count = count + 1;
+            }
+
+            increment();
+            increment();
+        ");
+
+        assert_eq!(function.chunk.globals, vec!["count", "increment"]);
+
+        let mut vm = VM::new(&mut function);
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+        assert_eq!(vm.global("count"), Some(&Value::F64(3.0)));
+    }
+
+    #[test]
+    fn test_variables_declared_without_value() {
+        // Compiled without typechecking, reads only follow assignments
+        let mut lexer = Lexer::new("
+            let total: int;
+            total = { let x: int; x = 4; x * 2 };
+        ");
+        let mut parser = Parser::new(&mut lexer);
+        let ast = parse_file(&mut parser);
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        let mut compiler = Compiler::new(function);
+        let function = compiler.compile(&ast);
+
+        let mut vm = VM::new(function);
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+        assert_eq!(vm.global("total"), Some(&Value::F64(8.0)));
+    }
+
+    // Hooks
+
+    fn compile(source: &str) -> FunctionObject {
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(&mut lexer);
+        let ast = parse_file(&mut parser);
+        check_program(&ast).unwrap();
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        let mut compiler = Compiler::new(function);
+        compiler.compile(&ast).clone()
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        instructions: usize,
+        calls: Vec<String>,
+        returns: Vec<Value>,
+        errors: Vec<RuntimeError>,
+        depths: Vec<usize>,
+        stop_after: Option<usize>,
+    }
+
+    impl Hooks for Recorder {
+        fn before_instruction(&mut self, _vm: &VM, _offset: usize, _instruction: &Instruction) -> HookAction {
+            self.instructions += 1;
+
+            match self.stop_after {
+                Some(limit) if self.instructions > limit => HookAction::Stop,
+                _ => HookAction::Continue,
+            }
+        }
+
+        fn on_call(&mut self, vm: &VM) {
+            let frame = vm.current_frame().unwrap();
+            self.calls.push(frame.function.name.clone());
+            self.depths.push(vm.frames().count());
+        }
+
+        fn on_return(&mut self, _vm: &VM, value: &Value) {
+            self.returns.push(value.clone());
+        }
+
+        fn on_error(&mut self, _vm: &VM, error: &RuntimeError) {
+            self.errors.push(error.clone());
+        }
+    }
+
+    #[test]
+    fn test_hooks_calls_and_returns() {
+        let mut function = compile("
+            fn double(x: int) -> int {
+                return x * 2;
+            }
+
+            double(double(3));
+        ");
+
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+
+        let mut vm = VM::new(&mut function);
+        vm.add_hooks(recorder.clone());
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+
+        let recorder = recorder.borrow();
+        assert_eq!(recorder.calls, vec!["Global", "double", "double"]);
+        assert_eq!(recorder.depths, vec![1, 2, 2]);
+        assert_eq!(recorder.returns, vec![Value::F64(6.0), Value::F64(12.0), Value::F64(12.0)]);
+        assert!(recorder.errors.is_empty());
+        assert!(recorder.instructions > 10);
+    }
+
+    #[test]
+    fn test_runtime_error_reported_to_hooks() {
+        let mut function = compile("
+            fn get(values: [int]) -> int {
+                return values[5];
+            }
+
+            get([1, 2]);
+        ");
+
+        let recorder = Rc::new(RefCell::new(Recorder::default()));
+
+        let mut vm = VM::new(&mut function);
+        vm.add_hooks(recorder.clone());
+        assert!(matches!(vm.run(), InterpretationResult::RUNTIME_ERROR));
+
+        let expected = RuntimeError {
+            message: String::from("Out of bounds error: array length is 2, but index is 5"),
+            function: String::from("get"),
+            line: 3,
+        };
+
+        assert_eq!(vm.error(), Some(&expected));
+        assert_eq!(recorder.borrow().errors, vec![expected]);
+    }
+
+    #[test]
+    fn test_hooks_interrupt_and_resume() {
+        let mut function = compile("
+            fn double(x: int) -> int {
+                return x * 2;
+            }
+
+            double(3);
+        ");
+
+        let recorder = Rc::new(RefCell::new(Recorder {
+            stop_after: Some(5),
+            ..Recorder::default()
+        }));
+
+        let mut vm = VM::new(&mut function);
+        vm.add_hooks(recorder.clone());
+        assert!(matches!(vm.run(), InterpretationResult::INTERRUPTED));
+        assert_eq!(vm.current_frame().unwrap().ip, 9);
+
+        // Resuming continues from the interrupted instruction, without notifying the top-level call again
+        recorder.borrow_mut().stop_after = None;
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+        assert_eq!(recorder.borrow().calls, vec!["Global", "double"]);
+        assert_eq!(recorder.borrow().returns, vec![Value::F64(6.0), Value::F64(6.0)]);
+    }
+
+    #[test]
+    fn test_debug_info_tables() {
+        let function = compile("
+            fn add(a: int, b: int) -> int {
+                let sum: int = a + b;
+                return sum;
+            }
+
+            add(1, 2);
+        ");
+
+        let add = match &function.chunk.contants[0] {
+            Value::Object(Object::Function(function)) => function,
+            value => panic!("Expected a function, got {:?}", value),
+        };
+
+        assert_eq!(add.chunk.span.start, Location::new(2, 13));
+        assert_eq!(add.chunk.span.end, Location::new(5, 13));
+
+        // GET_LOCAL a, at the start of "a + b"
+        assert_eq!(add.chunk.location(0), Some(Location::new(3, 32)));
+        assert_eq!(add.chunk.location(add.chunk.code.len()), None);
+
+        let mut locals: Vec<(&str, usize)> = add.chunk.locals.iter()
+            .map(|local| (local.name.as_str(), local.slot))
+            .collect();
+        locals.sort_by_key(|(_, slot)| *slot);
+        assert_eq!(locals, vec![("a", 0), ("b", 1), ("sum", 2)]);
+
+        let sum = add.chunk.locals.iter().find(|local| local.name == "sum").unwrap();
+        assert!(sum.start > 0 && sum.end <= add.chunk.code.len());
+        assert_eq!(add.chunk.live_locals(0).count(), 2);
+    }
+
+    #[test]
+    fn test_infinite_loop_runs_out_of_fuel() {
+        let mut function = compile("
+            while true {}
+        ");
+
+        let mut vm = VM::new(&mut function);
+        vm.set_fuel(Some(1000));
+        assert!(matches!(vm.run(), InterpretationResult::OUT_OF_FUEL));
+        assert!(vm.fuel().unwrap() < 3);
+        assert_eq!(vm.error(), None);
+
+        vm.add_fuel(500);
+        assert!(matches!(vm.run(), InterpretationResult::OUT_OF_FUEL));
+    }
+
+    #[test]
+    fn test_fuel_top_up_resumes() {
+        let mut function = compile("
+            {
+                let x = 0;
+                while x < 100 {
+                    x = x + 1;
+                };
+            }
+        ");
+
+        let mut unlimited = VM::new(&mut function.clone());
+        unlimited.set_fuel(Some(u64::MAX));
+        assert!(matches!(unlimited.run(), InterpretationResult::OK));
+        let needed = u64::MAX - unlimited.fuel().unwrap();
+
+        let mut vm = VM::new(&mut function);
+        vm.set_fuel(Some(needed / 2));
+        assert!(matches!(vm.run(), InterpretationResult::OUT_OF_FUEL));
+
+        // Running out of fuel doesn't lose any progress, nor waste fuel
+        vm.add_fuel(needed - needed / 2);
+        assert!(matches!(vm.run(), InterpretationResult::OK), "{:?}", vm.error());
+        assert_eq!(vm.fuel(), Some(0));
+    }
+
+    #[test]
+    fn test_heavier_instructions_cost_more() {
+        assert_eq!(Instruction::Pop.cost(), 1);
+        assert!(Instruction::Call { arguments: 2 }.cost() > Instruction::Add.cost());
+        assert!(Instruction::BuildArray { length: 10 }.cost() > Instruction::BuildArray { length: 1 }.cost());
+
+        // Without a limit nothing is consumed
+        let mut function = compile("1 + 2;");
+        let mut vm = VM::new(&mut function);
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+        assert_eq!(vm.fuel(), None);
+    }
+
+    #[test]
+    fn test_deadline_stops_program() {
+        let mut function = compile("
+            while true {}
+        ");
+
+        let mut vm = VM::new(&mut function);
+        vm.set_deadline(Some(Instant::now() + Duration::from_millis(20)));
+        assert!(matches!(vm.run(), InterpretationResult::TIMED_OUT));
+        assert_eq!(vm.error(), None);
+
+        // Moving the deadline resumes the program
+        vm.set_deadline(Some(Instant::now() + Duration::from_millis(5)));
+        assert!(matches!(vm.run(), InterpretationResult::TIMED_OUT));
+    }
+
+    #[test]
+    fn test_memory_released_after_run() {
+        let mut function = compile("
+            fn pair(a: int, b: int) -> [int] {
+                return [a, b];
+            }
+
+            pair(1, 2);
+        ");
+
+        let mut untracked = VM::new(&mut function.clone());
+        assert!(matches!(untracked.run(), InterpretationResult::OK));
+        assert_eq!(untracked.peak_memory().total(), 0);
+
+        let mut vm = VM::new(&mut function);
+        vm.set_memory_tracking(true);
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+
+        // Only the global holding the function is left
+        assert_eq!(vm.memory().stack, 0);
+        assert!(vm.memory().heap > VALUE_SIZE);
+
+        // The array was built while the arguments were still in their slots
+        let peak = vm.peak_memory();
+        assert!(peak.heap >= vm.memory().heap + 2 * VALUE_SIZE, "{peak}");
+        assert!(peak.stack >= 6 * VALUE_SIZE, "{peak}");
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let mut function = compile("
+            [\"aaaaaaaaaaaaaaaa\", \"bbbbbbbbbbbbbbbb\"];
+            [
+                [\"aaaaaaaaaaaaaaaa\", \"bbbbbbbbbbbbbbbb\"],
+                [\"aaaaaaaaaaaaaaaa\", \"bbbbbbbbbbbbbbbb\"],
+                [\"aaaaaaaaaaaaaaaa\", \"bbbbbbbbbbbbbbbb\"]
+            ];
+        ");
+
+        let mut unlimited = VM::new(&mut function.clone());
+        unlimited.set_memory_tracking(true);
+        assert!(matches!(unlimited.run(), InterpretationResult::OK));
+        let peak = unlimited.peak_memory().total();
+
+        let mut vm = VM::new(&mut function.clone());
+        vm.set_memory_limit(Some(peak));
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+        assert_eq!(vm.peak_memory(), unlimited.peak_memory());
+
+        let mut vm = VM::new(&mut function);
+        vm.set_memory_limit(Some(peak - 1));
+        assert!(matches!(vm.run(), InterpretationResult::RUNTIME_ERROR));
+
+        let error = vm.error().unwrap();
+        assert!(error.message.starts_with("Out of memory"), "{error}");
+        assert!(error.line >= 3, "{error}");
+        assert!(vm.memory().total() < peak);
+    }
+
+    const LIBRARY: &str = "
+        fn add(a: int, b: int) -> int {
+            return a + b;
+        }
+
+        fn is_positive(x: int) -> bool {
+            return x > 0;
+        }
+
+        fn greet() -> [int] {
+            return [1, 2, 3];
+        }
+
+        fn get(values: [int], index: int) -> int {
+            return values[index];
+        }
+
+        fn twice(f: fn(int) -> int, x: int) -> int {
+            return f(f(x));
+        }
+    ";
+
+    fn load(source: &str) -> (FunctionObject, VM) {
+        let mut function = compile(source);
+        let mut vm = VM::new(&mut function);
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+        (function, vm)
+    }
+
+    #[test]
+    fn test_call_function_with_conversions() {
+        let (_, mut vm) = load(LIBRARY);
+
+        assert_eq!(vm.call_function::<i64>("add", (2i64, 3i64)).unwrap(), 5);
+        assert!(!vm.call_function::<bool>("is_positive", (-4i64,)).unwrap());
+        assert_eq!(vm.call_function::<Vec<i64>>("greet", ()).unwrap(), vec![1, 2, 3]);
+        assert_eq!(vm.call_function::<i64>("get", (vec![10i64, 20, 30], 1i64)).unwrap(), 20);
+
+        // The VM is left as it was, so calls can be repeated
+        assert!(vm.stack().is_empty());
+        assert_eq!(vm.frames().count(), 0);
+        assert_eq!(vm.call_function::<i64>("add", (1i64, 1i64)).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_call_errors() {
+        let (_, mut vm) = load(LIBRARY);
+
+        match vm.call_function::<i64>("missing", ()) {
+            Err(CallError::UndefinedFunction(name)) => assert_eq!(name, "missing"),
+            result => panic!("Expected an undefined function, got {:?}", result),
+        }
+
+        match vm.call_function::<i64>("add", (1i64,)) {
+            Err(CallError::Arity { function, expected: 2, found: 1 }) => assert_eq!(function, "add"),
+            result => panic!("Expected an arity error, got {:?}", result),
+        }
+
+        match vm.call_function::<String>("add", (1i64, 2i64)) {
+            Err(CallError::Type { expected, found }) => assert_eq!((expected.as_str(), found.as_str()), ("string", "int")),
+            result => panic!("Expected a type error, got {:?}", result),
+        }
+
+        match vm.call_function::<i64>("get", (vec![1i64], 4i64)) {
+            Err(CallError::Runtime(error)) => {
+                assert_eq!(error.function, "get");
+                assert_eq!(vm.error(), Some(&error));
+            },
+            result => panic!("Expected a runtime error, got {:?}", result),
+        }
+
+        // Errors don't leave frames or values behind
+        assert!(vm.stack().is_empty());
+        assert_eq!(vm.frames().count(), 0);
+        assert_eq!(vm.call_function::<i64>("add", (1i64, 2i64)).unwrap(), 3);
+        assert_eq!(vm.error(), None);
+    }
+
+    #[test]
+    fn test_native_closures() {
+        let (_, mut vm) = load(LIBRARY);
+
+        let native = NativeObject::new("twice", 1, |arguments| match arguments {
+            [Value::F64(value)] => Ok(Value::F64(value * 2.0)),
+            _ => Err(String::from("Expected a number")),
+        });
+
+        let result = vm.apply(native.clone().to_value(), vec![21i64.to_value()]).unwrap();
+        assert_eq!(result, Value::F64(42.0));
+
+        match vm.apply(native.to_value(), vec![true.to_value()]) {
+            Err(CallError::Runtime(error)) => {
+                assert_eq!(error.function, "twice");
+                assert_eq!(error.message, "Expected a number");
+            },
+            result => panic!("Expected a runtime error, got {:?}", result),
+        }
+
+        assert!(vm.stack().is_empty());
+
+        // Typed closures convert their arguments and result
+        let increment = Closure::new("increment", |x: i64| x + 1);
+        assert_eq!(vm.call_function::<i64>("twice", (increment, 5i64)).unwrap(), 7);
+
+        let flip = Closure::new("flip", |x: bool| !x);
+        match vm.call_function::<i64>("twice", (flip, 5i64)) {
+            Err(CallError::Runtime(error)) => assert_eq!(error.message, "Expected bool, instead got int"),
+            result => panic!("Expected a runtime error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_call_checks_argument_types() {
+        let mut function = compile(LIBRARY);
+
+        let mut lexer = Lexer::new(LIBRARY);
+        let mut parser = Parser::new(&mut lexer);
+        let model = check_program(&parse_file(&mut parser)).unwrap();
+
+        let mut vm = VM::new(&mut function);
+        for signature in model.signatures() {
+            vm.set_signature(signature.clone());
+        }
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+
+        match vm.call_function::<i64>("add", (1i64, "two")) {
+            Err(CallError::Argument { function, index: 1, expected, found }) => {
+                assert_eq!((function.as_str(), expected.as_str(), found.as_str()), ("add", "int", "string"));
+            },
+            result => panic!("Expected an argument error, got {:?}", result),
+        }
+
+        match vm.call_function::<i64>("get", (vec![true], 0i64)) {
+            Err(error) => assert_eq!(error.to_string(), "Argument 1 of 'get' should be [int], instead got array"),
+            result => panic!("Expected an argument error, got {:?}", result),
+        }
+
+        // Nothing was pushed for the rejected calls
+        assert!(vm.stack().is_empty());
+        assert_eq!(vm.call_function::<i64>("add", (1i64, 2i64)).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_interrupt_spends_fuel_once() {
+        let mut function = compile("
+            fn double(x: int) -> int {
+                return x * 2;
+            }
+
+            double(3);
+        ");
+
+        let uninterrupted = Rc::new(RefCell::new(Recorder::default()));
+        let mut unlimited = VM::new(&mut function.clone());
+        unlimited.add_hooks(uninterrupted.clone());
+        unlimited.set_fuel(Some(u64::MAX));
+        assert!(matches!(unlimited.run(), InterpretationResult::OK));
+        let needed = u64::MAX - unlimited.fuel().unwrap();
+
+        let recorder = Rc::new(RefCell::new(Recorder {
+            stop_after: Some(5),
+            ..Recorder::default()
+        }));
+
+        let mut vm = VM::new(&mut function);
+        vm.add_hooks(recorder.clone());
+        vm.set_fuel(Some(needed));
+        assert!(matches!(vm.run(), InterpretationResult::INTERRUPTED));
+
+        // The interrupted instruction is paid for and seen by the hooks only once it runs
+        recorder.borrow_mut().stop_after = None;
+        assert!(matches!(vm.run(), InterpretationResult::OK), "{:?}", vm.error());
+        assert_eq!(vm.fuel(), Some(0));
+        assert_eq!(recorder.borrow().instructions, uninterrupted.borrow().instructions);
+    }
+
+    #[test]
+    fn test_call_stopped_by_fuel() {
+        let (_, mut vm) = load(LIBRARY);
+
+        vm.set_fuel(Some(3));
+        match vm.call_function::<i64>("add", (1i64, 2i64)) {
+            Err(CallError::Stopped(InterpretationResult::OUT_OF_FUEL)) => {},
+            result => panic!("Expected the call to run out of fuel, got {:?}", result),
+        }
+
+        vm.set_fuel(None);
+        assert_eq!(vm.call_function::<i64>("add", (1i64, 2i64)).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_tracer_output() {
+        let mut function = compile("
+            fn one() -> int {
+                return 1;
+            }
+
+            one();
+        ");
+
+        let tracer = Rc::new(RefCell::new(Tracer::new(Vec::<u8>::new(), true)));
+
+        let mut vm = VM::new(&mut function);
+        vm.add_hooks(tracer.clone());
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+
+        let output = String::from_utf8(tracer.borrow().output.clone()).unwrap();
+        assert!(output.starts_with("=> call Global with []\n"), "{output}");
+        assert!(output.contains("=> call one with []\n"), "{output}");
+        assert!(output.contains("<= return one with F64(1.0)\n"), "{output}");
+        assert!(output.contains("one       0000    3 CONSTANT 0"), "{output}");
+        assert!(output.contains("[ Object(Function("), "{output}");
+    }
+
+    #[test]
+    fn test_no_output_without_hooks() {
+        let mut function = compile("
+            fn one() -> int {
+                return 1;
+            }
+
+            one();
+        ");
+
+        let mut vm = VM::new(&mut function);
+        assert!(matches!(vm.run(), InterpretationResult::OK));
+        assert_eq!(vm.error(), None);
+    }
+
+}
@@ -0,0 +1,474 @@
+pub mod tests;
+
+use std::{
+    cell::RefCell,
+    env, fs,
+    io::{self, BufRead, Read, Write},
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::backend::{
+    coverage::Coverage,
+    debug::{disassemble_function, disassemble_program},
+    debugger::Debugger,
+    profiler::Profiler,
+    serialize::{save, EXTENSION},
+    vm::InterpretationResult,
+};
+
+use crate::engine::{
+    finish, parse,
+    repl::{Repl, Reply},
+    Diagnostic, Engine, Program,
+};
+
+use crate::frontend::{formatter::format_source, lexer::Lexer, token::TokenKind};
+
+pub const USAGE: &str = "Usage: silk <command> [options] <file | ->
+
+Commands:
+    run       Run a source or compiled file
+    check     Parse and typecheck a source file
+    build     Compile a source file
+    disasm    Disassemble a source or compiled file
+    tokens    Print the tokens of a source file
+    ast       Print the syntax tree of a source file
+    fmt       Format a source file
+    repl      Start an interactive session
+
+Options:
+    --check                     Fail on unformatted files instead of formatting them
+    --verbose                   Print the working directory and the compilation time
+    --emit=<bytecode|asm|json>  What build writes, bytecode by default
+    --output=<file>             Where build writes, \"-\" for stdout
+    --json                      Print disassemblies and syntax trees as JSON
+    --sexp                      Print syntax trees as S-expressions
+    --trace, --trace-stack      Print every instruction, and the stack too
+    --debug                     Run in the debugger
+    --profile                   Print a profile once the program finished
+    --profile-stacks=<file>     Write the profile as collapsed stacks
+    --coverage                  Print line and branch coverage
+    --coverage-lcov=<file>      Write the coverage as LCOV
+    --fuel=<amount>             Limit the instructions the program runs
+    --timeout=<ms>              Limit the time the program runs
+    --memory                    Print the peak memory of the program
+    --memory-limit=<bytes>      Limit the memory the program holds
+
+Reads the standard input when the file is \"-\".";
+
+// Exit codes, by the kind of error that stopped the command
+pub const EXIT_RUNTIME_ERROR: i32 = 1; // Including programs running out of fuel or time
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_PARSE_ERROR: i32 = 3;
+pub const EXIT_TYPE_ERROR: i32 = 4; // Including code the compiler doesn't support
+pub const EXIT_INPUT_ERROR: i32 = 5; // Unreadable or unwritable files, invalid compiled programs and unsavable ones
+pub const EXIT_UNFORMATTED: i32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Subcommand {
+    Run,
+    Check,
+    Build,
+    Disasm,
+    Tokens,
+    Ast,
+    Fmt,
+    Repl,
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Emit {
+    #[default]
+    Bytecode,
+    Assembly,
+    Json,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    pub verbose: bool,
+    pub check: bool,
+    pub emit: Emit,
+    pub output: Option<String>,
+    pub json: bool,
+    pub sexp: bool,
+    pub trace: bool,
+    pub trace_stack: bool,
+    pub debug: bool,
+    pub profile: bool,
+    pub profile_stacks: Option<String>,
+    pub coverage: bool,
+    pub coverage_lcov: Option<String>,
+    pub fuel: Option<u64>,
+    pub timeout: Option<u64>,
+    pub memory: bool,
+    pub memory_limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Command {
+    pub subcommand: Subcommand,
+    pub input: Option<Input>,
+    pub options: Options,
+}
+
+pub fn parse_arguments(args: &[String]) -> Result<Command, Diagnostic> {
+    let subcommand = match args.first().map(String::as_str) {
+        Some("run") => Subcommand::Run,
+        Some("check") => Subcommand::Check,
+        Some("build") => Subcommand::Build,
+        Some("disasm") => Subcommand::Disasm,
+        Some("tokens") => Subcommand::Tokens,
+        Some("ast") => Subcommand::Ast,
+        Some("fmt") => Subcommand::Fmt,
+        Some("repl") => Subcommand::Repl,
+        Some("help" | "--help" | "-h") => Subcommand::Help,
+        Some(command) => return Err(usage(&format!("Unknown command '{command}'"))),
+        None => return Err(usage("Missing command")),
+    };
+
+    let mut options = Options::default();
+    let mut inputs = vec![];
+
+    for arg in &args[1..] {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+
+        match (name, value) {
+            ("--verbose", None) => options.verbose = true,
+            ("--check", None) => options.check = true,
+            ("--json", None) => options.json = true,
+            ("--sexp", None) => options.sexp = true,
+            ("--trace", None) => options.trace = true,
+            ("--trace-stack", None) => options.trace_stack = true,
+            ("--debug", None) => options.debug = true,
+            ("--profile", None) => options.profile = true,
+            ("--coverage", None) => options.coverage = true,
+            ("--memory", None) => options.memory = true,
+            ("--emit", Some("bytecode")) => options.emit = Emit::Bytecode,
+            ("--emit", Some("asm")) => options.emit = Emit::Assembly,
+            ("--emit", Some("json")) => options.emit = Emit::Json,
+            ("--output", Some(path)) => options.output = Some(path.to_string()),
+            ("--profile-stacks", Some(path)) => options.profile_stacks = Some(path.to_string()),
+            ("--coverage-lcov", Some(path)) => options.coverage_lcov = Some(path.to_string()),
+            ("--fuel", Some(value)) => options.fuel = Some(number_option(name, value)?),
+            ("--timeout", Some(value)) => options.timeout = Some(number_option(name, value)?),
+            ("--memory-limit", Some(value)) => options.memory_limit = Some(number_option(name, value)?),
+            ("-", None) => inputs.push(Input::Stdin),
+            (name, _) if name.starts_with('-') => return Err(usage(&format!("Invalid option '{arg}'"))),
+            _ => inputs.push(Input::File(PathBuf::from(arg))),
+        }
+    }
+
+    if inputs.len() > 1 {
+        return Err(usage("Expected a single file"))
+    }
+
+    let input = inputs.pop();
+    match (subcommand, &input) {
+        (Subcommand::Repl | Subcommand::Help, Some(_)) => return Err(usage("Unexpected file")),
+        (Subcommand::Repl | Subcommand::Help, None) => {},
+        (_, None) => return Err(usage("Missing file")),
+        _ => {},
+    }
+
+    // The debugger reads its commands from stdin
+    if options.debug && input == Some(Input::Stdin) {
+        return Err(usage("Can't debug a program read from stdin"))
+    }
+
+    Ok(Command { subcommand, input, options })
+}
+
+pub fn exit_code(diagnostic: &Diagnostic) -> i32 {
+    match diagnostic {
+        Diagnostic::Parse(_) => EXIT_PARSE_ERROR,
+        Diagnostic::Type(_) | Diagnostic::Compile(_) => EXIT_TYPE_ERROR,
+        Diagnostic::Runtime(_) | Diagnostic::Stopped(_) => EXIT_RUNTIME_ERROR,
+        Diagnostic::Io(_) | Diagnostic::Write { .. } | Diagnostic::Load(_) | Diagnostic::Save(_) => EXIT_INPUT_ERROR,
+        Diagnostic::Verification(_) => EXIT_INPUT_ERROR,
+        Diagnostic::Usage(_) => EXIT_USAGE,
+        Diagnostic::Unformatted(_) => EXIT_UNFORMATTED,
+    }
+}
+
+// Runs a command line, without the program name, and returns the exit code
+pub fn main(args: &[String]) -> i32 {
+    let result = parse_arguments(args).and_then(|command| execute(&command));
+
+    match result {
+        Ok(()) => 0,
+        Err(diagnostic) => {
+            eprintln!("{diagnostic}");
+            exit_code(&diagnostic)
+        },
+    }
+}
+
+fn execute(command: &Command) -> Result<(), Diagnostic> {
+    let options = &command.options;
+
+    if options.verbose {
+        if let Ok(path) = env::current_dir() {
+            eprintln!("=> Directory: {:?}", path.as_path());
+        }
+    }
+
+    let engine = engine(options);
+    let input = match &command.input {
+        Some(input) => input,
+        None => match command.subcommand {
+            Subcommand::Repl => return repl(engine),
+            _ => {
+                println!("{USAGE}");
+                return Ok(())
+            },
+        },
+    };
+
+    let (name, bytes) = read_input(input)?;
+
+    match command.subcommand {
+        Subcommand::Tokens => return tokens(&source(bytes)?),
+        Subcommand::Ast => {
+            let ast = parse(&source(bytes)?)?;
+            match (options.json, options.sexp) {
+                (true, _) => println!("{}", ast.to_json()),
+                (_, true) => println!("{}", ast.to_sexp()),
+                _ => print!("{ast}"),
+            }
+
+            return Ok(())
+        },
+        Subcommand::Fmt => return format(&name, &source(bytes)?, input, options),
+        _ => {},
+    }
+
+    let start = Instant::now();
+    let program = engine.compile_bytes(&name, bytes)?;
+
+    if options.verbose {
+        eprintln!("=> Compiled {name} in {:?}", start.elapsed());
+    }
+
+    match command.subcommand {
+        Subcommand::Run => run(&engine, &program, options),
+        Subcommand::Build => build(&program, input, options),
+        Subcommand::Disasm => {
+            let program = disassemble_program(&program.function);
+            match options.json {
+                true => println!("{}", program.to_json()),
+                false => print!("{program}"),
+            }
+
+            Ok(())
+        },
+        _ => Ok(()),
+    }
+}
+
+fn engine(options: &Options) -> Engine {
+    let mut engine = Engine::new();
+    if options.trace || options.trace_stack {
+        engine.set_trace(Some(options.trace_stack));
+    }
+
+    engine.set_fuel(options.fuel);
+    engine.set_timeout(options.timeout.map(Duration::from_millis));
+    engine.set_memory_limit(options.memory_limit.map(|limit| limit as usize));
+    engine
+}
+
+fn read_input(input: &Input) -> Result<(String, Vec<u8>), Diagnostic> {
+    let mut bytes = vec![];
+
+    match input {
+        Input::Stdin => {
+            io::stdin().read_to_end(&mut bytes).map_err(|error| Diagnostic::Io(error.to_string()))?;
+            Ok((String::from("<stdin>"), bytes))
+        },
+        Input::File(path) => {
+            bytes = fs::read(path).map_err(|error| Diagnostic::Io(error.to_string()))?;
+            Ok((path.display().to_string(), bytes))
+        },
+    }
+}
+
+fn source(bytes: Vec<u8>) -> Result<String, Diagnostic> {
+    String::from_utf8(bytes).map_err(|error| Diagnostic::Io(error.to_string()))
+}
+
+// Files are rewritten in place, unless only checked
+fn format(name: &str, source: &str, input: &Input, options: &Options) -> Result<(), Diagnostic> {
+    let formatted = format_source(source).map_err(Diagnostic::Parse)?;
+
+    if options.check {
+        return match formatted == source {
+            true => Ok(()),
+            false => Err(Diagnostic::Unformatted(name.to_string())),
+        }
+    }
+
+    match input {
+        Input::Stdin => print!("{formatted}"),
+        Input::File(path) if formatted != source => {
+            fs::write(path, formatted).map_err(|error| Diagnostic::Write {
+                path: path.display().to_string(),
+                error: error.to_string(),
+            })?;
+        },
+        Input::File(_) => {},
+    }
+
+    Ok(())
+}
+
+fn tokens(source: &str) -> Result<(), Diagnostic> {
+    if source.is_empty() {
+        return Ok(())
+    }
+
+    let mut lexer = Lexer::new(source);
+    let mut token = lexer.next_token();
+
+    while token.kind != TokenKind::EOF {
+        println!("{}:{}\t{:?}\t{}", token.line, token.column, token.kind, token.value);
+        token = lexer.next_token();
+    }
+
+    Ok(())
+}
+
+fn build(program: &Program, input: &Input, options: &Options) -> Result<(), Diagnostic> {
+    let (output, extension) = match options.emit {
+        Emit::Bytecode => (save(&program.function).map_err(Diagnostic::Save)?, EXTENSION),
+        Emit::Assembly => (disassemble_function(&program.function).into_bytes(), "silks"),
+        Emit::Json => (disassemble_program(&program.function).to_json().into_bytes(), "json"),
+    };
+
+    // Programs read from the standard input are written to the standard output
+    let path = match (&options.output, input) {
+        (Some(path), _) => path.clone(),
+        (None, Input::Stdin) => String::from("-"),
+        (None, Input::File(path)) => path.with_extension(extension).display().to_string(),
+    };
+
+    let result = match path.as_str() {
+        "-" => io::stdout().write_all(&output),
+        path => fs::write(path, output),
+    };
+
+    result.map_err(|error| Diagnostic::Write { path, error: error.to_string() })
+}
+
+fn run(engine: &Engine, program: &Program, options: &Options) -> Result<(), Diagnostic> {
+    let mut vm = engine.load(program)?;
+
+    if options.debug {
+        let mut debugger = Debugger::new(io::stdin().lock(), io::stdout());
+        if let Some(source) = &program.source {
+            debugger.set_source(source);
+        }
+
+        vm.add_hooks(debugger);
+    }
+
+    let profiler = Rc::new(RefCell::new(Profiler::new()));
+    if options.profile || options.profile_stacks.is_some() {
+        vm.add_hooks(profiler.clone());
+    }
+
+    // Every function of the program is known before it runs
+    let coverage = match options.coverage || options.coverage_lcov.is_some() {
+        true => Some(Rc::new(RefCell::new(Coverage::new(&program.name, &program.function)))),
+        false => None,
+    };
+
+    if let Some(coverage) = &coverage {
+        vm.add_hooks(coverage.clone());
+    }
+
+    if options.memory {
+        vm.set_memory_tracking(true);
+    }
+
+    let result = vm.run();
+
+    if options.memory {
+        eprintln!("Peak memory: {}", vm.peak_memory());
+    }
+
+    if options.profile {
+        eprint!("{}", profiler.borrow().report());
+    }
+
+    if let Some(path) = &options.profile_stacks {
+        if let Err(error) = fs::write(path, profiler.borrow().collapsed_stacks()) {
+            eprintln!("Couldn't write profile stacks : {error}");
+        }
+    }
+
+    if let Some(coverage) = &coverage {
+        if options.coverage {
+            eprint!("{}", coverage.borrow().report());
+        }
+
+        if let Some(path) = &options.coverage_lcov {
+            if let Err(error) = fs::write(path, coverage.borrow().lcov()) {
+                eprintln!("Couldn't write coverage : {error}");
+            }
+        }
+    }
+
+    match finish(&vm, result) {
+        // Quitting the debugger isn't a failure
+        Err(Diagnostic::Stopped(InterpretationResult::INTERRUPTED)) => Ok(()),
+        result => result,
+    }
+}
+
+fn repl(engine: Engine) -> Result<(), Diagnostic> {
+    let mut repl = Repl::new(engine);
+    let mut lines = io::stdin().lock().lines();
+    let mut prompt = "> ";
+
+    loop {
+        print!("{prompt}");
+        let _ = io::stdout().flush();
+
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => break,
+        };
+
+        prompt = "> ";
+        match repl.feed(&line) {
+            Ok(Reply::Incomplete) => prompt = ". ",
+            Ok(Reply::Done) => {},
+            Ok(Reply::Output(output)) => println!("{output}"),
+            Err(diagnostic) => eprintln!("{diagnostic}"),
+        }
+    }
+
+    println!();
+    Ok(())
+}
+
+fn usage(message: &str) -> Diagnostic {
+    Diagnostic::Usage(format!("{message}\n\n{USAGE}"))
+}
+
+fn number_option(name: &str, value: &str) -> Result<u64, Diagnostic> {
+    value.parse::<u64>().map_err(|_| usage(&format!("Invalid number '{value}' for {name}")))
+}
@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use crate::cli::{
+        exit_code, main, parse_arguments, Emit, Input, Subcommand,
+        EXIT_INPUT_ERROR, EXIT_PARSE_ERROR, EXIT_RUNTIME_ERROR, EXIT_TYPE_ERROR, EXIT_UNFORMATTED, EXIT_USAGE,
+    };
+
+    use crate::engine::{Diagnostic, Engine};
+
+    fn arguments(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let command = parse_arguments(&arguments("run program.silk --fuel=100 --trace")).unwrap();
+        assert_eq!(command.subcommand, Subcommand::Run);
+        assert_eq!(command.input, Some(Input::File(PathBuf::from("program.silk"))));
+        assert_eq!(command.options.fuel, Some(100));
+        assert!(command.options.trace);
+        assert!(!command.options.verbose);
+
+        let command = parse_arguments(&arguments("build - --emit=asm --output=program.silks")).unwrap();
+        assert_eq!(command.subcommand, Subcommand::Build);
+        assert_eq!(command.input, Some(Input::Stdin));
+        assert_eq!(command.options.emit, Emit::Assembly);
+        assert_eq!(command.options.output.as_deref(), Some("program.silks"));
+
+        let command = parse_arguments(&arguments("repl")).unwrap();
+        assert_eq!(command.subcommand, Subcommand::Repl);
+        assert_eq!(command.input, None);
+
+        for line in ["check a.silk", "disasm a.silkc --json", "tokens a.silk", "ast a.silk --sexp", "fmt a.silk", "help"] {
+            assert!(parse_arguments(&arguments(line)).is_ok(), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        for line in [
+            "",
+            "execute a.silk",
+            "run",
+            "run a.silk b.silk",
+            "run a.silk --fuel=lots",
+            "run a.silk --unknown",
+            "build a.silk --emit=binary",
+            "repl a.silk",
+            "run - --debug",
+        ] {
+            match parse_arguments(&arguments(line)) {
+                Err(diagnostic) => assert_eq!(exit_code(&diagnostic), EXIT_USAGE, "{line}"),
+                Ok(command) => panic!("Expected a usage error for '{line}', instead got {:?}", command),
+            }
+        }
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let engine = Engine::new();
+
+        assert_eq!(exit_code(&engine.compile_str("let = 1;").unwrap_err()), EXIT_PARSE_ERROR);
+        assert_eq!(exit_code(&engine.compile_str("1 + true;").unwrap_err()), EXIT_TYPE_ERROR);
+        assert_eq!(exit_code(&engine.eval("[1][2];").unwrap_err()), EXIT_RUNTIME_ERROR);
+        assert_eq!(exit_code(&engine.compile_bytes("<stdin>", vec![0xff]).unwrap_err()), EXIT_INPUT_ERROR);
+    }
+
+    #[test]
+    fn test_format_file() {
+        let path = env::temp_dir().join(format!("silk_fmt_{}.silk", std::process::id()));
+        let file = path.display().to_string();
+        fs::write(&path, "let x=1;// One\n").unwrap();
+
+        assert_eq!(main(&arguments(&format!("fmt {file} --check"))), EXIT_UNFORMATTED);
+        assert_eq!(main(&arguments(&format!("fmt {file}"))), 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "let x = 1; // One\n");
+        assert_eq!(main(&arguments(&format!("fmt {file} --check"))), 0);
+
+        fs::write(&path, "let = 1;").unwrap();
+        assert_eq!(main(&arguments(&format!("fmt {file}"))), EXIT_PARSE_ERROR);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_build_write_error() {
+        let path = env::temp_dir().join(format!("silk_build_{}.silk", std::process::id()));
+        let file = path.display().to_string();
+        fs::write(&path, "let x = 1;").unwrap();
+
+        let missing = env::temp_dir().join("silk_missing_directory").join("program.silkc");
+        assert_eq!(main(&arguments(&format!("build {file} --output={}", missing.display()))), EXIT_INPUT_ERROR);
+
+        fs::remove_file(&path).unwrap();
+
+        // Failed writes aren't reported as reads
+        let diagnostic = Diagnostic::Write { path: String::from("out.silkc"), error: String::from("denied") };
+        assert_eq!(diagnostic.to_string(), "Couldn't write out.silkc : denied");
+        assert_eq!(exit_code(&diagnostic), EXIT_INPUT_ERROR);
+    }
+}
@@ -0,0 +1,331 @@
+pub mod tests;
+pub mod repl;
+
+use std::{
+    cell::RefCell,
+    fmt, fs, io,
+    path::Path,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::backend::{
+    bytecode::Chunk,
+    compiler::Compiler,
+    object::{FunctionObject, NativeObject, Object},
+    serialize::{is_compiled, load, LoadError, SaveError},
+    value::Value,
+    verifier::VerificationError,
+    vm::{hooks::Tracer, InterpretationResult, RuntimeError, VM},
+};
+
+use crate::frontend::{
+    ast,
+    lexer::Lexer,
+    parser::{parse_file, Parser},
+    typecheck::{check_file, model::SemanticModel, types::Type, FunctionSignature, TypeError},
+};
+
+// Everything that can stop a program from being compiled or from running to completion
+#[derive(Debug)]
+pub enum Diagnostic {
+    Io(String), // Reading a file
+    Write { path: String, error: String }, // "-" for the standard output
+    Load(LoadError),
+    Save(SaveError),
+    Parse(Vec<String>),
+    Type(TypeError),
+    Compile(Vec<String>),
+    Verification(VerificationError),
+    Runtime(RuntimeError),
+    Stopped(InterpretationResult), // Out of fuel, past the deadline or interrupted by a hook
+    Usage(String), // Invalid command line or REPL command
+    Unformatted(String), // A file differing from its formatted version
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::Io(error) => write!(f, "Couldn't read code file : {error}"),
+            Diagnostic::Write { path, error } if path == "-" => write!(f, "Couldn't write to the standard output : {error}"),
+            Diagnostic::Write { path, error } => write!(f, "Couldn't write {path} : {error}"),
+            Diagnostic::Load(error) => write!(f, "{error}"),
+            Diagnostic::Save(error) => write!(f, "{error}"),
+            Diagnostic::Parse(errors) => write!(f, "{}", errors.join("\n")),
+            Diagnostic::Type(error) => write!(f, "{error}"),
+            Diagnostic::Compile(errors) => write!(f, "{}", errors.join("\n")),
+            Diagnostic::Verification(error) => write!(f, "{error}"),
+            Diagnostic::Runtime(error) => write!(f, "{error}"),
+            Diagnostic::Stopped(InterpretationResult::OUT_OF_FUEL) => write!(f, "Program ran out of fuel"),
+            Diagnostic::Stopped(InterpretationResult::TIMED_OUT) => write!(f, "Program exceeded its time limit"),
+            Diagnostic::Stopped(result) => write!(f, "Program stopped before finishing: {result:?}"),
+            Diagnostic::Usage(message) => write!(f, "{message}"),
+            Diagnostic::Unformatted(name) => write!(f, "{name} isn't formatted"),
+        }
+    }
+}
+
+// A compiled program, ready to be run any number of times
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub name: String,
+    pub function: FunctionObject,
+    pub source: Option<String>, // Compiled files are loaded without their source
+    pub signatures: Vec<FunctionSignature>, // Of its global functions, unknown for compiled files
+}
+
+// Where programs write, natives capture it through Engine::output
+#[derive(Clone)]
+pub struct Output(Rc<RefCell<Box<dyn io::Write>>>);
+
+impl io::Write for Output {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+struct Native {
+    signature: FunctionSignature,
+    object: NativeObject,
+}
+
+// Runs the whole pipeline, from source to a finished VM, with the same configuration for every program
+pub struct Engine {
+    natives: Vec<Native>,
+    fuel: Option<u64>,
+    timeout: Option<Duration>,
+    memory_limit: Option<usize>,
+    output: Output,
+    trace: Option<bool>, // Traces instructions when set, and the stack too when true
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self {
+            natives: vec![],
+            fuel: None,
+            timeout: None,
+            memory_limit: None,
+            output: Output(Rc::new(RefCell::new(Box::new(io::stdout())))),
+            trace: None,
+        }
+    }
+
+    // Natives are typechecked like global functions and defined before the program runs
+    pub fn add_native(
+        &mut self,
+        name: &str,
+        parameters: Vec<Type>,
+        return_type: Type,
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) {
+        let object = NativeObject::new(name, parameters.len(), function);
+        let signature = FunctionSignature {
+            name: name.to_string(),
+            parameters,
+            return_type,
+        };
+
+        self.natives.retain(|native| native.signature.name != name);
+        self.natives.push(Native { signature, object });
+    }
+
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    // The deadline of a program starts when it's loaded
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+    }
+
+    pub fn set_output(&mut self, output: impl io::Write + 'static) {
+        *self.output.0.borrow_mut() = Box::new(output);
+    }
+
+    pub fn output(&self) -> Output {
+        self.output.clone()
+    }
+
+    pub fn set_trace(&mut self, trace: Option<bool>) {
+        self.trace = trace;
+    }
+
+    // Compiling
+
+    pub fn compile_str(&self, source: &str) -> Result<Program, Diagnostic> {
+        let (function, signatures) = self.compile_source(source)?;
+
+        Ok(Program {
+            name: String::from("<string>"),
+            function,
+            source: Some(source.to_string()),
+            signatures,
+        })
+    }
+
+    pub fn compile_file(&self, path: &Path) -> Result<Program, Diagnostic> {
+        let bytes = fs::read(path).map_err(|error| Diagnostic::Io(error.to_string()))?;
+        self.compile_bytes(&path.display().to_string(), bytes)
+    }
+
+    // Source code is compiled, compiled programs are loaded as they are
+    pub fn compile_bytes(&self, name: &str, bytes: Vec<u8>) -> Result<Program, Diagnostic> {
+        let name = name.to_string();
+
+        if is_compiled(&bytes) {
+            let function = load(&bytes).map_err(Diagnostic::Load)?;
+            return Ok(Program { name, function, source: None, signatures: vec![] })
+        }
+
+        let source = String::from_utf8(bytes).map_err(|error| Diagnostic::Io(error.to_string()))?;
+        let (function, signatures) = self.compile_source(&source)?;
+
+        Ok(Program { name, function, source: Some(source), signatures })
+    }
+
+    fn compile_source(&self, source: &str) -> Result<(FunctionObject, Vec<FunctionSignature>), Diagnostic> {
+        let ast = parse(source)?;
+        let model = check_file(&ast, &self.signatures()).map_err(Diagnostic::Type)?;
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        let mut compiler = Compiler::new(function);
+        compiler.model = Some(&model);
+        let function = compiler.compile(&ast).clone();
+
+        if !compiler.errors.is_empty() {
+            return Err(Diagnostic::Compile(compiler.errors))
+        }
+
+        Ok((function, global_signatures(&ast, &model)))
+    }
+
+    fn signatures(&self) -> Vec<FunctionSignature> {
+        self.natives.iter().map(|native| native.signature.clone()).collect()
+    }
+
+    // Running
+
+    // A verified VM with the configuration applied, hooks can still be added before it runs
+    pub fn load(&self, program: &Program) -> Result<VM, Diagnostic> {
+        let mut vm = VM::new(&mut program.function.clone());
+        vm.verify().map_err(Diagnostic::Verification)?;
+        self.define_natives(&mut vm)?;
+
+        for signature in &program.signatures {
+            vm.set_signature(signature.clone());
+        }
+
+        if let Some(trace_stack) = self.trace {
+            vm.add_hooks(Tracer::new(self.output(), trace_stack));
+        }
+
+        self.set_limits(&mut vm);
+        Ok(vm)
+    }
+
+    fn define_natives(&self, vm: &mut VM) -> Result<(), Diagnostic> {
+        for native in &self.natives {
+            vm.set_signature(native.signature.clone());
+
+            let value = Value::Object(Object::Native(native.object.clone()));
+            if let Err(message) = vm.define_global(&native.signature.name, value) {
+                return Err(Diagnostic::Runtime(RuntimeError {
+                    message,
+                    function: native.signature.name.clone(),
+                    line: 0,
+                }))
+            }
+        }
+
+        Ok(())
+    }
+
+    // The deadline starts now
+    fn set_limits(&self, vm: &mut VM) {
+        vm.set_fuel(self.fuel);
+        vm.set_deadline(self.timeout.map(|timeout| Instant::now() + timeout));
+        vm.set_memory_limit(self.memory_limit);
+    }
+
+    // The finished VM is returned, so its globals can still be called
+    pub fn run(&self, program: &Program) -> Result<VM, Diagnostic> {
+        let mut vm = self.load(program)?;
+        let result = vm.run();
+        finish(&vm, result)?;
+
+        Ok(vm)
+    }
+
+    // Value of the last expression of the source
+    pub fn eval(&self, source: &str) -> Result<Value, Diagnostic> {
+        let program = self.compile_str(source)?;
+        let vm = self.run(&program)?;
+
+        Ok(vm.result().cloned().unwrap_or(Value::Void))
+    }
+}
+
+// Signatures of the functions declared at the top of the file, the ones the host can call
+pub fn global_signatures(file: &ast::File, model: &SemanticModel) -> Vec<FunctionSignature> {
+    file.statements.iter()
+        .filter_map(|statement| match statement {
+            ast::Statement::Expression(statement) => match statement.expression.as_ref() {
+                ast::Expression::Function(function) if function.identifier.is_some() => model.signature(function).cloned(),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn parse(source: &str) -> Result<ast::File, Diagnostic> {
+    if source.is_empty() {
+        return Err(Diagnostic::Parse(vec![String::from("Empty file.")]))
+    }
+
+    let mut lexer = Lexer::new(source);
+    let mut parser = Parser::new(&mut lexer);
+
+    let ast = parse_file(&mut parser);
+    if !parser.errors.is_empty() {
+        return Err(Diagnostic::Parse(parser.errors))
+    }
+
+    Ok(ast)
+}
+
+// Turns the result of a run into a diagnostic when the program didn't finish
+pub fn finish(vm: &VM, result: InterpretationResult) -> Result<(), Diagnostic> {
+    if let Some(error) = vm.verification_error() {
+        return Err(Diagnostic::Verification(error.clone()))
+    }
+
+    if let Some(error) = vm.error() {
+        return Err(Diagnostic::Runtime(error.clone()))
+    }
+
+    match result {
+        InterpretationResult::OK => Ok(()),
+        result => Err(Diagnostic::Stopped(result)),
+    }
+}
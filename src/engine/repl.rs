@@ -0,0 +1,237 @@
+use std::mem;
+
+use crate::backend::{
+    bytecode::Chunk,
+    compiler::Compiler,
+    debug::{disassemble_function, format_value},
+    interner::Interner,
+    object::{FunctionObject, Object},
+    value::Value,
+    vm::{hooks::Tracer, VM},
+};
+
+use crate::frontend::{
+    ast,
+    typecheck::{types::Type, Environment, FunctionSignature},
+};
+
+use super::{finish, global_signatures, parse, Diagnostic, Engine};
+
+const COMMANDS: &str = ":type <expression>, :ast <expression>, :disasm <function>";
+
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    Incomplete, // The entry goes on, on the next line
+    Done,
+    Output(String),
+}
+
+// Runs entries one after the other, each one can use the globals declared by the previous ones
+pub struct Repl {
+    engine: Engine,
+    environment: Environment,
+    globals: Interner,
+    vm: VM,
+    buffer: String,
+}
+
+impl Repl {
+    pub fn new(engine: Engine) -> Self {
+        let environment = Environment::new(&engine.signatures());
+
+        // Nothing runs until the first entry is loaded
+        let mut vm = VM::new(&mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        });
+
+        if let Some(trace_stack) = engine.trace {
+            vm.add_hooks(Tracer::new(engine.output(), trace_stack));
+        }
+
+        Self {
+            engine,
+            environment,
+            globals: Interner::new(),
+            vm,
+            buffer: String::new(),
+        }
+    }
+
+    pub fn vm(&mut self) -> &mut VM {
+        &mut self.vm
+    }
+
+    // Reads a line, entries are run once their braces are balanced
+    pub fn feed(&mut self, line: &str) -> Result<Reply, Diagnostic> {
+        if self.buffer.is_empty() && line.trim_start().starts_with(':') {
+            return self.run_command(line.trim())
+        }
+
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+
+        if brace_depth(&self.buffer) > 0 {
+            return Ok(Reply::Incomplete)
+        }
+
+        let entry = mem::take(&mut self.buffer);
+        match self.eval(&entry)? {
+            Some(value) => Ok(Reply::Output(format_value(&value))),
+            None => Ok(Reply::Done),
+        }
+    }
+
+    // Drops the lines of an unfinished entry
+    pub fn cancel(&mut self) {
+        self.buffer.clear();
+    }
+
+    // Runs an entry, returning the value of its last expression unless it's a declaration or void
+    pub fn eval(&mut self, source: &str) -> Result<Option<Value>, Diagnostic> {
+        let source = match complete_entry(source) {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+
+        let ast = parse(&source)?;
+
+        // Declarations are only kept once the entry ran, its globals are unset otherwise
+        let environment = self.environment.clone();
+        let model = self.environment.check(&ast).map_err(Diagnostic::Type)?;
+
+        let function = &mut FunctionObject {
+            chunk: Chunk::new(),
+            arity: 0,
+            name: String::from("Global"),
+        };
+
+        // Globals keep their slots from one entry to the next
+        let mut compiler = Compiler::new(function);
+        compiler.globals = mem::take(&mut self.globals);
+        compiler.model = Some(&model);
+        compiler.compile(&ast);
+        self.globals = mem::take(&mut compiler.globals);
+
+        if !compiler.errors.is_empty() {
+            self.environment = environment;
+            return Err(Diagnostic::Compile(compiler.errors))
+        }
+
+        if let Err(diagnostic) = self.run(function, global_signatures(&ast, &model)) {
+            self.environment = environment;
+            return Err(diagnostic)
+        }
+
+        // Declarations and assignments leave their value on the stack, but aren't typed as values
+        let echoed = match ast.statements.last() {
+            Some(ast::Statement::Expression(statement)) => !matches!(
+                statement.expression.as_ref(),
+                ast::Expression::Function(_) | ast::Expression::Assign(_) | ast::Expression::FieldAssign(_)
+            ),
+            _ => false,
+        };
+
+        match self.vm.result() {
+            Some(Value::Void) | None => Ok(None),
+            Some(value) if echoed => Ok(Some(value.clone())),
+            Some(_) => Ok(None),
+        }
+    }
+
+    fn run(&mut self, function: &FunctionObject, signatures: Vec<FunctionSignature>) -> Result<(), Diagnostic> {
+        self.vm.load(function);
+        for signature in signatures {
+            self.vm.set_signature(signature);
+        }
+
+        self.vm.verify().map_err(Diagnostic::Verification)?;
+        self.engine.define_natives(&mut self.vm)?;
+        self.engine.set_limits(&mut self.vm);
+
+        let result = self.vm.run();
+        finish(&self.vm, result)
+    }
+
+    pub fn type_of(&mut self, source: &str) -> Result<Type, Diagnostic> {
+        let expression = parse_expression(source)?;
+        self.environment.type_of(&expression).map_err(Diagnostic::Type)
+    }
+
+    fn run_command(&mut self, line: &str) -> Result<Reply, Diagnostic> {
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+
+        if argument.is_empty() {
+            return Err(Diagnostic::Usage(format!("Missing argument for {command}, expected one of {COMMANDS}")))
+        }
+
+        match command {
+            ":type" => Ok(Reply::Output(self.type_of(argument)?.to_string())),
+            ":ast" => Ok(Reply::Output(parse_expression(argument)?.to_string())),
+            ":disasm" => match self.vm.global(argument) {
+                Some(Value::Object(Object::Function(function))) => {
+                    Ok(Reply::Output(disassemble_function(function).trim_end().to_string()))
+                },
+                Some(_) => Err(Diagnostic::Usage(format!("'{argument}' is not a function"))),
+                None => Err(Diagnostic::Usage(format!("Undefined function '{argument}'"))),
+            },
+            command => Err(Diagnostic::Usage(format!("Unknown command {command}, expected one of {COMMANDS}"))),
+        }
+    }
+}
+
+// Statements are terminated for the user, blocks and declarations ending with a brace don't need it
+fn complete_entry(source: &str) -> Option<String> {
+    let source = source.trim();
+    if source.is_empty() {
+        return None
+    }
+
+    match source.ends_with(';') || source.ends_with('}') {
+        true => Some(source.to_string()),
+        false => Some(format!("{source};")),
+    }
+}
+
+fn parse_expression(source: &str) -> Result<Box<ast::Expression>, Diagnostic> {
+    let source = match complete_entry(source) {
+        Some(source) => source,
+        None => return Err(Diagnostic::Usage(String::from("Expected an expression"))),
+    };
+
+    let ast = parse(&source)?;
+    match ast.statements.into_iter().last() {
+        Some(ast::Statement::Expression(statement)) => Ok(statement.expression),
+        _ => Err(Diagnostic::Usage(String::from("Expected an expression"))),
+    }
+}
+
+// Open braces, skipping strings and comments
+fn brace_depth(source: &str) -> i64 {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut characters = source.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match character {
+            '"' => in_string = !in_string,
+            '\\' if in_string => { characters.next(); },
+            '/' if !in_string && characters.peek() == Some(&'/') => {
+                for character in characters.by_ref() {
+                    if character == '\n' {
+                        break
+                    }
+                }
+            },
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => depth -= 1,
+            _ => {},
+        }
+    }
+
+    depth
+}
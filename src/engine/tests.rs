@@ -0,0 +1,361 @@
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Write, rc::Rc, time::Duration};
+
+    use crate::backend::{
+        object::Object,
+        serialize::save,
+        value::Value,
+        vm::InterpretationResult,
+    };
+
+    use crate::engine::{
+        repl::{Repl, Reply},
+        Diagnostic,
+        Engine,
+    };
+    use crate::frontend::typecheck::types::Type;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buffer)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        let engine = Engine::new();
+
+        assert_eq!(engine.eval("1 + 2 * 3;").unwrap(), Value::F64(7.0));
+        assert_eq!(engine.eval("{ let x = 4; x * 2; }").unwrap(), Value::F64(8.0));
+        // Declarations are expressions too
+        let value = engine.eval("fn f() -> int { return 1; }").unwrap();
+        assert!(matches!(value, Value::Object(Object::Function(_))), "{value:?}");
+    }
+
+    #[test]
+    fn test_eval_function_values() {
+        let engine = Engine::new();
+
+        let value = engine.eval("
+            fn double(x: int) -> int { return x * 2; }
+            fn inc(x: int) -> int { return x + 1; }
+            fn apply(f: fn(int) -> int, x: int) -> int { return f(x); }
+            fn pick(first: bool) -> fn(int) -> int { if first { return double; } else { return inc; } }
+
+            let table: [fn(int) -> int] = [double, inc];
+            let g = pick(false);
+
+            fn main() -> int {
+                let local: fn(int) -> int = double;
+                return apply(local, 5) + table[1](10) + g(1) + pick(true)(3);
+            }
+            main();
+        ").unwrap();
+
+        assert_eq!(value, Value::F64(29.0));
+    }
+
+    #[test]
+    fn test_eval_anonymous_functions() {
+        let engine = Engine::new();
+
+        let value = engine.eval("
+            fn apply(f: fn(int) -> int, x: int) -> int { return f(x); }
+            let triple = fn(x: int) -> int => x * 3;
+            let six: int = triple(2);
+            apply(fn(x: int) -> int { x * 2; }, 4) + apply(fn(x: int) -> int => x + 1, 1) + six;
+        ").unwrap();
+
+        assert_eq!(value, Value::F64(16.0));
+
+        // Functions don't capture variables, using one is rejected before running
+        let result = engine.eval("fn outer(y: int) -> int { let f = fn(x: int) -> int { x + y; }; f(1); } outer(2);");
+        assert!(matches!(result, Err(Diagnostic::Type(_))), "{:?}", result.err());
+    }
+
+    #[test]
+    fn test_eval_block_scopes() {
+        let engine = Engine::new();
+
+        assert_eq!(engine.eval("let x = 1; { let x = 2; x = x + 1; } x;").unwrap(), Value::F64(1.0));
+        assert_eq!(engine.eval("let y = { let z = 2; z * 3; }; y;").unwrap(), Value::F64(6.0));
+        assert_eq!(engine.eval("if true { let z = 2; z; } else { 0; }").unwrap(), Value::F64(2.0));
+        assert!(matches!(engine.eval("{ let y = 1; } y;"), Err(Diagnostic::Type(_))));
+    }
+
+    #[test]
+    fn test_eval_structs() {
+        let engine = Engine::new();
+
+        let value = engine.eval("
+            struct Point { x: int, y: int }
+            struct Line { from: Point, to: Point }
+
+            fn moved(point: Point, by: int) -> Point {
+                point.x = point.x + by;
+                return point;
+            }
+
+            let line = Line { from: Point { x: 1, y: 2 }, to: Point { y: 4, x: 3 } };
+            let start = line.from;
+            line.to.y = 10;
+            start.x = 100;
+            let end = moved(line.to, 5);
+            line.from.x + line.to.y * 10 + end.x * 1000 + start.x * 10000;
+        ").unwrap();
+
+        // Structs are copied: neither 'start' nor 'moved' change 'line'
+        assert_eq!(value, Value::F64(1.0 + 100.0 + 8000.0 + 1000000.0));
+    }
+
+    #[test]
+    fn test_eval_deferred_assignment() {
+        let engine = Engine::new();
+
+        assert_eq!(engine.eval("let x: int; x = 3; x;").unwrap(), Value::F64(3.0));
+        assert_eq!(engine.eval("
+            fn sign(n: int) -> int {
+                let result: int;
+                if n < 0 { result = 0 - 1; } else { result = 1; }
+                return result;
+            }
+            sign(0 - 5);
+        ").unwrap(), Value::F64(-1.0));
+    }
+
+    #[test]
+    fn test_run_keeps_globals() {
+        let engine = Engine::new();
+        let program = engine.compile_str("
+            fn square(x: int) -> int {
+                return x * x;
+            }
+        ").unwrap();
+
+        // A program can be run more than once
+        engine.run(&program).unwrap();
+        let mut vm = engine.run(&program).unwrap();
+        assert_eq!(vm.call_function::<i64>("square", (9i64,)).unwrap(), 81);
+
+        // Arguments are checked against the parameter types
+        match vm.call_function::<i64>("square", (2.5f64,)) {
+            Err(error) => assert_eq!(error.to_string(), "Argument 1 of 'square' should be int, instead got number"),
+            result => panic!("Expected an argument error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let engine = Engine::new();
+
+        match engine.compile_str("let = 3;") {
+            Err(Diagnostic::Parse(errors)) => assert!(!errors.is_empty()),
+            result => panic!("Expected parse errors, instead got {:?}", result.map(|program| program.name)),
+        }
+
+        match engine.compile_str("1 + true;") {
+            Err(Diagnostic::Type(error)) => {
+                assert_eq!(error.message, "Type mismatch in infix expression: Integer != Boolean");
+                assert_eq!(error.line, 1);
+            },
+            result => panic!("Expected a type error, instead got {:?}", result.map(|program| program.name)),
+        }
+
+        // Nested functions report their errors too
+        match engine.compile_str("fn f() { while true { break; } }") {
+            Err(Diagnostic::Compile(errors)) => assert_eq!(errors, ["Compile error on line 1:23: 'break' isn't supported yet"]),
+            result => panic!("Expected a compile error, instead got {:?}", result.map(|program| program.name)),
+        }
+
+        match engine.eval("[1, 2][5];") {
+            Err(Diagnostic::Runtime(error)) => assert_eq!(error.function, "Global"),
+            result => panic!("Expected a runtime error, instead got {:?}", result),
+        }
+
+        match engine.compile_file(std::path::Path::new("missing.silk")) {
+            Err(Diagnostic::Io(_)) => {},
+            result => panic!("Expected an io error, instead got {:?}", result.map(|program| program.name)),
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut engine = Engine::new();
+        engine.set_fuel(Some(1000));
+
+        match engine.eval("while true {}") {
+            Err(Diagnostic::Stopped(InterpretationResult::OUT_OF_FUEL)) => {},
+            result => panic!("Expected to run out of fuel, instead got {:?}", result),
+        }
+
+        engine.set_fuel(None);
+        engine.set_timeout(Some(Duration::from_millis(10)));
+
+        let error = engine.eval("while true {}").unwrap_err();
+        assert_eq!(error.to_string(), "Program exceeded its time limit");
+
+        engine.set_timeout(None);
+        engine.set_memory_limit(Some(64));
+
+        match engine.eval("[1, 2, 3, 4, 5, 6, 7, 8];") {
+            Err(Diagnostic::Runtime(error)) => assert!(error.message.starts_with("Out of memory"), "{error}"),
+            result => panic!("Expected to run out of memory, instead got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_natives_write_to_output() {
+        let buffer = Buffer::default();
+        let mut engine = Engine::new();
+        engine.set_output(buffer.clone());
+
+        let output = RefCell::new(engine.output());
+        engine.add_native("print_int", vec![Type::Integer], Type::Void, move |arguments| {
+            let _ = writeln!(output.borrow_mut(), "{:?}", arguments[0]);
+            Ok(Value::Void)
+        });
+
+        engine.add_native("double", vec![Type::Integer], Type::Integer, |arguments| match arguments {
+            [Value::F64(value)] => Ok(Value::F64(value * 2.0)),
+            _ => Err(String::from("Expected a number")),
+        });
+
+        engine.eval("print_int(double(21)); print_int(1);").unwrap();
+        assert_eq!(buffer.text(), "F64(42.0)\nF64(1.0)\n");
+
+        // Natives are typechecked like functions
+        assert!(matches!(engine.compile_str("double(true);"), Err(Diagnostic::Type(_))));
+    }
+
+    #[test]
+    fn test_trace_to_output() {
+        let buffer = Buffer::default();
+        let mut engine = Engine::new();
+        engine.set_output(buffer.clone());
+        engine.set_trace(Some(false));
+
+        engine.eval("1 + 2;").unwrap();
+        assert!(buffer.text().contains("ADD"), "{}", buffer.text());
+    }
+
+    #[test]
+    fn test_compiled_file() {
+        let engine = Engine::new();
+        let program = engine.compile_str("20 + 22;").unwrap();
+
+        let path = std::env::temp_dir().join("silk_engine_test.silkc");
+        std::fs::write(&path, save(&program.function).unwrap()).unwrap();
+
+        let loaded = engine.compile_file(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(loaded.source.is_none());
+        let vm = engine.run(&loaded).unwrap();
+        assert_eq!(vm.result(), Some(&Value::F64(42.0)));
+    }
+
+    // REPL
+
+    fn feed_all(repl: &mut Repl, lines: &[&str]) -> Vec<Reply> {
+        lines.iter().map(|line| repl.feed(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_repl_persistent_globals() {
+        let mut repl = Repl::new(Engine::new());
+
+        let replies = feed_all(&mut repl, &[
+            "fn add(a: int, b: int) -> int {",
+            "    return a + b;",
+            "}",
+            "let total = add(2, 3)",
+            "total = total + 1;",
+            "add(total, 10)",
+        ]);
+
+        assert_eq!(replies, vec![
+            Reply::Incomplete,
+            Reply::Incomplete,
+            Reply::Done,
+            Reply::Done,
+            Reply::Done,
+            Reply::Output(String::from("16")),
+        ]);
+
+        assert_eq!(repl.vm().global("total"), Some(&Value::F64(6.0)));
+        assert_eq!(repl.vm().call_function::<i64>("add", (6i64, 1i64)).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_repl_errors_are_discarded() {
+        let mut repl = Repl::new(Engine::new());
+        repl.feed("let x = 1").unwrap();
+
+        // The declaration of y doesn't typecheck, so y is never declared
+        assert!(matches!(repl.feed("let y = 2; x + true"), Err(Diagnostic::Type(_))));
+        assert!(matches!(repl.feed("y"), Err(Diagnostic::Type(_))));
+
+        // Runtime errors don't break the following entries
+        assert!(matches!(repl.feed("[1][3]"), Err(Diagnostic::Runtime(_))));
+        assert_eq!(repl.feed("x + 1").unwrap(), Reply::Output(String::from("2")));
+
+        // Neither are the declarations of an entry that failed at runtime
+        assert!(matches!(repl.feed("let z = [1][3]"), Err(Diagnostic::Runtime(_))));
+        assert!(matches!(repl.feed("z"), Err(Diagnostic::Type(_))));
+    }
+
+    #[test]
+    fn test_repl_commands() {
+        let mut engine = Engine::new();
+        engine.add_native("double", vec![Type::Integer], Type::Integer, |arguments| match arguments {
+            [Value::F64(value)] => Ok(Value::F64(value * 2.0)),
+            _ => Err(String::from("Expected a number")),
+        });
+
+        let mut repl = Repl::new(engine);
+        feed_all(&mut repl, &["fn positive(x: int) -> bool { x > 0; }", "let values = [1, 2]"]);
+
+        assert_eq!(repl.feed(":type positive(double(4))").unwrap(), Reply::Output(String::from("bool")));
+        assert_eq!(repl.feed(":type values").unwrap(), Reply::Output(String::from("[int]")));
+        assert_eq!(repl.feed("double(values[1])").unwrap(), Reply::Output(String::from("4")));
+
+        match repl.feed(":disasm positive").unwrap() {
+            Reply::Output(output) => assert!(output.starts_with(".function positive 1\n"), "{output}"),
+            reply => panic!("Expected a disassembly, instead got {:?}", reply),
+        }
+
+        assert_eq!(repl.feed(":ast -1 * 2 + x").unwrap(), Reply::Output(String::from("(((-1) * 2) + x)")));
+
+        assert!(matches!(repl.feed(":disasm missing"), Err(Diagnostic::Usage(_))));
+        assert!(matches!(repl.feed(":run positive"), Err(Diagnostic::Usage(_))));
+    }
+
+    #[test]
+    fn test_repl_multiline_braces() {
+        let mut repl = Repl::new(Engine::new());
+
+        // Braces in strings and comments don't count
+        assert_eq!(repl.feed("\"{\"").unwrap(), Reply::Output(String::from("\"{\"")));
+        assert_eq!(repl.feed("if true { // }").unwrap(), Reply::Incomplete);
+        assert_eq!(repl.feed("    1;").unwrap(), Reply::Incomplete);
+        assert_eq!(repl.feed("} else { 2; }").unwrap(), Reply::Output(String::from("1")));
+
+        assert_eq!(repl.feed("{").unwrap(), Reply::Incomplete);
+        repl.cancel();
+        assert_eq!(repl.feed("3").unwrap(), Reply::Output(String::from("3")));
+    }
+}
@@ -1,3 +1,9 @@
+pub mod print;
+pub mod visit;
+pub mod tests;
+
+use visit::{walk_expression, Visitor};
+
 use super::{
     token::Token,
     typecheck::types::Type,
@@ -15,6 +21,7 @@ pub struct File {
 // Statements
 pub enum Statement {
     Let(LetStatement),
+    Struct(StructStatement),
     Expression(ExpressionStatement),
 }
 
@@ -25,6 +32,19 @@ pub struct LetStatement {
     pub expression: Option<Box<Expression>>,
 }
 
+// Fields are stored in declaration order, which gives their slot
+pub struct StructStatement {
+    pub node: Node,
+    pub identifier: Identifier,
+    pub fields: Vec<StructField>,
+    pub end: Node, // Closing brace
+}
+
+pub struct StructField {
+    pub identifier: Identifier,
+    pub annotation: Type,
+}
+
 pub struct ExpressionStatement {
     pub node: Node,
     pub expression: Box<Expression>,
@@ -48,8 +68,41 @@ pub enum Expression {
     Break(BreakExpression),
     Call(CallExpression),
     Return(ReturnExpression),
-    Access(AccessExpression),
     Index(IndexExpression),
+    Struct(StructExpression),
+    Field(FieldExpression),
+    FieldAssign(FieldAssignmentExpression),
+}
+
+impl Expression {
+    // Statements ending with a block take no semicolon, struct literals end with a brace but aren't blocks
+    pub fn ends_with_block(&self) -> bool {
+        match self {
+            Expression::Block(_) | Expression::If(_) | Expression::While(_) => true,
+            Expression::Function(function) => function.body.ends_with_block(),
+            Expression::Prefix(expression) => expression.expression.ends_with_block(),
+            Expression::Infix(expression) => expression.right_expression.ends_with_block(),
+            Expression::Assign(expression) => expression.expression.ends_with_block(),
+            Expression::FieldAssign(expression) => expression.expression.ends_with_block(),
+            Expression::Return(expression) => expression.expression.ends_with_block(),
+            _ => false,
+        }
+    }
+
+    pub fn has_struct_literal(&self) -> bool {
+        struct Finder(bool);
+
+        impl Visitor for Finder {
+            fn visit_struct_expression(&mut self, _expression: &StructExpression) {
+                self.0 = true;
+            }
+        }
+
+        let mut finder = Finder(false);
+        walk_expression(&mut finder, self);
+
+        return finder.0
+    }
 }
 
 pub struct Identifier {
@@ -79,10 +132,19 @@ pub struct BooleanLiteral {
 
 pub struct Function {
     pub node: Node,
-    pub identifier: Identifier,
+    pub identifier: Option<Identifier>, // Anonymous functions are only values
     pub parameters: Vec<FunctionParameter>,
     pub annotation: Type,
-    pub body: Box<Expression>,
+    pub body: Box<Expression>, // A block, or any expression after an arrow
+}
+
+impl Function {
+    pub fn name(&self) -> &str {
+        match &self.identifier {
+            Some(identifier) => &identifier.value,
+            None => "<anonymous>",
+        }
+    }
 }
 
 pub struct FunctionParameter {
@@ -117,6 +179,7 @@ pub struct ArrayExpression {
 pub struct BlockExpression {
     pub node: Node,
     pub statements: Vec<Statement>,
+    pub end: Node, // Closing brace
 }
 
 pub struct IfExpression {
@@ -147,14 +210,33 @@ pub struct ReturnExpression {
     pub expression: Box<Expression>,
 }
 
-pub struct AccessExpression {
-    pub node: Node,
-    pub left_expression: Box<Expression>,
-    pub right_expression: Box<Expression>,
-}
-
 pub struct IndexExpression {
     pub node: Node,
     pub indexed: Box<Expression>,
     pub index: Box<Expression>,
-}
\ No newline at end of file
+}
+
+// Fields are initialized in source order, in any order relative to the declaration
+pub struct StructExpression {
+    pub node: Node,
+    pub identifier: Identifier,
+    pub fields: Vec<FieldInitializer>,
+    pub end: Node, // Closing brace
+}
+
+pub struct FieldInitializer {
+    pub identifier: Identifier,
+    pub expression: Box<Expression>,
+}
+
+pub struct FieldExpression {
+    pub node: Node,
+    pub expression: Box<Expression>,
+    pub field: Identifier,
+}
+
+pub struct FieldAssignmentExpression {
+    pub node: Node,
+    pub target: FieldExpression,
+    pub expression: Box<Expression>,
+}
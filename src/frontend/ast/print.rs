@@ -0,0 +1,469 @@
+use std::fmt;
+
+use super::{Expression, FieldExpression, File, Node, Statement};
+
+// Source code with every operation grouped, so the shape of the tree shows without ambiguity
+
+impl fmt::Display for File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for statement in &self.statements {
+            writeln!(f, "{statement}")?;
+        }
+
+        return Ok(())
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Let(statement) => {
+                write!(f, "let {}", statement.identifier.value)?;
+
+                if let Some(annotation) = &statement.annotation {
+                    write!(f, ": {annotation}")?;
+                }
+
+                if let Some(expression) = &statement.expression {
+                    write!(f, " = {expression}")?;
+                }
+
+                write!(f, ";")
+            },
+            Statement::Struct(statement) => {
+                let fields: Vec<String> = statement.fields.iter()
+                    .map(|field| format!("{}: {}", field.identifier.value, field.annotation))
+                    .collect();
+
+                match fields.is_empty() {
+                    true => write!(f, "struct {} {{}}", statement.identifier.value),
+                    false => write!(f, "struct {} {{ {} }}", statement.identifier.value, fields.join(", ")),
+                }
+            },
+            Statement::Expression(statement) => {
+                // The parser rejects semicolons after blocks
+                match statement.expression.ends_with_block() {
+                    true => write!(f, "{}", statement.expression),
+                    false => write!(f, "{};", statement.expression),
+                }
+            },
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Identifier(identifier) => write!(f, "{}", identifier.value),
+            Expression::NumberLiteral(literal) => write!(f, "{}", literal.value),
+            Expression::CharacterLiteral(literal) => write!(f, "'{}'", literal.value),
+            Expression::StringLiteral(literal) => write!(f, "\"{}\"", literal.value),
+            Expression::BooleanLiteral(literal) => write!(f, "{}", literal.value),
+            Expression::Function(function) => {
+                let parameters: Vec<String> = function.parameters.iter()
+                    .map(|parameter| format!("{}: {}", parameter.identifier.value, parameter.annotation))
+                    .collect();
+
+                let name = function.identifier.as_ref().map_or(String::new(), |identifier| format!(" {}", identifier.value));
+
+                match function.body.as_ref() {
+                    Expression::Block(_) => write!(f, "fn{name}({}) -> {} {}", parameters.join(", "), function.annotation, function.body),
+                    // The body of an arrow extends as far as it can
+                    body => write!(f, "(fn{name}({}) -> {} => {body})", parameters.join(", "), function.annotation),
+                }
+            },
+            Expression::Prefix(expression) => write!(f, "({}{})", expression.operator, expression.expression),
+            Expression::Infix(expression) => {
+                write!(f, "({} {} {})", expression.left_expression, expression.operator, expression.right_expression)
+            },
+            Expression::Assign(expression) => write!(f, "({} = {})", expression.identifier.value, expression.expression),
+            Expression::Array(expression) => write!(f, "[{}]", join(&expression.elements, ", ")),
+            Expression::Block(block) => {
+                if block.statements.is_empty() {
+                    return write!(f, "{{}}")
+                }
+
+                write!(f, "{{")?;
+                for statement in &block.statements {
+                    write!(f, " {statement}")?;
+                }
+                write!(f, " }}")
+            },
+            Expression::If(expression) => {
+                write!(f, "if {} {}", Condition(&expression.condition), expression.consequence)?;
+
+                match &expression.alternative {
+                    Some(alternative) => write!(f, " else {alternative}"),
+                    None => Ok(()),
+                }
+            },
+            Expression::While(expression) => write!(f, "while {} {}", Condition(&expression.condition), expression.iteration),
+            Expression::Break(_) => write!(f, "break"),
+            Expression::Call(expression) => write!(f, "{}({})", expression.identifier, join(&expression.arguments, ", ")),
+            Expression::Return(expression) => write!(f, "(return {})", expression.expression),
+            Expression::Index(expression) => write!(f, "{}[{}]", expression.indexed, expression.index),
+            Expression::Struct(expression) => {
+                let fields: Vec<String> = expression.fields.iter()
+                    .map(|field| format!("{}: {}", field.identifier.value, field.expression))
+                    .collect();
+
+                match fields.is_empty() {
+                    true => write!(f, "{} {{}}", expression.identifier.value),
+                    false => write!(f, "{} {{ {} }}", expression.identifier.value, fields.join(", ")),
+                }
+            },
+            Expression::Field(expression) => write!(f, "{}.{}", expression.expression, expression.field.value),
+            Expression::FieldAssign(expression) => write!(
+                f,
+                "({}.{} = {})",
+                expression.target.expression,
+                expression.target.field.value,
+                expression.expression,
+            ),
+        }
+    }
+}
+
+// Conditions holding a struct literal are grouped, or its brace would open the block
+struct Condition<'a>(&'a Expression);
+
+impl fmt::Display for Condition<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.has_struct_literal() {
+            true => write!(f, "({})", self.0),
+            false => write!(f, "{}", self.0),
+        }
+    }
+}
+
+fn join(expressions: &[Box<Expression>], separator: &str) -> String {
+    let expressions: Vec<String> = expressions.iter().map(|expression| expression.to_string()).collect();
+    expressions.join(separator)
+}
+
+// S-expressions, one list per node with its kind first
+
+impl File {
+    pub fn to_sexp(&self) -> String {
+        let statements: Vec<String> = self.statements.iter().map(Statement::to_sexp).collect();
+        match statements.is_empty() {
+            true => String::from("(file)"),
+            false => format!("(file {})", statements.join(" ")),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let statements: Vec<String> = self.statements.iter().map(Statement::to_json).collect();
+        format!("{{\"kind\":\"File\",\"statements\":[{}]}}", statements.join(","))
+    }
+}
+
+impl Statement {
+    pub fn to_sexp(&self) -> String {
+        match self {
+            Statement::Let(statement) => {
+                let identifier = match &statement.annotation {
+                    Some(annotation) => format!("({} {annotation})", statement.identifier.value),
+                    None => statement.identifier.value.clone(),
+                };
+
+                match &statement.expression {
+                    Some(expression) => format!("(let {identifier} {})", expression.to_sexp()),
+                    None => format!("(let {identifier})"),
+                }
+            },
+            Statement::Struct(statement) => {
+                let fields: Vec<String> = statement.fields.iter()
+                    .map(|field| format!(" ({} {})", field.identifier.value, field.annotation))
+                    .collect();
+
+                format!("(struct {}{})", statement.identifier.value, fields.concat())
+            },
+            Statement::Expression(statement) => statement.expression.to_sexp(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        match self {
+            Statement::Let(statement) => {
+                let annotation = match &statement.annotation {
+                    Some(annotation) => json_string(&annotation.to_string()),
+                    None => String::from("null"),
+                };
+
+                let expression = match &statement.expression {
+                    Some(expression) => expression.to_json(),
+                    None => String::from("null"),
+                };
+
+                format!(
+                    "{{\"kind\":\"Let\",{},\"identifier\":{},\"annotation\":{annotation},\"expression\":{expression}}}",
+                    json_location(&statement.node),
+                    json_string(&statement.identifier.value),
+                )
+            },
+            Statement::Struct(statement) => {
+                let fields: Vec<String> = statement.fields.iter()
+                    .map(|field| format!(
+                        "{{\"identifier\":{},\"annotation\":{}}}",
+                        json_string(&field.identifier.value),
+                        json_string(&field.annotation.to_string()),
+                    ))
+                    .collect();
+
+                format!(
+                    "{{\"kind\":\"Struct\",{},\"identifier\":{},\"fields\":[{}]}}",
+                    json_location(&statement.node),
+                    json_string(&statement.identifier.value),
+                    fields.join(","),
+                )
+            },
+            Statement::Expression(statement) => format!(
+                "{{\"kind\":\"Expression\",{},\"expression\":{}}}",
+                json_location(&statement.node),
+                statement.expression.to_json(),
+            ),
+        }
+    }
+}
+
+impl Expression {
+    pub fn to_sexp(&self) -> String {
+        match self {
+            Expression::Identifier(identifier) => identifier.value.clone(),
+            Expression::NumberLiteral(literal) => literal.value.to_string(),
+            Expression::CharacterLiteral(literal) => format!("'{}'", literal.value),
+            Expression::StringLiteral(literal) => format!("\"{}\"", literal.value),
+            Expression::BooleanLiteral(literal) => literal.value.to_string(),
+            Expression::Function(function) => {
+                let parameters: Vec<String> = function.parameters.iter()
+                    .map(|parameter| format!("({} {})", parameter.identifier.value, parameter.annotation))
+                    .collect();
+
+                // Anonymous functions have no name before their parameters
+                let name = match &function.identifier {
+                    Some(identifier) => format!("{} ", identifier.value),
+                    None => String::new(),
+                };
+
+                format!("(fn {name}({}) {} {})", parameters.join(" "), function.annotation, function.body.to_sexp())
+            },
+            Expression::Prefix(expression) => format!("({} {})", expression.operator, expression.expression.to_sexp()),
+            Expression::Infix(expression) => format!(
+                "({} {} {})",
+                expression.operator,
+                expression.left_expression.to_sexp(),
+                expression.right_expression.to_sexp(),
+            ),
+            Expression::Assign(expression) => format!("(= {} {})", expression.identifier.value, expression.expression.to_sexp()),
+            Expression::Array(expression) => sexp_list("array", &expression.elements),
+            Expression::Block(block) => {
+                let statements: Vec<String> = block.statements.iter().map(Statement::to_sexp).collect();
+                match statements.is_empty() {
+                    true => String::from("(block)"),
+                    false => format!("(block {})", statements.join(" ")),
+                }
+            },
+            Expression::If(expression) => match &expression.alternative {
+                Some(alternative) => format!(
+                    "(if {} {} {})",
+                    expression.condition.to_sexp(),
+                    expression.consequence.to_sexp(),
+                    alternative.to_sexp(),
+                ),
+                None => format!("(if {} {})", expression.condition.to_sexp(), expression.consequence.to_sexp()),
+            },
+            Expression::While(expression) => {
+                format!("(while {} {})", expression.condition.to_sexp(), expression.iteration.to_sexp())
+            },
+            Expression::Break(_) => String::from("(break)"),
+            Expression::Call(expression) => match expression.arguments.is_empty() {
+                true => format!("(call {})", expression.identifier.to_sexp()),
+                false => format!("(call {} {})", expression.identifier.to_sexp(), sexp_join(&expression.arguments)),
+            },
+            Expression::Return(expression) => format!("(return {})", expression.expression.to_sexp()),
+            Expression::Index(expression) => {
+                format!("(index {} {})", expression.indexed.to_sexp(), expression.index.to_sexp())
+            },
+            Expression::Struct(expression) => {
+                let fields: Vec<String> = expression.fields.iter()
+                    .map(|field| format!(" ({} {})", field.identifier.value, field.expression.to_sexp()))
+                    .collect();
+
+                format!("(new {}{})", expression.identifier.value, fields.concat())
+            },
+            Expression::Field(expression) => format!("(. {} {})", expression.expression.to_sexp(), expression.field.value),
+            Expression::FieldAssign(expression) => format!(
+                "(= (. {} {}) {})",
+                expression.target.expression.to_sexp(),
+                expression.target.field.value,
+                expression.expression.to_sexp(),
+            ),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        match self {
+            Expression::Identifier(identifier) => json_node("Identifier", &identifier.node, vec![
+                ("value", json_string(&identifier.value)),
+            ]),
+            Expression::NumberLiteral(literal) => json_node("Number", &literal.node, vec![
+                ("value", literal.value.to_string()),
+            ]),
+            Expression::CharacterLiteral(literal) => json_node("Character", &literal.node, vec![
+                ("value", json_string(&literal.value.to_string())),
+            ]),
+            Expression::StringLiteral(literal) => json_node("String", &literal.node, vec![
+                ("value", json_string(&literal.value)),
+            ]),
+            Expression::BooleanLiteral(literal) => json_node("Boolean", &literal.node, vec![
+                ("value", literal.value.to_string()),
+            ]),
+            Expression::Function(function) => {
+                let parameters: Vec<String> = function.parameters.iter()
+                    .map(|parameter| format!(
+                        "{{\"identifier\":{},\"annotation\":{}}}",
+                        json_string(&parameter.identifier.value),
+                        json_string(&parameter.annotation.to_string()),
+                    ))
+                    .collect();
+
+                json_node("Function", &function.node, vec![
+                    ("identifier", match &function.identifier {
+                        Some(identifier) => json_string(&identifier.value),
+                        None => String::from("null"),
+                    }),
+                    ("parameters", format!("[{}]", parameters.join(","))),
+                    ("annotation", json_string(&function.annotation.to_string())),
+                    ("body", function.body.to_json()),
+                ])
+            },
+            Expression::Prefix(expression) => json_node("Prefix", &expression.node, vec![
+                ("operator", json_string(&expression.operator)),
+                ("expression", expression.expression.to_json()),
+            ]),
+            Expression::Infix(expression) => json_node("Infix", &expression.node, vec![
+                ("operator", json_string(&expression.operator)),
+                ("left", expression.left_expression.to_json()),
+                ("right", expression.right_expression.to_json()),
+            ]),
+            Expression::Assign(expression) => json_node("Assign", &expression.node, vec![
+                ("identifier", json_string(&expression.identifier.value)),
+                ("expression", expression.expression.to_json()),
+            ]),
+            Expression::Array(expression) => json_node("Array", &expression.node, vec![
+                ("elements", json_list(&expression.elements)),
+            ]),
+            Expression::Block(block) => {
+                let statements: Vec<String> = block.statements.iter().map(Statement::to_json).collect();
+                json_node("Block", &block.node, vec![
+                    ("statements", format!("[{}]", statements.join(","))),
+                ])
+            },
+            Expression::If(expression) => json_node("If", &expression.node, vec![
+                ("condition", expression.condition.to_json()),
+                ("consequence", expression.consequence.to_json()),
+                ("alternative", match &expression.alternative {
+                    Some(alternative) => alternative.to_json(),
+                    None => String::from("null"),
+                }),
+            ]),
+            Expression::While(expression) => json_node("While", &expression.node, vec![
+                ("condition", expression.condition.to_json()),
+                ("iteration", expression.iteration.to_json()),
+            ]),
+            Expression::Break(expression) => json_node("Break", &expression.node, vec![]),
+            Expression::Call(expression) => json_node("Call", &expression.node, vec![
+                ("callee", expression.identifier.to_json()),
+                ("arguments", json_list(&expression.arguments)),
+            ]),
+            Expression::Return(expression) => json_node("Return", &expression.node, vec![
+                ("expression", expression.expression.to_json()),
+            ]),
+            Expression::Index(expression) => json_node("Index", &expression.node, vec![
+                ("indexed", expression.indexed.to_json()),
+                ("index", expression.index.to_json()),
+            ]),
+            Expression::Struct(expression) => {
+                let fields: Vec<String> = expression.fields.iter()
+                    .map(|field| format!(
+                        "{{\"identifier\":{},\"expression\":{}}}",
+                        json_string(&field.identifier.value),
+                        field.expression.to_json(),
+                    ))
+                    .collect();
+
+                json_node("StructLiteral", &expression.node, vec![
+                    ("identifier", json_string(&expression.identifier.value)),
+                    ("fields", format!("[{}]", fields.join(","))),
+                ])
+            },
+            Expression::Field(expression) => json_field(expression),
+            Expression::FieldAssign(expression) => json_node("FieldAssign", &expression.node, vec![
+                ("target", json_field(&expression.target)),
+                ("expression", expression.expression.to_json()),
+            ]),
+        }
+    }
+}
+
+fn sexp_join(expressions: &[Box<Expression>]) -> String {
+    let expressions: Vec<String> = expressions.iter().map(|expression| expression.to_sexp()).collect();
+    expressions.join(" ")
+}
+
+fn sexp_list(kind: &str, expressions: &[Box<Expression>]) -> String {
+    match expressions.is_empty() {
+        true => format!("({kind})"),
+        false => format!("({kind} {})", sexp_join(expressions)),
+    }
+}
+
+// JSON, every node is an object with its kind and position
+
+fn json_node(kind: &str, node: &Node, fields: Vec<(&str, String)>) -> String {
+    let mut json = format!("{{\"kind\":\"{kind}\",{}", json_location(node));
+
+    for (name, value) in fields {
+        json.push_str(&format!(",\"{name}\":{value}"));
+    }
+
+    json.push('}');
+    return json
+}
+
+fn json_field(expression: &FieldExpression) -> String {
+    json_node("Field", &expression.node, vec![
+        ("expression", expression.expression.to_json()),
+        ("field", json_string(&expression.field.value)),
+    ])
+}
+
+fn json_location(node: &Node) -> String {
+    format!("\"line\":{},\"column\":{}", node.token.line, node.token.column)
+}
+
+fn json_list(expressions: &[Box<Expression>]) -> String {
+    let expressions: Vec<String> = expressions.iter().map(|expression| expression.to_json()).collect();
+    format!("[{}]", expressions.join(","))
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+
+    for character in value.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            character if (character as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", character as u32)),
+            character => escaped.push(character),
+        }
+    }
+
+    escaped.push('"');
+    return escaped
+}
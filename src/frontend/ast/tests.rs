@@ -0,0 +1,94 @@
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{
+        ast::{
+            self,
+            visit::{walk_expression_mut, walk_function, Visitor, VisitorMut},
+        },
+        lexer::Lexer,
+        parser::{parse_file, Parser},
+    };
+
+    fn parse(code: &str) -> ast::File {
+        let mut lexer = Lexer::new(code);
+        let mut parser = Parser::new(&mut lexer);
+
+        let file = parse_file(&mut parser);
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+        file
+    }
+
+    // Names in source order, and the functions they appear in
+    #[derive(Default)]
+    struct Names {
+        names: Vec<String>,
+        functions: Vec<String>,
+    }
+
+    impl Visitor for Names {
+        fn visit_identifier(&mut self, identifier: &ast::Identifier) {
+            self.names.push(identifier.value.clone());
+        }
+
+        fn visit_function(&mut self, function: &ast::Function) {
+            self.functions.push(function.name().to_string());
+            walk_function(self, function);
+        }
+    }
+
+    #[test]
+    fn test_visitor_order() {
+        let file = parse("
+            let x = [a, b[c]];
+            fn f(y: int) -> int {
+                if y > z { return g(y).h; } else { while w { k = !v; break; } }
+                return 0;
+            }
+        ");
+
+        let mut visitor = Names::default();
+        visitor.visit_file(&file);
+
+        assert_eq!(visitor.names, ["x", "a", "b", "c", "f", "y", "y", "z", "g", "y", "h", "w", "k", "v"]);
+        assert_eq!(visitor.functions, ["f"]);
+    }
+
+    // Folds additions and multiplications of literals
+    struct Folder;
+
+    impl VisitorMut for Folder {
+        fn visit_expression_mut(&mut self, expression: &mut ast::Expression) {
+            walk_expression_mut(self, expression);
+
+            let folded = match expression {
+                ast::Expression::Infix(infix) => match (infix.left_expression.as_ref(), infix.right_expression.as_ref()) {
+                    (ast::Expression::NumberLiteral(left), ast::Expression::NumberLiteral(right)) => {
+                        match infix.operator.as_str() {
+                            "+" => Some((left.value + right.value, left.node.token.clone())),
+                            "*" => Some((left.value * right.value, left.node.token.clone())),
+                            _ => None,
+                        }
+                    },
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            if let Some((value, token)) = folded {
+                *expression = ast::Expression::NumberLiteral(ast::NumberLiteral {
+                    node: ast::Node { token },
+                    value,
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut_rewrites() {
+        let mut file = parse("let x = 1 + 2 * 3; f(x, 2 * (4 + 1) - x); [1 * 1][0 + 0];");
+        Folder.visit_file_mut(&mut file);
+
+        assert_eq!(file.to_string(), "let x = 7;\nf(x, (10 - x));\n[1][0];\n");
+    }
+}
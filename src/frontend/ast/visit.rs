@@ -0,0 +1,509 @@
+use super::{
+    ArrayExpression, AssignmentExpression, BlockExpression, BooleanLiteral, BreakExpression,
+    CallExpression, CharacterLiteral, Expression, ExpressionStatement, FieldAssignmentExpression, FieldExpression,
+    File, Function, FunctionParameter, Identifier, IfExpression, IndexExpression, InfixExpression, LetStatement,
+    NumberLiteral, PrefixExpression, ReturnExpression, Statement, StringLiteral, StructExpression, StructStatement,
+    WhileExpression,
+};
+
+// Traverses the tree in source order. Every node has a visit method, overriding one replaces
+// the traversal of that node, and the walk function of the same name resumes it for the children.
+
+pub trait Visitor {
+    fn visit_file(&mut self, file: &File) {
+        walk_file(self, file);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_let_statement(&mut self, statement: &LetStatement) {
+        walk_let_statement(self, statement);
+    }
+
+    fn visit_struct_statement(&mut self, statement: &StructStatement) {
+        walk_struct_statement(self, statement);
+    }
+
+    fn visit_expression_statement(&mut self, statement: &ExpressionStatement) {
+        walk_expression_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_identifier(&mut self, _identifier: &Identifier) {}
+
+    fn visit_number_literal(&mut self, _literal: &NumberLiteral) {}
+
+    fn visit_character_literal(&mut self, _literal: &CharacterLiteral) {}
+
+    fn visit_string_literal(&mut self, _literal: &StringLiteral) {}
+
+    fn visit_boolean_literal(&mut self, _literal: &BooleanLiteral) {}
+
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function);
+    }
+
+    fn visit_function_parameter(&mut self, parameter: &FunctionParameter) {
+        walk_function_parameter(self, parameter);
+    }
+
+    fn visit_prefix_expression(&mut self, expression: &PrefixExpression) {
+        walk_prefix_expression(self, expression);
+    }
+
+    fn visit_infix_expression(&mut self, expression: &InfixExpression) {
+        walk_infix_expression(self, expression);
+    }
+
+    fn visit_assignment_expression(&mut self, expression: &AssignmentExpression) {
+        walk_assignment_expression(self, expression);
+    }
+
+    fn visit_array_expression(&mut self, expression: &ArrayExpression) {
+        walk_array_expression(self, expression);
+    }
+
+    fn visit_block_expression(&mut self, expression: &BlockExpression) {
+        walk_block_expression(self, expression);
+    }
+
+    fn visit_if_expression(&mut self, expression: &IfExpression) {
+        walk_if_expression(self, expression);
+    }
+
+    fn visit_while_expression(&mut self, expression: &WhileExpression) {
+        walk_while_expression(self, expression);
+    }
+
+    fn visit_break_expression(&mut self, _expression: &BreakExpression) {}
+
+    fn visit_call_expression(&mut self, expression: &CallExpression) {
+        walk_call_expression(self, expression);
+    }
+
+    fn visit_return_expression(&mut self, expression: &ReturnExpression) {
+        walk_return_expression(self, expression);
+    }
+
+    fn visit_index_expression(&mut self, expression: &IndexExpression) {
+        walk_index_expression(self, expression);
+    }
+
+    fn visit_struct_expression(&mut self, expression: &StructExpression) {
+        walk_struct_expression(self, expression);
+    }
+
+    fn visit_field_expression(&mut self, expression: &FieldExpression) {
+        walk_field_expression(self, expression);
+    }
+
+    fn visit_field_assignment_expression(&mut self, expression: &FieldAssignmentExpression) {
+        walk_field_assignment_expression(self, expression);
+    }
+}
+
+pub fn walk_file<V: Visitor + ?Sized>(visitor: &mut V, file: &File) {
+    for statement in &file.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Let(statement) => visitor.visit_let_statement(statement),
+        Statement::Struct(statement) => visitor.visit_struct_statement(statement),
+        Statement::Expression(statement) => visitor.visit_expression_statement(statement),
+    }
+}
+
+pub fn walk_let_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &LetStatement) {
+    visitor.visit_identifier(&statement.identifier);
+
+    if let Some(expression) = &statement.expression {
+        visitor.visit_expression(expression);
+    }
+}
+
+pub fn walk_struct_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &StructStatement) {
+    visitor.visit_identifier(&statement.identifier);
+
+    for field in &statement.fields {
+        visitor.visit_identifier(&field.identifier);
+    }
+}
+
+pub fn walk_expression_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &ExpressionStatement) {
+    visitor.visit_expression(&statement.expression);
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+    match expression {
+        Expression::Identifier(identifier) => visitor.visit_identifier(identifier),
+        Expression::NumberLiteral(literal) => visitor.visit_number_literal(literal),
+        Expression::CharacterLiteral(literal) => visitor.visit_character_literal(literal),
+        Expression::StringLiteral(literal) => visitor.visit_string_literal(literal),
+        Expression::BooleanLiteral(literal) => visitor.visit_boolean_literal(literal),
+        Expression::Function(function) => visitor.visit_function(function),
+        Expression::Prefix(expression) => visitor.visit_prefix_expression(expression),
+        Expression::Infix(expression) => visitor.visit_infix_expression(expression),
+        Expression::Assign(expression) => visitor.visit_assignment_expression(expression),
+        Expression::Array(expression) => visitor.visit_array_expression(expression),
+        Expression::Block(expression) => visitor.visit_block_expression(expression),
+        Expression::If(expression) => visitor.visit_if_expression(expression),
+        Expression::While(expression) => visitor.visit_while_expression(expression),
+        Expression::Break(expression) => visitor.visit_break_expression(expression),
+        Expression::Call(expression) => visitor.visit_call_expression(expression),
+        Expression::Return(expression) => visitor.visit_return_expression(expression),
+        Expression::Index(expression) => visitor.visit_index_expression(expression),
+        Expression::Struct(expression) => visitor.visit_struct_expression(expression),
+        Expression::Field(expression) => visitor.visit_field_expression(expression),
+        Expression::FieldAssign(expression) => visitor.visit_field_assignment_expression(expression),
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+    if let Some(identifier) = &function.identifier {
+        visitor.visit_identifier(identifier);
+    }
+
+    for parameter in &function.parameters {
+        visitor.visit_function_parameter(parameter);
+    }
+
+    visitor.visit_expression(&function.body);
+}
+
+pub fn walk_function_parameter<V: Visitor + ?Sized>(visitor: &mut V, parameter: &FunctionParameter) {
+    visitor.visit_identifier(&parameter.identifier);
+}
+
+pub fn walk_prefix_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &PrefixExpression) {
+    visitor.visit_expression(&expression.expression);
+}
+
+pub fn walk_infix_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &InfixExpression) {
+    visitor.visit_expression(&expression.left_expression);
+    visitor.visit_expression(&expression.right_expression);
+}
+
+pub fn walk_assignment_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &AssignmentExpression) {
+    visitor.visit_identifier(&expression.identifier);
+    visitor.visit_expression(&expression.expression);
+}
+
+pub fn walk_array_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &ArrayExpression) {
+    for element in &expression.elements {
+        visitor.visit_expression(element);
+    }
+}
+
+pub fn walk_block_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &BlockExpression) {
+    for statement in &expression.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_if_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &IfExpression) {
+    visitor.visit_expression(&expression.condition);
+    visitor.visit_expression(&expression.consequence);
+
+    if let Some(alternative) = &expression.alternative {
+        visitor.visit_expression(alternative);
+    }
+}
+
+pub fn walk_while_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &WhileExpression) {
+    visitor.visit_expression(&expression.condition);
+    visitor.visit_expression(&expression.iteration);
+}
+
+pub fn walk_call_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &CallExpression) {
+    visitor.visit_expression(&expression.identifier);
+
+    for argument in &expression.arguments {
+        visitor.visit_expression(argument);
+    }
+}
+
+pub fn walk_return_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &ReturnExpression) {
+    visitor.visit_expression(&expression.expression);
+}
+
+pub fn walk_index_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &IndexExpression) {
+    visitor.visit_expression(&expression.indexed);
+    visitor.visit_expression(&expression.index);
+}
+
+pub fn walk_struct_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &StructExpression) {
+    visitor.visit_identifier(&expression.identifier);
+
+    for field in &expression.fields {
+        visitor.visit_identifier(&field.identifier);
+        visitor.visit_expression(&field.expression);
+    }
+}
+
+pub fn walk_field_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &FieldExpression) {
+    visitor.visit_expression(&expression.expression);
+    visitor.visit_identifier(&expression.field);
+}
+
+pub fn walk_field_assignment_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &FieldAssignmentExpression) {
+    visitor.visit_field_expression(&expression.target);
+    visitor.visit_expression(&expression.expression);
+}
+
+// Same traversal with mutable access, for passes rewriting the tree in place
+pub trait VisitorMut {
+    fn visit_file_mut(&mut self, file: &mut File) {
+        walk_file_mut(self, file);
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_let_statement_mut(&mut self, statement: &mut LetStatement) {
+        walk_let_statement_mut(self, statement);
+    }
+
+    fn visit_struct_statement_mut(&mut self, statement: &mut StructStatement) {
+        walk_struct_statement_mut(self, statement);
+    }
+
+    fn visit_expression_statement_mut(&mut self, statement: &mut ExpressionStatement) {
+        walk_expression_statement_mut(self, statement);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_identifier_mut(&mut self, _identifier: &mut Identifier) {}
+
+    fn visit_number_literal_mut(&mut self, _literal: &mut NumberLiteral) {}
+
+    fn visit_character_literal_mut(&mut self, _literal: &mut CharacterLiteral) {}
+
+    fn visit_string_literal_mut(&mut self, _literal: &mut StringLiteral) {}
+
+    fn visit_boolean_literal_mut(&mut self, _literal: &mut BooleanLiteral) {}
+
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        walk_function_mut(self, function);
+    }
+
+    fn visit_function_parameter_mut(&mut self, parameter: &mut FunctionParameter) {
+        walk_function_parameter_mut(self, parameter);
+    }
+
+    fn visit_prefix_expression_mut(&mut self, expression: &mut PrefixExpression) {
+        walk_prefix_expression_mut(self, expression);
+    }
+
+    fn visit_infix_expression_mut(&mut self, expression: &mut InfixExpression) {
+        walk_infix_expression_mut(self, expression);
+    }
+
+    fn visit_assignment_expression_mut(&mut self, expression: &mut AssignmentExpression) {
+        walk_assignment_expression_mut(self, expression);
+    }
+
+    fn visit_array_expression_mut(&mut self, expression: &mut ArrayExpression) {
+        walk_array_expression_mut(self, expression);
+    }
+
+    fn visit_block_expression_mut(&mut self, expression: &mut BlockExpression) {
+        walk_block_expression_mut(self, expression);
+    }
+
+    fn visit_if_expression_mut(&mut self, expression: &mut IfExpression) {
+        walk_if_expression_mut(self, expression);
+    }
+
+    fn visit_while_expression_mut(&mut self, expression: &mut WhileExpression) {
+        walk_while_expression_mut(self, expression);
+    }
+
+    fn visit_break_expression_mut(&mut self, _expression: &mut BreakExpression) {}
+
+    fn visit_call_expression_mut(&mut self, expression: &mut CallExpression) {
+        walk_call_expression_mut(self, expression);
+    }
+
+    fn visit_return_expression_mut(&mut self, expression: &mut ReturnExpression) {
+        walk_return_expression_mut(self, expression);
+    }
+
+    fn visit_index_expression_mut(&mut self, expression: &mut IndexExpression) {
+        walk_index_expression_mut(self, expression);
+    }
+
+    fn visit_struct_expression_mut(&mut self, expression: &mut StructExpression) {
+        walk_struct_expression_mut(self, expression);
+    }
+
+    fn visit_field_expression_mut(&mut self, expression: &mut FieldExpression) {
+        walk_field_expression_mut(self, expression);
+    }
+
+    fn visit_field_assignment_expression_mut(&mut self, expression: &mut FieldAssignmentExpression) {
+        walk_field_assignment_expression_mut(self, expression);
+    }
+}
+
+pub fn walk_file_mut<V: VisitorMut + ?Sized>(visitor: &mut V, file: &mut File) {
+    for statement in &mut file.statements {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match statement {
+        Statement::Let(statement) => visitor.visit_let_statement_mut(statement),
+        Statement::Struct(statement) => visitor.visit_struct_statement_mut(statement),
+        Statement::Expression(statement) => visitor.visit_expression_statement_mut(statement),
+    }
+}
+
+pub fn walk_let_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut LetStatement) {
+    visitor.visit_identifier_mut(&mut statement.identifier);
+
+    if let Some(expression) = &mut statement.expression {
+        visitor.visit_expression_mut(expression);
+    }
+}
+
+pub fn walk_struct_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut StructStatement) {
+    visitor.visit_identifier_mut(&mut statement.identifier);
+
+    for field in &mut statement.fields {
+        visitor.visit_identifier_mut(&mut field.identifier);
+    }
+}
+
+pub fn walk_expression_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut ExpressionStatement) {
+    visitor.visit_expression_mut(&mut statement.expression);
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut Expression) {
+    match expression {
+        Expression::Identifier(identifier) => visitor.visit_identifier_mut(identifier),
+        Expression::NumberLiteral(literal) => visitor.visit_number_literal_mut(literal),
+        Expression::CharacterLiteral(literal) => visitor.visit_character_literal_mut(literal),
+        Expression::StringLiteral(literal) => visitor.visit_string_literal_mut(literal),
+        Expression::BooleanLiteral(literal) => visitor.visit_boolean_literal_mut(literal),
+        Expression::Function(function) => visitor.visit_function_mut(function),
+        Expression::Prefix(expression) => visitor.visit_prefix_expression_mut(expression),
+        Expression::Infix(expression) => visitor.visit_infix_expression_mut(expression),
+        Expression::Assign(expression) => visitor.visit_assignment_expression_mut(expression),
+        Expression::Array(expression) => visitor.visit_array_expression_mut(expression),
+        Expression::Block(expression) => visitor.visit_block_expression_mut(expression),
+        Expression::If(expression) => visitor.visit_if_expression_mut(expression),
+        Expression::While(expression) => visitor.visit_while_expression_mut(expression),
+        Expression::Break(expression) => visitor.visit_break_expression_mut(expression),
+        Expression::Call(expression) => visitor.visit_call_expression_mut(expression),
+        Expression::Return(expression) => visitor.visit_return_expression_mut(expression),
+        Expression::Index(expression) => visitor.visit_index_expression_mut(expression),
+        Expression::Struct(expression) => visitor.visit_struct_expression_mut(expression),
+        Expression::Field(expression) => visitor.visit_field_expression_mut(expression),
+        Expression::FieldAssign(expression) => visitor.visit_field_assignment_expression_mut(expression),
+    }
+}
+
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(visitor: &mut V, function: &mut Function) {
+    if let Some(identifier) = &mut function.identifier {
+        visitor.visit_identifier_mut(identifier);
+    }
+
+    for parameter in &mut function.parameters {
+        visitor.visit_function_parameter_mut(parameter);
+    }
+
+    visitor.visit_expression_mut(&mut function.body);
+}
+
+pub fn walk_function_parameter_mut<V: VisitorMut + ?Sized>(visitor: &mut V, parameter: &mut FunctionParameter) {
+    visitor.visit_identifier_mut(&mut parameter.identifier);
+}
+
+pub fn walk_prefix_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut PrefixExpression) {
+    visitor.visit_expression_mut(&mut expression.expression);
+}
+
+pub fn walk_infix_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut InfixExpression) {
+    visitor.visit_expression_mut(&mut expression.left_expression);
+    visitor.visit_expression_mut(&mut expression.right_expression);
+}
+
+pub fn walk_assignment_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut AssignmentExpression) {
+    visitor.visit_identifier_mut(&mut expression.identifier);
+    visitor.visit_expression_mut(&mut expression.expression);
+}
+
+pub fn walk_array_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut ArrayExpression) {
+    for element in &mut expression.elements {
+        visitor.visit_expression_mut(element);
+    }
+}
+
+pub fn walk_block_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut BlockExpression) {
+    for statement in &mut expression.statements {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_if_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut IfExpression) {
+    visitor.visit_expression_mut(&mut expression.condition);
+    visitor.visit_expression_mut(&mut expression.consequence);
+
+    if let Some(alternative) = &mut expression.alternative {
+        visitor.visit_expression_mut(alternative);
+    }
+}
+
+pub fn walk_while_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut WhileExpression) {
+    visitor.visit_expression_mut(&mut expression.condition);
+    visitor.visit_expression_mut(&mut expression.iteration);
+}
+
+pub fn walk_call_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut CallExpression) {
+    visitor.visit_expression_mut(&mut expression.identifier);
+
+    for argument in &mut expression.arguments {
+        visitor.visit_expression_mut(argument);
+    }
+}
+
+pub fn walk_return_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut ReturnExpression) {
+    visitor.visit_expression_mut(&mut expression.expression);
+}
+
+pub fn walk_index_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut IndexExpression) {
+    visitor.visit_expression_mut(&mut expression.indexed);
+    visitor.visit_expression_mut(&mut expression.index);
+}
+
+pub fn walk_struct_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut StructExpression) {
+    visitor.visit_identifier_mut(&mut expression.identifier);
+
+    for field in &mut expression.fields {
+        visitor.visit_identifier_mut(&mut field.identifier);
+        visitor.visit_expression_mut(&mut field.expression);
+    }
+}
+
+pub fn walk_field_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut FieldExpression) {
+    visitor.visit_expression_mut(&mut expression.expression);
+    visitor.visit_identifier_mut(&mut expression.field);
+}
+
+pub fn walk_field_assignment_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut FieldAssignmentExpression) {
+    visitor.visit_field_expression_mut(&mut expression.target);
+    visitor.visit_expression_mut(&mut expression.expression);
+}
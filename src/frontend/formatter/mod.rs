@@ -0,0 +1,411 @@
+pub mod tests;
+
+use super::{
+    ast,
+    lexer::Lexer,
+    parser::{parse_file, Parser, Precedence},
+    token::{Comment, TokenKind},
+    typecheck::types::Type,
+};
+
+const INDENT: &str = "    ";
+
+// Prints source code in the canonical style: four spaces indentation, spaces around
+// binary operators, opening braces on the line of their construct, and semicolons
+// after every statement not ending with a block. Comments and single blank lines are kept.
+pub fn format_source(source: &str) -> Result<String, Vec<String>> {
+    if source.is_empty() {
+        return Ok(String::new())
+    }
+
+    let mut lexer = Lexer::new(source);
+    let mut parser = Parser::new(&mut lexer);
+
+    let file = parse_file(&mut parser);
+    if !parser.errors.is_empty() {
+        return Err(parser.errors)
+    }
+
+    // The parser borrows its lexer for good, comments are read by another pass
+    let mut lexer = Lexer::new(source);
+    while lexer.next_token().kind != TokenKind::EOF {}
+
+    let mut formatter = Formatter {
+        output: String::new(),
+        depth: 0,
+        lines: source.lines().collect(),
+        comments: lexer.comments().to_vec(),
+        next_comment: 0,
+        block_start: true,
+    };
+
+    formatter.write_statements(&file.statements);
+    formatter.write_comments_before(usize::MAX);
+
+    Ok(formatter.output)
+}
+
+struct Formatter<'a> {
+    output: String,
+    depth: usize,
+    lines: Vec<&'a str>,
+    comments: Vec<Comment>,
+    next_comment: usize,
+    block_start: bool, // Nothing was written in the current block yet
+}
+
+impl<'a> Formatter<'a> {
+    fn write_statements(&mut self, statements: &[ast::Statement]) {
+        for statement in statements {
+            let (line, end_line) = statement_lines(statement);
+
+            self.write_comments_before(line);
+            self.start_line(line);
+            self.write_statement(statement);
+            self.write_trailing_comment(end_line);
+            self.output.push('\n');
+        }
+    }
+
+    fn write_statement(&mut self, statement: &ast::Statement) {
+        match statement {
+            ast::Statement::Let(statement) => {
+                self.output.push_str("let ");
+                self.output.push_str(&statement.identifier.value);
+
+                if let Some(annotation) = &statement.annotation {
+                    self.output.push_str(&format!(": {annotation}"));
+                }
+
+                if let Some(expression) = &statement.expression {
+                    self.output.push_str(" = ");
+                    self.write_expression(expression);
+                }
+
+                self.output.push(';');
+            },
+            ast::Statement::Struct(statement) => self.write_struct(statement),
+            ast::Statement::Expression(statement) => {
+                self.write_expression(&statement.expression);
+
+                // The parser rejects semicolons after blocks
+                if !statement.expression.ends_with_block() {
+                    self.output.push(';');
+                }
+            },
+        }
+    }
+
+    fn write_expression(&mut self, expression: &ast::Expression) {
+        match expression {
+            ast::Expression::Identifier(identifier) => self.output.push_str(&identifier.value),
+            ast::Expression::NumberLiteral(literal) => self.output.push_str(&literal.value.to_string()),
+            ast::Expression::CharacterLiteral(literal) => self.output.push_str(&format!("'{}'", literal.value)),
+            ast::Expression::StringLiteral(literal) => self.output.push_str(&format!("\"{}\"", literal.value)),
+            ast::Expression::BooleanLiteral(literal) => self.output.push_str(&literal.value.to_string()),
+            ast::Expression::Function(function) => self.write_function(function),
+            ast::Expression::Prefix(expression) => {
+                self.output.push_str(&expression.operator);
+                self.write_operand(&expression.expression, precedence(&expression.expression) < Precedence::PREFIX);
+            },
+            ast::Expression::Infix(expression) => {
+                let operator_precedence = operator_precedence(&expression.operator);
+
+                // Operators are left associative
+                self.write_operand(&expression.left_expression, precedence(&expression.left_expression) < operator_precedence);
+                self.output.push_str(&format!(" {} ", expression.operator));
+                self.write_operand(&expression.right_expression, precedence(&expression.right_expression) <= operator_precedence);
+            },
+            ast::Expression::Assign(expression) => {
+                self.output.push_str(&format!("{} = ", expression.identifier.value));
+                self.write_expression(&expression.expression);
+            },
+            ast::Expression::Array(expression) => {
+                self.output.push('[');
+                self.write_list(&expression.elements);
+                self.output.push(']');
+            },
+            ast::Expression::Block(block) => self.write_block(block),
+            ast::Expression::If(expression) => {
+                self.output.push_str("if ");
+                self.write_operand(&expression.condition, expression.condition.has_struct_literal());
+                self.output.push(' ');
+                self.write_expression(&expression.consequence);
+
+                if let Some(alternative) = &expression.alternative {
+                    self.output.push_str(" else ");
+                    self.write_expression(alternative);
+                }
+            },
+            ast::Expression::While(expression) => {
+                self.output.push_str("while ");
+                self.write_operand(&expression.condition, expression.condition.has_struct_literal());
+                self.output.push(' ');
+                self.write_expression(&expression.iteration);
+            },
+            ast::Expression::Break(_) => self.output.push_str("break"),
+            ast::Expression::Call(expression) => {
+                self.write_operand(&expression.identifier, precedence(&expression.identifier) < Precedence::INDEX);
+                self.output.push('(');
+                self.write_list(&expression.arguments);
+                self.output.push(')');
+            },
+            ast::Expression::Return(expression) => {
+                self.output.push_str("return ");
+                self.write_expression(&expression.expression);
+            },
+            ast::Expression::Index(expression) => {
+                self.write_operand(&expression.indexed, precedence(&expression.indexed) < Precedence::INDEX);
+                self.output.push('[');
+                self.write_expression(&expression.index);
+                self.output.push(']');
+            },
+            ast::Expression::Struct(expression) => {
+                self.output.push_str(&expression.identifier.value);
+
+                if expression.fields.is_empty() {
+                    self.output.push_str(" {}");
+                    return
+                }
+
+                self.output.push_str(" { ");
+                for (index, field) in expression.fields.iter().enumerate() {
+                    if index > 0 {
+                        self.output.push_str(", ");
+                    }
+
+                    self.output.push_str(&format!("{}: ", field.identifier.value));
+                    self.write_expression(&field.expression);
+                }
+                self.output.push_str(" }");
+            },
+            ast::Expression::Field(expression) => self.write_field(expression),
+            ast::Expression::FieldAssign(expression) => {
+                self.write_field(&expression.target);
+                self.output.push_str(" = ");
+                self.write_expression(&expression.expression);
+            },
+        }
+    }
+
+    fn write_field(&mut self, expression: &ast::FieldExpression) {
+        self.write_operand(&expression.expression, precedence(&expression.expression) < Precedence::INDEX);
+        self.output.push('.');
+        self.output.push_str(&expression.field.value);
+    }
+
+    // Groupings aren't part of the tree, they are added back where precedence requires them
+    fn write_operand(&mut self, expression: &ast::Expression, grouped: bool) {
+        if grouped {
+            self.output.push('(');
+            self.write_expression(expression);
+            self.output.push(')');
+        } else {
+            self.write_expression(expression);
+        }
+    }
+
+    fn write_list(&mut self, expressions: &[Box<ast::Expression>]) {
+        for (index, expression) in expressions.iter().enumerate() {
+            if index > 0 {
+                self.output.push_str(", ");
+            }
+
+            self.write_expression(expression);
+        }
+    }
+
+    fn write_function(&mut self, function: &ast::Function) {
+        let parameters: Vec<String> = function.parameters.iter()
+            .map(|parameter| format!("{}: {}", parameter.identifier.value, parameter.annotation))
+            .collect();
+
+        match &function.identifier {
+            Some(identifier) => self.output.push_str(&format!("fn {}({})", identifier.value, parameters.join(", "))),
+            None => self.output.push_str(&format!("fn({})", parameters.join(", "))),
+        }
+
+        // Functions without annotation return void
+        if function.annotation != Type::Void {
+            self.output.push_str(&format!(" -> {}", function.annotation));
+        }
+
+        match function.body.as_ref() {
+            ast::Expression::Block(_) => self.output.push(' '),
+            _ => self.output.push_str(" => "),
+        }
+
+        self.write_expression(&function.body);
+    }
+
+    // One field per line, each followed by a comma
+    fn write_struct(&mut self, statement: &ast::StructStatement) {
+        self.output.push_str(&format!("struct {} {{", statement.identifier.value));
+        self.write_trailing_comment(statement.node.token.line);
+
+        let has_comments = self.comments.get(self.next_comment)
+            .is_some_and(|comment| comment.line < statement.end.token.line);
+
+        if statement.fields.is_empty() && !has_comments {
+            self.output.push('}');
+            return
+        }
+
+        self.output.push('\n');
+        self.depth += 1;
+        self.block_start = true;
+
+        for field in &statement.fields {
+            let line = field.identifier.node.token.line;
+
+            self.write_comments_before(line);
+            self.start_line(line);
+            self.output.push_str(&format!("{}: {},", field.identifier.value, field.annotation));
+            self.write_trailing_comment(line);
+            self.output.push('\n');
+        }
+
+        self.write_comments_before(statement.end.token.line);
+
+        self.depth -= 1;
+        self.output.push_str(&INDENT.repeat(self.depth));
+        self.output.push('}');
+    }
+
+    fn write_block(&mut self, block: &ast::BlockExpression) {
+        self.output.push('{');
+        self.write_trailing_comment(block.node.token.line);
+
+        let has_comments = self.comments.get(self.next_comment)
+            .is_some_and(|comment| comment.line < block.end.token.line);
+
+        if block.statements.is_empty() && !has_comments {
+            self.output.push('}');
+            return
+        }
+
+        self.output.push('\n');
+        self.depth += 1;
+        self.block_start = true;
+
+        self.write_statements(&block.statements);
+        self.write_comments_before(block.end.token.line);
+
+        self.depth -= 1;
+        self.output.push_str(&INDENT.repeat(self.depth));
+        self.output.push('}');
+    }
+
+    // Comments
+
+    // Comments on their own lines, before the given line
+    fn write_comments_before(&mut self, line: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.line >= line {
+                return
+            }
+
+            let comment = comment.clone();
+            self.next_comment += 1;
+
+            self.start_line(comment.line);
+            self.output.push_str(&comment.text);
+            self.output.push('\n');
+        }
+    }
+
+    // A comment at the end of the given line
+    fn write_trailing_comment(&mut self, line: usize) {
+        if let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.line == line {
+                self.output.push(' ');
+                self.output.push_str(&comment.text);
+                self.next_comment += 1;
+            }
+        }
+    }
+
+    // Indents a new line, after a blank line if the source had one there
+    fn start_line(&mut self, line: usize) {
+        let blank = line > 1 && self.lines.get(line - 2).is_some_and(|previous| previous.trim().is_empty());
+        if blank && !self.block_start {
+            self.output.push('\n');
+        }
+
+        self.block_start = false;
+        self.output.push_str(&INDENT.repeat(self.depth));
+    }
+}
+
+// How tightly an expression binds, the ones extending to the right as far as they can bind the least
+fn precedence(expression: &ast::Expression) -> Precedence {
+    match expression {
+        ast::Expression::Infix(expression) => operator_precedence(&expression.operator),
+        ast::Expression::Assign(_) | ast::Expression::FieldAssign(_) => Precedence::ASSIGNMENT,
+        ast::Expression::Prefix(_) => Precedence::PREFIX,
+        ast::Expression::Index(_) => Precedence::INDEX,
+        ast::Expression::Field(_) => Precedence::FIELD,
+        ast::Expression::Return(_) => Precedence::LOWEST,
+        ast::Expression::Function(function) if !matches!(function.body.as_ref(), ast::Expression::Block(_)) => Precedence::LOWEST,
+        _ => Precedence::CALL,
+    }
+}
+
+fn operator_precedence(operator: &str) -> Precedence {
+    match operator {
+        "||" => Precedence::OR,
+        "&&" => Precedence::AND,
+        "==" | "!=" => Precedence::EQUALITY,
+        ">" | "<" => Precedence::LESSGREATER,
+        "+" | "-" => Precedence::SUM,
+        "*" | "/" => Precedence::PRODUCT,
+        _ => Precedence::LOWEST,
+    }
+}
+
+// First and last lines of a statement, as far as the tree tells
+fn statement_lines(statement: &ast::Statement) -> (usize, usize) {
+    match statement {
+        ast::Statement::Let(statement) => {
+            let line = statement.node.token.line;
+            (line, statement.expression.as_ref().map_or(line, |expression| last_line(expression).max(line)))
+        },
+        ast::Statement::Struct(statement) => (statement.node.token.line, statement.end.token.line),
+        ast::Statement::Expression(statement) => {
+            let line = statement.node.token.line;
+            (line, last_line(&statement.expression).max(line))
+        },
+    }
+}
+
+fn last_line(expression: &ast::Expression) -> usize {
+    match expression {
+        ast::Expression::Block(block) => block.end.token.line,
+        ast::Expression::Function(function) => last_line(&function.body),
+        ast::Expression::If(expression) => match &expression.alternative {
+            Some(alternative) => last_line(alternative),
+            None => last_line(&expression.consequence),
+        },
+        ast::Expression::While(expression) => last_line(&expression.iteration),
+        ast::Expression::Prefix(expression) => last_line(&expression.expression),
+        ast::Expression::Infix(expression) => last_line(&expression.right_expression),
+        ast::Expression::Assign(expression) => last_line(&expression.expression),
+        ast::Expression::Return(expression) => last_line(&expression.expression),
+        ast::Expression::Index(expression) => last_line(&expression.index).max(last_line(&expression.indexed)),
+        ast::Expression::Array(expression) => expression.elements.iter()
+            .map(|element| last_line(element))
+            .fold(expression.node.token.line, usize::max),
+        ast::Expression::Call(expression) => expression.arguments.iter()
+            .map(|argument| last_line(argument))
+            .fold(last_line(&expression.identifier), usize::max),
+        ast::Expression::Identifier(identifier) => identifier.node.token.line,
+        ast::Expression::NumberLiteral(literal) => literal.node.token.line,
+        ast::Expression::CharacterLiteral(literal) => literal.node.token.line,
+        ast::Expression::StringLiteral(literal) => literal.node.token.line,
+        ast::Expression::BooleanLiteral(literal) => literal.node.token.line,
+        ast::Expression::Break(expression) => expression.node.token.line,
+        ast::Expression::Struct(expression) => expression.end.token.line,
+        ast::Expression::Field(expression) => expression.field.node.token.line,
+        ast::Expression::FieldAssign(expression) => last_line(&expression.expression),
+    }
+}
@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{
+        formatter::format_source,
+        lexer::Lexer,
+        parser::{parse_file, Parser},
+    };
+
+    fn assert_formatted(code: &str, expected: &str) {
+        let formatted = format_source(code).unwrap();
+        assert_eq!(formatted, expected);
+
+        // Formatting is stable
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_statements() {
+        assert_formatted("let   x:int=1+2*3;", "let x: int = 1 + 2 * 3;\n");
+        assert_formatted("let x: int;", "let x: int;\n");
+        assert_formatted("x=[1,2,  3];x[0];", "x = [1, 2, 3];\nx[0];\n");
+        assert_formatted("print( \"a\" , 'b' );", "print(\"a\", 'b');\n");
+        assert_formatted("!true;-x;", "!true;\n-x;\n");
+        assert_formatted("", "");
+    }
+
+    #[test]
+    fn test_format_groupings() {
+        assert_formatted("(1 + 2) * 3;", "(1 + 2) * 3;\n");
+        assert_formatted("1 + (2 * 3);", "1 + 2 * 3;\n");
+        assert_formatted("1 - (2 - 3);", "1 - (2 - 3);\n");
+        assert_formatted("(1 - 2) - 3;", "1 - 2 - 3;\n");
+        assert_formatted("-(1 + 2);", "-(1 + 2);\n");
+        assert_formatted("(a || b) && c;", "(a || b) && c;\n");
+    }
+
+    #[test]
+    fn test_format_blocks() {
+        assert_formatted(
+            "fn add(a: int, b: int) -> int { return a + b; }",
+            "fn add(a: int, b: int) -> int {\n    return a + b;\n}\n",
+        );
+        assert_formatted("fn main() {}", "fn main() {}\n");
+        assert_formatted(
+            "if x > 1 { x; } else if x < 0 { 0; } else { 1; }",
+            "if x > 1 {\n    x;\n} else if x < 0 {\n    0;\n} else {\n    1;\n}\n",
+        );
+        assert_formatted(
+            "while true { if x { break; } }",
+            "while true {\n    if x {\n        break;\n    }\n}\n",
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        assert_formatted(
+            "// Header\nlet x = 1; // One\n\n\n\n// Loop\nwhile x < 3 { // Condition\n  x = x + 1;\n  // Last\n}\n// End",
+            "// Header\nlet x = 1; // One\n\n// Loop\nwhile x < 3 { // Condition\n    x = x + 1;\n    // Last\n}\n// End\n",
+        );
+        assert_formatted("fn f() {\n// Empty\n}", "fn f() {\n    // Empty\n}\n");
+    }
+
+    #[test]
+    fn test_format_anonymous_functions() {
+        assert_formatted("let f = fn (x:int)->int=>x+1;", "let f = fn(x: int) -> int => x + 1;\n");
+        assert_formatted("(fn()->int => 1)();", "(fn() -> int => 1)();\n");
+        assert_formatted(
+            "apply(fn(x: int) -> int { x * 2; }, 4);",
+            "apply(fn(x: int) -> int {\n    x * 2;\n}, 4);\n",
+        );
+    }
+
+    #[test]
+    fn test_format_structs() {
+        assert_formatted(
+            "struct Point{\nx:int,// Across\ny:int}",
+            "struct Point {\n    x: int, // Across\n    y: int,\n}\n",
+        );
+        assert_formatted("struct Empty {}", "struct Empty {}\n");
+        assert_formatted("let p=Point{x:1,y:a.b.c};p.x=(p).y;", "let p = Point { x: 1, y: a.b.c };\np.x = p.y;\n");
+        assert_formatted("if (P{x:1}).x==1 {}", "if (P { x: 1 }.x == 1) {}\n");
+        assert_formatted("(a+b).c;", "(a + b).c;\n");
+    }
+
+    #[test]
+    fn test_format_errors() {
+        assert!(format_source("let = ;").is_err());
+    }
+
+    // Every snippet of the parser tests formats to code that formats to itself and still parses
+    #[test]
+    fn test_format_idempotent() {
+        let corpus = include_str!("../parser/tests.rs");
+
+        for literal in corpus.split("String::from(\"").skip(1) {
+            let code = match literal.find("\")") {
+                Some(end) => literal[..end].replace("\\n", "\n").replace("\\\"", "\"").replace("\\\\", "\\"),
+                None => continue,
+            };
+
+            let formatted = match format_source(&code) {
+                Ok(formatted) => formatted,
+                Err(_) => continue,
+            };
+
+            assert_eq!(format_source(&formatted).unwrap(), formatted, "{code}");
+
+            if !formatted.is_empty() {
+                let mut lexer = Lexer::new(&formatted);
+                let mut parser = Parser::new(&mut lexer);
+                parse_file(&mut parser);
+                assert!(parser.errors.is_empty(), "{formatted}");
+            }
+        }
+    }
+}
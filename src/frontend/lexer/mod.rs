@@ -1,6 +1,7 @@
 pub mod tests;
 
 use super::token::{
+    Comment,
     Token, // TODO: This may belong to lexer instead of token
     TokenKind,
     Keywords,
@@ -12,10 +13,11 @@ pub struct Lexer<'a> {
     code: &'a[u8],
     character: u8,
     line: usize,
-    column: usize,
+    line_start: usize, // Position of the first character of the line
     position: usize,
     peek_position: usize,
-    keywords: Keywords
+    keywords: Keywords,
+    comments: Vec<Comment>, // Trivia, skipped like whitespace but kept for tools such as the formatter
 }
 
 impl<'a> Lexer<'a> {
@@ -23,14 +25,15 @@ impl<'a> Lexer<'a> {
         let mut lexer = Self {
             code: code.as_bytes(),
             character: 0,
-            line: 0,
-            column: 0,
+            line: 1,
+            line_start: 0,
             position: 0,
             peek_position: 0,
-            keywords: get_keywords()
+            keywords: get_keywords(),
+            comments: vec![],
         };
 
-        if lexer.code.len() > 0 {
+        if !lexer.code.is_empty() {
             lexer.next_character();
             return lexer
         }
@@ -38,6 +41,11 @@ impl<'a> Lexer<'a> {
         panic!("Empty file.");
     }
 
+    // Comments read so far, in source order
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
 
@@ -45,7 +53,7 @@ impl<'a> Lexer<'a> {
             kind: TokenKind::UNKNOW,
             value: self.u8_to_string(self.character),
             line: self.line,
-            column: self.column
+            column: self.position - self.line_start + 1,
         };
 
         match self.character {
@@ -54,6 +62,10 @@ impl<'a> Lexer<'a> {
                     self.next_character();
                     token.kind = TokenKind::EQUALS;
                     token.value = String::from("==");
+                } else if self.get_next_character() == b'>' {
+                    self.next_character();
+                    token.kind = TokenKind::ARROW;
+                    token.value = String::from("=>");
                 } else {
                     token.kind = TokenKind::ASSIGN;
                 }
@@ -89,6 +101,7 @@ impl<'a> Lexer<'a> {
             b'[' => token.kind = TokenKind::LBRACKET,
             b']' => token.kind = TokenKind::RBRACKET,
             b',' => token.kind = TokenKind::COMMA,
+            b'.' => token.kind = TokenKind::DOT,
             b':' => {
                 if self.get_next_character() == b':' {
                     self.next_character();
@@ -157,24 +170,33 @@ impl<'a> Lexer<'a> {
             match self.character {
                 b' ' | b'\t' | b'\r' => self.next_character(),
                 b'\n' => {
-                    self.line += 1;
-                    self.column = 0;
                     self.next_character();
+                    self.line += 1;
+                    self.line_start = self.position;
                 },
                 b'/' => {
                     if self.get_next_character() != b'/' {
                         return;
                     }
 
-                    while self.character != b'\n' {
-                        self.next_character();
+                    let start = self.position;
+                    let column = self.position - self.line_start + 1;
 
-                        if self.character == 0 {
-                            return;
-                        }
+                    // The new line is left for the next iteration to count it
+                    while self.character != b'\n' && self.character != 0 {
+                        self.next_character();
                     }
 
-                    self.next_character();
+                    let text = String::from_utf8_lossy(&self.code[start..self.position]);
+                    self.comments.push(Comment {
+                        text: text.trim_end().to_string(),
+                        line: self.line,
+                        column,
+                    });
+
+                    if self.character == 0 {
+                        return;
+                    }
                 },
                 _ => return
             }
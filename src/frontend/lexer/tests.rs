@@ -5,8 +5,8 @@ mod tests {
         token::TokenKind
     };
 
-    fn test_lex(code: &String, expected_tokens: &Vec<TokenKind>) {
-        let mut lexer = Lexer::new(&code);
+    fn test_lex(code: &str, expected_tokens: &[TokenKind]) {
+        let mut lexer = Lexer::new(code);
 
         let mut index = 0;
         let mut token = lexer.next_token();
@@ -27,6 +27,20 @@ mod tests {
         test_lex(&code, &expected_tokens);
     }
 
+    #[test]
+    fn test_keep_comments() {
+        let code = "// Comment \n\nlet x = 1; //Other comment \n //".to_string();
+        let mut lexer = Lexer::new(&code);
+
+        while lexer.next_token().kind != TokenKind::EOF {}
+
+        let comments: Vec<(&str, usize, usize)> = lexer.comments().iter()
+            .map(|comment| (comment.text.as_str(), comment.line, comment.column))
+            .collect();
+
+        assert_eq!(comments, vec![("// Comment", 1, 1), ("//Other comment", 3, 12), ("//", 4, 2)]);
+    }
+
     #[test]
     fn test_read_numbers() {
         let code = "3 45 0".to_string();
@@ -95,6 +109,24 @@ mod tests {
         test_lex(&code, &expected_tokens);
     }
 
+    #[test]
+    fn test_read_struct() {
+        let code = "struct Point { x: int } p.x".to_string();
+        let expected_tokens = vec![
+            TokenKind::STRUCT,
+            TokenKind::IDENTIFIER,
+            TokenKind::LBRACE,
+            TokenKind::IDENTIFIER,
+            TokenKind::COLON,
+            TokenKind::PRIMITIVE_TYPE,
+            TokenKind::RBRACE,
+            TokenKind::IDENTIFIER,
+            TokenKind::DOT,
+            TokenKind::IDENTIFIER,
+        ];
+        test_lex(&code, &expected_tokens);
+    }
+
     #[test]
     fn test_read_brackets() {
         let code = "[]".to_string();
@@ -113,4 +145,31 @@ mod tests {
         ];
         test_lex(&code, &expected_tokens);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_token_positions() {
+        let code = "let a = 1;\n// Comment\n  a == 2;".to_string();
+        let mut lexer = Lexer::new(&code);
+
+        let mut positions = vec![];
+        let mut token = lexer.next_token();
+        while token.kind != TokenKind::EOF {
+            positions.push((token.value.clone(), token.line, token.column));
+            token = lexer.next_token();
+        }
+
+        let expected: Vec<(String, usize, usize)> = vec![
+            ("let", 1, 1),
+            ("a", 1, 5),
+            ("=", 1, 7),
+            ("1", 1, 9),
+            (";", 1, 10),
+            ("a", 3, 3),
+            ("==", 3, 5),
+            ("2", 3, 8),
+            (";", 3, 9),
+        ].into_iter().map(|(value, line, column)| (value.to_string(), line, column)).collect();
+
+        assert_eq!(positions, expected);
+    }
+}
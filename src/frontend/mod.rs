@@ -2,4 +2,5 @@ pub mod token;
 pub mod lexer;
 pub mod ast;
 pub mod parser;
-pub mod typecheck;
\ No newline at end of file
+pub mod typecheck;
+pub mod formatter;
@@ -1,6 +1,6 @@
 pub mod tests;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, mem};
 
 use super::{
     ast,
@@ -21,15 +21,15 @@ pub enum Precedence {
 	SUM,            // +, -
 	PRODUCT,        // *, /
 	PREFIX,         // -expression, !expression
-	ACCESS,         // expression::expression
 	INDEX,          // identifier[expression]
 	CALL,           // identifier(expression, expression)
+	FIELD,          // expression.field
 }
 
 type Precedences = HashMap<TokenKind, Precedence>;
 
 fn get_precedences() -> Precedences {
-    let mut precedences = Precedences::with_capacity(13);
+    let mut precedences = Precedences::with_capacity(14);
 
     precedences.insert(TokenKind::ASSIGN, Precedence::ASSIGNMENT);
     precedences.insert(TokenKind::OR, Precedence::OR);
@@ -42,9 +42,9 @@ fn get_precedences() -> Precedences {
     precedences.insert(TokenKind::MINUS, Precedence::SUM);
     precedences.insert(TokenKind::ASTERISK, Precedence::PRODUCT);
     precedences.insert(TokenKind::SLASH, Precedence::PRODUCT);
-    precedences.insert(TokenKind::DOUBLECOLON, Precedence::ACCESS);
     precedences.insert(TokenKind::LBRACKET, Precedence::INDEX);
     precedences.insert(TokenKind::LPAREN, Precedence::CALL);
+    precedences.insert(TokenKind::DOT, Precedence::FIELD);
 
     precedences
 }
@@ -83,7 +83,7 @@ fn get_prefix_parsing_functions() -> PrefixParsingFunctions {
 }
 
 fn get_infix_parsing_functions() -> InfixParsingFunctions {
-    let mut functions: InfixParsingFunctions = HashMap::with_capacity(13);
+    let mut functions: InfixParsingFunctions = HashMap::with_capacity(14);
 
     functions.insert(TokenKind::PLUS, parse_infix_expression);
     functions.insert(TokenKind::MINUS, parse_infix_expression);
@@ -97,10 +97,10 @@ fn get_infix_parsing_functions() -> InfixParsingFunctions {
     functions.insert(TokenKind::OR, parse_infix_expression);
 
     functions.insert(TokenKind::ASSIGN, parse_assignment_expression);
-    functions.insert(TokenKind::DOUBLECOLON, parse_access_expression);
     functions.insert(TokenKind::LBRACKET, parse_index_expression);
     
     functions.insert(TokenKind::LPAREN, parse_call_expression);
+    functions.insert(TokenKind::DOT, parse_field_expression);
 
     return functions
 }
@@ -112,7 +112,8 @@ pub struct Parser<'a> {
     pub errors: Vec<String>,
     prefix_parsing_functions: PrefixParsingFunctions,
     infix_parsing_functions: InfixParsingFunctions,
-    precedences: Precedences
+    precedences: Precedences,
+    struct_literals: bool, // Off in conditions, where a brace after a name opens the block
 }
 
 impl<'a> Parser<'a> {
@@ -127,7 +128,8 @@ impl<'a> Parser<'a> {
             errors: vec![],
             prefix_parsing_functions: get_prefix_parsing_functions(),
             infix_parsing_functions: get_infix_parsing_functions(),
-            precedences: get_precedences()
+            precedences: get_precedences(),
+            struct_literals: true,
         }
     }
 
@@ -142,12 +144,10 @@ impl<'a> Parser<'a> {
             return true
         } else {
             self.add_error(
-                String::from(
-                    format!(
-                        "Expected token {:?}, instead got: {:?}", 
-                        expected, 
-                        self.current_token.kind
-                    )
+                format!(
+                    "Expected token {:?}, instead got: {:?}", 
+                    expected, 
+                    self.current_token.kind
                 )
             );
             return false
@@ -207,6 +207,7 @@ pub fn parse_file(parser: &mut Parser) -> ast::File {
 fn parse_statement(parser: &mut Parser) -> ast::Statement {
     match parser.current_token.kind {
         TokenKind::LET => parse_let_stament(parser),
+        TokenKind::STRUCT => parse_struct_statement(parser),
         _ => parse_expression_statement(parser)
     }
 }
@@ -253,17 +254,64 @@ fn parse_let_stament(parser: &mut Parser) -> ast::Statement {
     return ast::Statement::Let(statement);
 }
 
-fn parse_expression_statement(parser: &mut Parser) -> ast::Statement {
-    let expression = ast::Statement::Expression(
-        ast::ExpressionStatement {
-            node: ast::Node {
-                token: parser.get_current_token()
-            },
-            expression: parse_expression(parser, Precedence::LOWEST)
+// struct Point { x: int, y: int }
+fn parse_struct_statement(parser: &mut Parser) -> ast::Statement {
+    let node = ast::Node {
+        token: parser.get_current_token()
+    };
+
+    parser.assert_peek(TokenKind::IDENTIFIER);
+    let identifier = parse_identifier(parser);
+
+    parser.assert_peek(TokenKind::LBRACE);
+
+    let mut fields = vec![];
+    while !parser.is_peek_token(TokenKind::RBRACE) && !parser.is_peek_token(TokenKind::EOF) {
+        parser.assert_peek(TokenKind::IDENTIFIER);
+        let field = parse_identifier(parser);
+
+        parser.assert_peek(TokenKind::COLON);
+        parser.next_token();
+
+        fields.push(
+            ast::StructField {
+                identifier: field,
+                annotation: parse_type(parser),
+            }
+        );
+
+        // The last field may be followed by a comma
+        if !parser.is_peek_token(TokenKind::COMMA) {
+            break
         }
-    );
 
-    if parser.is_current_token(TokenKind::RBRACE) {
+        parser.next_token();
+    }
+
+    parser.assert_peek(TokenKind::RBRACE);
+
+    let end = ast::Node {
+        token: parser.get_current_token()
+    };
+
+    return ast::Statement::Struct(
+        ast::StructStatement {
+            node,
+            identifier,
+            fields,
+            end,
+        }
+    )
+}
+
+fn parse_expression_statement(parser: &mut Parser) -> ast::Statement {
+    let node = ast::Node {
+        token: parser.get_current_token()
+    };
+
+    let expression = parse_expression(parser, Precedence::LOWEST);
+
+    if expression.ends_with_block() {
         // At the end of a block, no semicolon should be added
         if parser.is_peek_token(TokenKind::SEMICOLON) {
             parser.add_error(String::from("Unexpected semicolon after block"));
@@ -273,7 +321,12 @@ fn parse_expression_statement(parser: &mut Parser) -> ast::Statement {
         parser.assert_peek(TokenKind::SEMICOLON);
     }
 
-    return expression
+    return ast::Statement::Expression(
+        ast::ExpressionStatement {
+            node,
+            expression,
+        }
+    )
 }
 
 // Expressions
@@ -282,7 +335,13 @@ fn parse_expression(parser: &mut Parser, precedence: Precedence) -> Box<ast::Exp
     let prefix_function = match parser.prefix_parsing_functions.get(&parser.current_token.kind) {
         Some(prefix_function) => *prefix_function,
         None => {
-            panic!("Prefix function for token {:?} not found", parser.current_token.kind)
+            parser.add_error(format!("Expected an expression, instead got: {:?}", parser.current_token.kind));
+
+            // The token stands for the missing expression, parsing goes on to report further errors
+            return Box::new(ast::Expression::Identifier(ast::Identifier {
+                node: ast::Node { token: parser.get_current_token() },
+                value: parser.current_token.value.clone(),
+            }))
         }
     };
 
@@ -302,6 +361,10 @@ fn parse_expression(parser: &mut Parser, precedence: Precedence) -> Box<ast::Exp
 }
 
 fn parse_identifier_expression(parser: &mut Parser) -> Box<ast::Expression> {
+    if parser.struct_literals && parser.is_peek_token(TokenKind::LBRACE) {
+        return parse_struct_expression(parser)
+    }
+
     Box::new(
         ast::Expression::Identifier(
             parse_identifier(parser)
@@ -390,14 +453,18 @@ fn parse_boolean_literal(parser: &mut Parser) -> Box<ast::Expression> {
     )
 }
 
+// Named functions are declared in their scope, anonymous ones are values.
+// The body is a block, or an expression after an arrow: fn(x: int) -> int => x * 2
 fn parse_function(parser: &mut Parser) -> Box<ast::Expression> {
     let node = ast::Node {
         token: parser.get_current_token()
     };
 
-    parser.assert_peek(TokenKind::IDENTIFIER);
-
-    let identifier = parse_identifier(parser);
+    let mut identifier = None;
+    if parser.is_peek_token(TokenKind::IDENTIFIER) {
+        parser.next_token();
+        identifier = Some(parse_identifier(parser));
+    }
 
     parser.assert_peek(TokenKind::LPAREN);
 
@@ -407,7 +474,8 @@ fn parse_function(parser: &mut Parser) -> Box<ast::Expression> {
 
     // Parse annotation
     let mut annotation = Type::Void;
-    if parser.is_peek_token(TokenKind::MINUS) {
+    let annotated = parser.is_peek_token(TokenKind::MINUS);
+    if annotated {
         parser.assert_peek(TokenKind::MINUS);
         parser.assert_peek(TokenKind::GREATER_THAN);
         parser.next_token();
@@ -415,9 +483,19 @@ fn parse_function(parser: &mut Parser) -> Box<ast::Expression> {
         annotation = parse_type(parser);
     }
 
-    parser.assert_peek(TokenKind::LBRACE);
-    
-    let body = parse_block_expression(parser);
+    let body = if parser.is_peek_token(TokenKind::ARROW) {
+        // The returned expression doesn't say its type, it has to be written
+        if !annotated {
+            parser.add_error(String::from("Expected a return type before '=>', like fn(x: int) -> int => x * 2"));
+        }
+
+        parser.next_token();
+        parser.next_token();
+        parse_expression(parser, Precedence::LOWEST)
+    } else {
+        parser.assert_peek(TokenKind::LBRACE);
+        parse_block_expression(parser)
+    };
 
     return Box::new(
         ast::Expression::Function(
@@ -534,17 +612,22 @@ fn parse_infix_expression(parser: &mut Parser, left_expression: Box<ast::Express
 fn parse_grouped_expression(parser: &mut Parser) -> Box<ast::Expression> {
     parser.next_token();
 
+    let struct_literals = mem::replace(&mut parser.struct_literals, true);
     let expression = parse_expression(parser, Precedence::LOWEST);
+    parser.struct_literals = struct_literals;
 
     parser.assert_peek(TokenKind::RPAREN);
 
     return expression
 }
 
+// Infix parsing functions all take the left operand boxed
+#[allow(clippy::boxed_local)]
 fn parse_assignment_expression(parser: &mut Parser, expression: Box<ast::Expression>) -> Box<ast::Expression> {
 
     let identifier = match *expression {
         ast::Expression::Identifier(identifier) => identifier,
+        ast::Expression::Field(target) => return parse_field_assignment_expression(parser, target),
         _ => {
             parser.add_error(String::from("Expected identifier."));
             panic!("Expected identifier.");
@@ -568,6 +651,24 @@ fn parse_assignment_expression(parser: &mut Parser, expression: Box<ast::Express
     )
 }
 
+fn parse_field_assignment_expression(parser: &mut Parser, target: ast::FieldExpression) -> Box<ast::Expression> {
+    let node = ast::Node {
+        token: parser.get_current_token()
+    };
+
+    parser.next_token();
+
+    return Box::new(
+        ast::Expression::FieldAssign(
+            ast::FieldAssignmentExpression {
+                node,
+                target,
+                expression: parse_expression(parser, Precedence::LOWEST),
+            }
+        )
+    )
+}
+
 fn parse_array_expression(parser: &mut Parser) -> Box<ast::Expression> {
     let node = ast::Node {
         token: parser.get_current_token()
@@ -587,6 +688,8 @@ fn parse_array_expression(parser: &mut Parser) -> Box<ast::Expression> {
     )
 }
 
+// Elements are boxed like every other expression of the tree
+#[allow(clippy::vec_box)]
 fn parse_array_elements(parser: &mut Parser) -> Vec<Box<ast::Expression>> {
     let mut arguments = Vec::<Box<ast::Expression>>::new();
 
@@ -619,11 +722,16 @@ fn parse_block_expression(parser: &mut Parser) -> Box<ast::Expression> {
         parser.next_token();
     }
 
+    let end = ast::Node {
+        token: parser.get_current_token()
+    };
+
     return Box::new(
         ast::Expression::Block(
             ast::BlockExpression {
                 node,
-                statements
+                statements,
+                end,
             }
         )
     )
@@ -636,7 +744,7 @@ fn parse_if_expression(parser: &mut Parser) -> Box<ast::Expression> {
 
     parser.next_token();
 
-    let condition = parse_expression(parser, Precedence::LOWEST);
+    let condition = parse_condition(parser);
 
     parser.assert_peek(TokenKind::LBRACE);
 
@@ -673,7 +781,7 @@ fn parse_while_expression(parser: &mut Parser) -> Box<ast::Expression> {
     };
 
     parser.next_token();
-    let condition = parse_expression(parser, Precedence::LOWEST);
+    let condition = parse_condition(parser);
 
 
     parser.assert_peek(TokenKind::LBRACE);
@@ -691,6 +799,15 @@ fn parse_while_expression(parser: &mut Parser) -> Box<ast::Expression> {
     )
 }
 
+// A struct literal would take the opening brace of the block, it must be grouped in conditions
+fn parse_condition(parser: &mut Parser) -> Box<ast::Expression> {
+    let struct_literals = mem::replace(&mut parser.struct_literals, false);
+    let condition = parse_expression(parser, Precedence::LOWEST);
+    parser.struct_literals = struct_literals;
+
+    return condition
+}
+
 fn parse_break_expression(parser: &mut Parser) -> Box<ast::Expression> {
     return Box::new(
         ast::Expression::Break(
@@ -723,6 +840,7 @@ fn parse_call_expression(parser: &mut Parser, identifier: Box<ast::Expression>)
     )
 }
 
+#[allow(clippy::vec_box)]
 fn parse_call_arguments(parser: &mut Parser) -> Vec<Box<ast::Expression>> {
     let mut arguments = Vec::<Box<ast::Expression>>::new();
 
@@ -742,20 +860,68 @@ fn parse_call_arguments(parser: &mut Parser) -> Vec<Box<ast::Expression>> {
     return arguments
 }
 
-fn parse_access_expression(parser: &mut Parser, left_expression: Box<ast::Expression>) -> Box<ast::Expression> {
+fn parse_field_expression(parser: &mut Parser, expression: Box<ast::Expression>) -> Box<ast::Expression> {
     let node = ast::Node {
         token: parser.get_current_token()
     };
 
-    parser.next_token();
-    let right_expression = parse_expression(parser, Precedence::LOWEST);
+    parser.assert_peek(TokenKind::IDENTIFIER);
 
     return Box::new(
-        ast::Expression::Access(
-            ast::AccessExpression {
+        ast::Expression::Field(
+            ast::FieldExpression {
                 node,
-                left_expression,
-                right_expression,
+                expression,
+                field: parse_identifier(parser),
+            }
+        )
+    )
+}
+
+// Point { x: 1, y: 2 }
+fn parse_struct_expression(parser: &mut Parser) -> Box<ast::Expression> {
+    let node = ast::Node {
+        token: parser.get_current_token()
+    };
+
+    let identifier = parse_identifier(parser);
+    parser.assert_peek(TokenKind::LBRACE);
+
+    let mut fields = vec![];
+    while !parser.is_peek_token(TokenKind::RBRACE) && !parser.is_peek_token(TokenKind::EOF) {
+        parser.assert_peek(TokenKind::IDENTIFIER);
+        let field = parse_identifier(parser);
+
+        parser.assert_peek(TokenKind::COLON);
+        parser.next_token();
+
+        fields.push(
+            ast::FieldInitializer {
+                identifier: field,
+                expression: parse_expression(parser, Precedence::LOWEST),
+            }
+        );
+
+        if !parser.is_peek_token(TokenKind::COMMA) {
+            break
+        }
+
+        parser.next_token();
+    }
+
+    parser.assert_peek(TokenKind::RBRACE);
+
+    let end = ast::Node {
+        token: parser.get_current_token()
+    };
+
+    return Box::new(
+        ast::Expression::Struct(
+            ast::StructExpression {
+                node,
+                identifier,
+                fields,
+                end,
             }
         )
     )
@@ -789,6 +955,8 @@ fn parse_type(parser: &mut Parser) -> Type {
     match parser.current_token.kind {
         TokenKind::PRIMITIVE_TYPE => parse_primitive_type(parser),
         TokenKind::LBRACKET => parse_array_type(parser),
+        TokenKind::FUNCTION => parse_function_type(parser),
+        TokenKind::IDENTIFIER => Type::Struct(parser.current_token.value.clone()),
         _ => {
             parser.add_error(format!("Invalid type '{}'", parser.current_token.value));
             Type::Integer
@@ -814,3 +982,33 @@ fn parse_array_type(parser: &mut Parser) -> Type {
     parser.assert_peek(TokenKind::RBRACKET);
     return Type::Array(Box::new(array_type));
 }
+
+// fn(int, bool) -> int, the return type defaults to void as in declarations
+fn parse_function_type(parser: &mut Parser) -> Type {
+    parser.assert_peek(TokenKind::LPAREN);
+
+    let mut parameters = vec![];
+    while !parser.is_peek_token(TokenKind::RPAREN) && !parser.is_peek_token(TokenKind::EOF) {
+        parser.next_token();
+        parameters.push(parse_type(parser));
+
+        if !parser.is_peek_token(TokenKind::COMMA) {
+            break
+        }
+
+        parser.next_token();
+    }
+
+    parser.assert_peek(TokenKind::RPAREN);
+
+    let mut return_type = Type::Void;
+    if parser.is_peek_token(TokenKind::MINUS) {
+        parser.assert_peek(TokenKind::MINUS);
+        parser.assert_peek(TokenKind::GREATER_THAN);
+        parser.next_token();
+
+        return_type = parse_type(parser);
+    }
+
+    return Type::Function(parameters, Box::new(return_type));
+}
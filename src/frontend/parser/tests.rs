@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use crate::frontend::{
+        ast,
         lexer::Lexer, 
         parser::{
             parse_file, 
@@ -8,13 +9,32 @@ mod tests {
         }
     };
 
-    fn test_parse(code: &String) {
+    fn parse(code: &str) -> ast::File {
+        let mut lexer = Lexer::new(code);
+        let mut parser = Parser::new(&mut lexer);
+
+        let file = parse_file(&mut parser);
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+        file
+    }
+
+    // Golden comparison against the fully parenthesized rendering of the tree
+    fn assert_parse(code: &str, expected: &str) {
+        let file = parse(code);
+        assert_eq!(file.to_string().trim_end(), expected);
+
+        // The rendering is source code itself, with the same tree
+        assert_eq!(parse(&file.to_string()).to_string(), file.to_string());
+    }
+
+    fn test_parse(code: &str) {
         let mut lexer = Lexer::new(code);
         let mut parser = Parser::new(&mut lexer);
 
         parse_file(&mut parser);
 
-        if parser.errors.len() > 0 {
+        if !parser.errors.is_empty() {
             for error in parser.errors {
                 println!("Parsing error: {error}");
             }
@@ -23,6 +43,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_missing_expression() {
+        let mut lexer = Lexer::new("let x = ;");
+        let mut parser = Parser::new(&mut lexer);
+
+        parse_file(&mut parser);
+
+        assert_eq!(parser.errors.first().map(String::as_str), Some("Expected an expression, instead got: SEMICOLON"));
+    }
+
+    #[test]
+    fn test_parse_missing_semicolon_before_brace() {
+        let mut lexer = Lexer::new("fn f() -> int { 1 }");
+        let mut parser = Parser::new(&mut lexer);
+
+        parse_file(&mut parser);
+
+        assert!(!parser.errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_double_colon() {
+        let mut lexer = Lexer::new("a :: b;");
+        let mut parser = Parser::new(&mut lexer);
+
+        parse_file(&mut parser);
+
+        assert!(!parser.errors.is_empty());
+    }
+
+    #[test]
+    fn test_parse_arrow_function_without_return_type() {
+        let mut lexer = Lexer::new("let f = fn(x: int) => x * 2;");
+        let mut parser = Parser::new(&mut lexer);
+
+        parse_file(&mut parser);
+
+        assert_eq!(
+            parser.errors.first().map(String::as_str),
+            Some("Expected a return type before '=>', like fn(x: int) -> int => x * 2")
+        );
+    }
+
     #[test]
     fn test_parse_let_statement() {
         // Initialized, with annotation
@@ -250,19 +313,6 @@ mod tests {
         test_parse(&code);
     }
 
-    #[test]
-    fn test_parse_access_expression() {
-        let code = String::from("
-            x::y;
-        ");
-        test_parse(&code);
-
-        let code = String::from("
-            z::call();
-        ");
-        test_parse(&code);
-    }
-
     #[test]
     fn test_parse_index_expression() {
         let code = String::from("
@@ -287,4 +337,89 @@ mod tests {
         test_parse(&code);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_parse_function_type() {
+        assert_parse("let f: fn(int, bool) -> int;", "let f: fn(int, bool) -> int;");
+        assert_parse("let f: fn();", "let f: fn() -> void;");
+        assert_parse("let f: [fn(fn(int) -> bool) -> [int]];", "let f: [fn(fn(int) -> bool) -> [int]];");
+        assert_parse(
+            "fn compose(f: fn(int) -> int) -> fn(int) -> int { return f; }",
+            "fn compose(f: fn(int) -> int) -> fn(int) -> int { (return f); }",
+        );
+    }
+
+    #[test]
+    fn test_parse_anonymous_function() {
+        assert_parse("let f = fn(x: int) -> int { x * 2; };", "let f = fn(x: int) -> int { (x * 2); };");
+        assert_parse("let f = fn(x: int) -> int => x + 1;", "let f = (fn(x: int) -> int => (x + 1));");
+        assert_parse("apply(fn() -> int => 1, 2);", "apply((fn() -> int => 1), 2);");
+        assert_parse("(fn(x: int) -> int => x)(3);", "(fn(x: int) -> int => x)(3);");
+    }
+
+    #[test]
+    fn test_parse_struct() {
+        assert_parse("struct Point { x: int, y: int, }", "struct Point { x: int, y: int }");
+        assert_parse("struct Empty {}", "struct Empty {}");
+        assert_parse("struct Line { from: Point, to: Point }", "struct Line { from: Point, to: Point }");
+        assert_parse("let p = Point { x: 1, y: 2 + 3 };", "let p = Point { x: 1, y: (2 + 3) };");
+        assert_parse("p.x + line.to.y * 2;", "(p.x + (line.to.y * 2));");
+        assert_parse("line.from.x = p.y = 1;", "(line.from.x = (p.y = 1));");
+        assert_parse("make().x;", "make().x;");
+        assert_parse("if (P { x: 1 }).x == 1 { 1; }", "if ((P { x: 1 }.x == 1)) { 1; }");
+        assert_parse("while p.x < 3 { p.x = p.x + 1; }", "while (p.x < 3) { (p.x = (p.x + 1)); }");
+        assert_parse("fn f() -> P { return P { x: 1 }; }", "fn f() -> P { (return P { x: 1 }); }");
+    }
+
+    // Golden trees
+
+    #[test]
+    fn test_parse_golden_precedence() {
+        assert_parse("1 + 2 * 3 - 4;", "((1 + (2 * 3)) - 4);");
+        assert_parse("a || b && c == d < e;", "(a || (b && (c == (d < e))));");
+        assert_parse("-x * !y;", "((-x) * (!y));");
+        assert_parse("(1 + 2) * 3;", "((1 + 2) * 3);");
+        assert_parse("x = y = 1 + 2;", "(x = (y = (1 + 2)));");
+        assert_parse("a[1][2] + f(x)(y);", "(a[1][2] + f(x)(y));");
+    }
+
+    #[test]
+    fn test_parse_golden_statements() {
+        assert_parse("let x: [int] = [1, 2 + 3];", "let x: [int] = [1, (2 + 3)];");
+        assert_parse("let y;", "let y;");
+        assert_parse(
+            "fn f(a: int, b: bool) -> int { if b { return a; } else { a + 1; } }",
+            "fn f(a: int, b: bool) -> int { if b { (return a); } else { (a + 1); } }",
+        );
+        assert_parse("while i < 10 { i = i + 1; break; }", "while (i < 10) { (i = (i + 1)); break; }");
+        assert_parse("fn main() {}\n'c';\n\"s\";", "fn main() -> void {}\n'c';\n\"s\";");
+    }
+
+    #[test]
+    fn test_parse_sexp() {
+        let file = parse("let x: int = -1 + f(2, [3]); if x > 0 { x; } else { while true { break; } }");
+        assert_eq!(
+            file.to_sexp(),
+            "(file (let (x int) (+ (- 1) (call f 2 (array 3)))) (if (> x 0) (block x) (block (while true (block (break))))))",
+        );
+
+        let file = parse("fn id(a: int) -> int { return a; } id(1) + b[0];");
+        assert_eq!(file.to_sexp(), "(file (fn id ((a int)) int (block (return a))) (+ (call id 1) (index b 0)))");
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let file = parse("let s = \"a\";\nx + 1;");
+        assert_eq!(
+            file.to_json(),
+            concat!(
+                "{\"kind\":\"File\",\"statements\":[",
+                "{\"kind\":\"Let\",\"line\":1,\"column\":1,\"identifier\":\"s\",\"annotation\":null,",
+                "\"expression\":{\"kind\":\"String\",\"line\":1,\"column\":9,\"value\":\"a\"}},",
+                "{\"kind\":\"Expression\",\"line\":2,\"column\":1,\"expression\":",
+                "{\"kind\":\"Infix\",\"line\":2,\"column\":3,\"operator\":\"+\",",
+                "\"left\":{\"kind\":\"Identifier\",\"line\":2,\"column\":1,\"value\":\"x\"},",
+                "\"right\":{\"kind\":\"Number\",\"line\":2,\"column\":5,\"value\":1}}}]}",
+            ),
+        );
+    }
+}
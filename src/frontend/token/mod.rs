@@ -29,8 +29,10 @@ pub enum TokenKind {
     // Delimiters
     EOF,
     COMMA,
+    DOT,
     COLON,
     DOUBLECOLON,
+    ARROW,
     SEMICOLON,
 	LPAREN,
 	RPAREN,
@@ -52,6 +54,7 @@ pub enum TokenKind {
     BREAK,
     FUNCTION,
     RETURN,
+    STRUCT,
 
     // Types
     PRIMITIVE_TYPE,
@@ -60,7 +63,7 @@ pub enum TokenKind {
 pub type Keywords = HashMap<&'static str, TokenKind>;
 
 pub fn get_keywords() -> Keywords {
-    let mut keywords: Keywords = HashMap::with_capacity(11);
+    let mut keywords: Keywords = HashMap::with_capacity(13);
 
     keywords.insert("let", TokenKind::LET);
     keywords.insert("true", TokenKind::TRUE);
@@ -71,6 +74,7 @@ pub fn get_keywords() -> Keywords {
     keywords.insert("break", TokenKind::BREAK);
     keywords.insert("fn", TokenKind::FUNCTION);
     keywords.insert("return", TokenKind::RETURN);
+    keywords.insert("struct", TokenKind::STRUCT);
 
     keywords.insert("int", TokenKind::PRIMITIVE_TYPE);
     keywords.insert("bool", TokenKind::PRIMITIVE_TYPE);
@@ -86,7 +90,15 @@ pub fn get_token_kind(keywords: &Keywords, identifier: &str) -> TokenKind {
     }
 }
 
-#[derive(Clone)]
+// A "//" comment, up to the end of its line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub value: String,
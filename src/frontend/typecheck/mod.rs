@@ -1,12 +1,63 @@
 pub mod tests;
 pub mod types;
+pub mod model;
 
-use std::collections::HashMap;
+use std::{collections::{HashMap, HashSet}, fmt, mem};
 
+use model::{Binding, BindingKind, SemanticModel};
 use types::Type;
 
 use super::ast;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub message: String,
+    pub line: usize, // 0 until the statement holding the error is known
+    pub column: usize,
+    pub declaration: Option<(usize, usize)>, // Line and column of the name the error is about
+}
+
+impl TypeError {
+    pub fn new(message: String) -> Self {
+        Self {
+            message,
+            line: 0,
+            column: 0,
+            declaration: None,
+        }
+    }
+
+    // Error on the use of a name, which also points at its declaration
+    fn at_use(message: String, identifier: &ast::Identifier, declaration: (usize, usize)) -> Self {
+        Self {
+            message,
+            line: identifier.node.token.line,
+            column: identifier.node.token.column,
+            declaration: match declaration {
+                (0, _) => None, // Provided by the host
+                declaration => Some(declaration),
+            },
+        }
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Type error on line {}:{}: {}", self.line, self.column, self.message)?;
+
+        match self.declaration {
+            Some((line, column)) => write!(f, " (declared on line {line}:{column})"),
+            None => Ok(()),
+        }
+    }
+}
+
+macro_rules! type_error {
+    ($($argument:tt)*) => {
+        return Err(TypeError::new(format!($($argument)*)))
+    };
+}
+
 #[derive(Clone)]
 enum Symbol {
     Variable(VariableSymbol),
@@ -17,6 +68,10 @@ enum Symbol {
 struct VariableSymbol {
     name: String,
     variable_type: Type,
+    parameter: bool,
+    assigned: bool, // Definitely holds a value at this point of the code
+    line: usize,
+    column: usize,
 }
 
 #[derive(Clone)]
@@ -24,23 +79,39 @@ struct FunctionSymbol {
     name: String,
     return_type: Type,
     parameters: Vec<Type>,
+    line: usize, // 0 for functions provided by the host
+    column: usize,
+}
+
+// Fields in declaration order, the index of a field is its slot
+#[derive(Clone)]
+struct StructSymbol {
+    name: String,
+    fields: Vec<(String, Type)>,
 }
 
 type Symbols = HashMap<String, Symbol>;
+type Structs = HashMap<String, StructSymbol>;
 
+#[derive(Clone)]
 struct Scope {
     symbols: Symbols,
+    structs: Structs, // Types have their own names, apart from values
     return_type: Type,
+    function_depth: usize, // Functions enclosing the scope
 }
 
+#[derive(Clone)]
 struct SymbolTable {
     scopes: Vec<Scope>,
+    model: SemanticModel,
 }
 
 impl SymbolTable {
     pub fn new() -> Self {
         Self {
             scopes: Vec::<Scope>::new(),
+            model: SemanticModel::new(),
         }
     }
 
@@ -49,21 +120,27 @@ impl SymbolTable {
     }
 
     pub fn enter_scope(&mut self) {
-        let current_scope_type = match self.scopes.last() {
-            Some(scope) => scope.return_type.clone(),
-            None => Type::Void, // This is the global scope return type
+        let (current_scope_type, function_depth) = match self.scopes.last() {
+            Some(scope) => (scope.return_type.clone(), scope.function_depth),
+            None => (Type::Void, 0), // This is the global scope return type
         };
 
         self.scopes.push(Scope {
             symbols: Symbols::new(),
+            structs: Structs::new(),
             return_type: current_scope_type,
+            function_depth,
         });
     }
 
     pub fn enter_function_scope(&mut self, return_type: Type) {
+        let function_depth = self.get_current_scope().function_depth + 1;
+
         self.scopes.push(Scope {
             symbols: Symbols::new(),
+            structs: Structs::new(),
             return_type,
+            function_depth,
         });
     }
 
@@ -74,7 +151,7 @@ impl SymbolTable {
     pub fn insert(&mut self, symbol: Symbol) {
         let current_scope = match self.scopes.last_mut() {
             Some(scope) => scope,
-            None => panic!("No scope found.")
+            None => unreachable!("No scope found.")
         };
 
         let symbol_name = get_symbol_name(&symbol);
@@ -91,6 +168,106 @@ impl SymbolTable {
         return None
     }
 
+    pub fn insert_struct(&mut self, symbol: StructSymbol) {
+        let current_scope = match self.scopes.last_mut() {
+            Some(scope) => scope,
+            None => unreachable!("No scope found.")
+        };
+
+        current_scope.structs.insert(symbol.name.clone(), symbol);
+    }
+
+    pub fn get_struct(&self, name: &str) -> Option<&StructSymbol> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(symbol) = scope.structs.get(name) {
+                return Some(symbol)
+            }
+        }
+
+        return None
+    }
+
+    // Symbol of a name, with where its value lives as seen from the current scope
+    pub fn resolve(&self, name: &str) -> Option<(&Symbol, BindingKind)> {
+        let function_depth = self.get_current_scope().function_depth;
+
+        for (index, scope) in self.scopes.iter().enumerate().rev() {
+            if let Some(symbol) = scope.symbols.get(name) {
+                let kind = match symbol {
+                    _ if index == 0 => BindingKind::Global,
+                    _ if scope.function_depth != function_depth => BindingKind::Upvalue,
+                    Symbol::Variable(variable) if variable.parameter => BindingKind::Parameter,
+                    _ => BindingKind::Local,
+                };
+
+                return Some((symbol, kind))
+            }
+        }
+
+        return None
+    }
+
+    pub fn assign(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(symbol) = scope.symbols.get_mut(name) {
+                if let Symbol::Variable(variable) = symbol {
+                    variable.assigned = true;
+                }
+
+                return
+            }
+        }
+    }
+
+    // Variables which may not hold a value yet, by scope
+    pub fn unassigned(&self) -> HashSet<(usize, String)> {
+        let mut unassigned = HashSet::new();
+
+        for (index, scope) in self.scopes.iter().enumerate() {
+            for symbol in scope.symbols.values() {
+                if let Symbol::Variable(variable) = symbol {
+                    if !variable.assigned {
+                        unassigned.insert((index, variable.name.clone()));
+                    }
+                }
+            }
+        }
+
+        return unassigned
+    }
+
+    pub fn set_unassigned(&mut self, unassigned: &HashSet<(usize, String)>) {
+        for (index, scope) in self.scopes.iter_mut().enumerate() {
+            for symbol in scope.symbols.values_mut() {
+                if let Symbol::Variable(variable) = symbol {
+                    variable.assigned = !unassigned.contains(&(index, variable.name.clone()));
+                }
+            }
+        }
+    }
+
+    // Records the declaration an identifier refers to
+    pub fn bind(&mut self, identifier: &ast::Identifier) {
+        let binding = match self.resolve(&identifier.value) {
+            Some((Symbol::Variable(variable), kind)) => Binding {
+                name: variable.name.clone(),
+                kind,
+                binding_type: variable.variable_type.clone(),
+                line: variable.line,
+                column: variable.column,
+            },
+            Some((Symbol::Function(function), kind)) => Binding {
+                name: function.name.clone(),
+                kind,
+                binding_type: Type::Function(function.parameters.clone(), Box::new(function.return_type.clone())),
+                line: function.line,
+                column: function.column,
+            },
+            None => return,
+        };
+
+        self.model.set_binding(identifier, binding);
+    }
 }
 
 fn get_symbol_name(symbol: &Symbol) -> String {
@@ -100,10 +277,34 @@ fn get_symbol_name(symbol: &Symbol) -> String {
     }
 }
 
+// Structs are declared first, so any annotation of the scope can name them
+fn declare_scope_structs(symbol_table: &mut SymbolTable, statements: &Vec<ast::Statement>) {
+    for statement in statements {
+        if let ast::Statement::Struct(statement) = statement {
+            symbol_table.insert_struct(struct_symbol(statement));
+        }
+    }
+}
+
+fn struct_symbol(statement: &ast::StructStatement) -> StructSymbol {
+    StructSymbol {
+        name: statement.identifier.value.clone(),
+        fields: statement.fields.iter()
+            .map(|field| (field.identifier.value.clone(), field.annotation.clone()))
+            .collect(),
+    }
+}
+
 fn declare_scope_functions(symbol_table: &mut SymbolTable, statements: &Vec<ast::Statement>) {
     for statement in statements {
         if let ast::Statement::Expression(expression) = statement {
             if let ast::Expression::Function(function) = expression.expression.as_ref() {
+                // Anonymous functions aren't declared
+                let identifier = match &function.identifier {
+                    Some(identifier) => identifier,
+                    None => continue,
+                };
+
                 let mut parameters_types = Vec::<Type>::new();
                 for parameter in &function.parameters {
                     parameters_types.push(parameter.annotation.clone());
@@ -112,96 +313,252 @@ fn declare_scope_functions(symbol_table: &mut SymbolTable, statements: &Vec<ast:
                 symbol_table.insert(
                     Symbol::Function(
                         FunctionSymbol {
-                            name: function.identifier.value.clone(),
+                            name: identifier.value.clone(),
                             return_type: function.annotation.clone(),
                             parameters: parameters_types,
+                            line: identifier.node.token.line,
+                            column: identifier.node.token.column,
                         }
                     )
                 );
+                symbol_table.bind(identifier);
             }
         }
     }
 }
 
-pub fn check_program(file: &ast::File) {
-    check_file(file);
+// Function provided by the host, such as a native, callable like a global function
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub parameters: Vec<Type>,
+    pub return_type: Type,
 }
 
-pub fn check_file(file: &ast::File) {
-    let mut symbol_table = SymbolTable::new();
+pub fn check_program(file: &ast::File) -> Result<SemanticModel, TypeError> {
+    check_file(file, &[])
+}
 
-    // Global scope
-    symbol_table.enter_scope();
+pub fn check_file(file: &ast::File, externals: &[FunctionSignature]) -> Result<SemanticModel, TypeError> {
+    Environment::new(externals).check(file)
+}
 
-    // We first declare functions so their can be used before their declaration
-    declare_scope_functions(&mut symbol_table, &file.statements);
-    
-    for statement in &file.statements {
-        check_statement(&mut symbol_table, statement);
-    }
+// Global scope kept between checks, so code can use what earlier code declared
+#[derive(Clone)]
+pub struct Environment {
+    symbol_table: SymbolTable,
 }
 
-fn check_statement(symbol_table: &mut SymbolTable, statement: &ast::Statement) {
-    match statement {
-        ast::Statement::Let(let_statement) => check_let_statement(symbol_table, let_statement),
-        ast::Statement::Expression(expression) => {
-            let expected_type = synthesize_expression(symbol_table, &expression.expression);
-            check_expression(symbol_table, &expression.expression, expected_type)
-        },
+impl Environment {
+    pub fn new(externals: &[FunctionSignature]) -> Self {
+        let mut symbol_table = SymbolTable::new();
+
+        // Global scope
+        symbol_table.enter_scope();
+
+        for external in externals {
+            symbol_table.insert(
+                Symbol::Function(
+                    FunctionSymbol {
+                        name: external.name.clone(),
+                        return_type: external.return_type.clone(),
+                        parameters: external.parameters.clone(),
+                        line: 0,
+                        column: 0,
+                    }
+                )
+            );
+        }
+
+        Self { symbol_table }
+    }
+
+    // Declarations of a file that doesn't typecheck are discarded
+    pub fn check(&mut self, file: &ast::File) -> Result<SemanticModel, TypeError> {
+        let scopes = self.symbol_table.scopes.clone();
+
+        let result = self.check_statements(&file.statements);
+        let model = mem::take(&mut self.symbol_table.model);
+
+        match result {
+            Ok(()) => Ok(model),
+            Err(error) => {
+                self.symbol_table.scopes = scopes;
+                Err(error)
+            },
+        }
+    }
+
+    fn check_statements(&mut self, statements: &Vec<ast::Statement>) -> Result<(), TypeError> {
+        // We first declare functions so their can be used before their declaration
+        declare_scope_structs(&mut self.symbol_table, statements);
+        declare_scope_functions(&mut self.symbol_table, statements);
+
+        for statement in statements {
+            check_statement(&mut self.symbol_table, statement)?;
+        }
+
+        Ok(())
+    }
+
+    // Type of an expression in the global scope, without declaring anything
+    pub fn type_of(&mut self, expression: &ast::Expression) -> Result<Type, TypeError> {
+        let scopes = self.symbol_table.scopes.clone();
+
+        let result = synthesize_expression(&mut self.symbol_table, expression).and_then(|expression_type| {
+            check_expression(&mut self.symbol_table, expression, expression_type.clone())?;
+            Ok(expression_type)
+        });
+
+        self.symbol_table.scopes = scopes;
+        self.symbol_table.model = SemanticModel::new();
+        result
     }
 }
 
-fn check_let_statement(symbol_table: &mut SymbolTable, statement: &ast::LetStatement) {
+// Errors are located at the innermost statement holding them
+fn check_statement(symbol_table: &mut SymbolTable, statement: &ast::Statement) -> Result<(), TypeError> {
+    let (result, node) = match statement {
+        ast::Statement::Let(let_statement) => (check_let_statement(symbol_table, let_statement), &let_statement.node),
+        ast::Statement::Struct(struct_statement) => (check_struct_statement(symbol_table, struct_statement), &struct_statement.node),
+        ast::Statement::Expression(expression) => {
+            let result = synthesize_expression(symbol_table, &expression.expression)
+                .and_then(|expected_type| check_expression(symbol_table, &expression.expression, expected_type));
 
-    let assigned_type = match &statement.expression {
-        Some(expression) => {
-            let assigned_type = synthesize_expression(symbol_table, expression);
-            check_expression(symbol_table, expression, assigned_type.clone());
-            assigned_type
-        },
-        None => {
-            todo!("Requires typing annotation.")
+            (result, &expression.node)
         },
     };
 
-    match &statement.annotation {
-        Some(annotation ) => {
-            if annotation != &assigned_type && assigned_type != Type::None {
-                panic!("Expected {:?}, instead got {:?}", annotation, assigned_type);
+    result.map_err(|mut error| {
+        if error.line == 0 {
+            error.line = node.token.line;
+            error.column = node.token.column;
+        }
+
+        error
+    })
+}
+
+// Variables without annotation take the type of their value,
+// variables without value must be assigned before being read.
+fn check_let_statement(symbol_table: &mut SymbolTable, statement: &ast::LetStatement) -> Result<(), TypeError> {
+    let name = &statement.identifier.value;
+
+    if let Some(annotation) = &statement.annotation {
+        check_type(symbol_table, annotation)?;
+    }
+
+    let variable_type = match (&statement.annotation, &statement.expression) {
+        (Some(annotation), Some(expression)) => {
+            check_expression(symbol_table, expression, annotation.clone())?;
+            annotation.clone()
+        },
+        (None, Some(expression)) => {
+            let assigned_type = synthesize_expression(symbol_table, expression)?;
+            if assigned_type == Type::Void {
+                type_error!("Cannot declare '{name}' with a void value");
             }
+
+            check_expression(symbol_table, expression, assigned_type.clone())?;
+            assigned_type
         },
-        None => {}
+        (Some(annotation), None) => annotation.clone(),
+        (None, None) => type_error!("Cannot infer the type of '{name}' without a value, it requires an annotation"),
     };
 
     symbol_table.insert(
         Symbol::Variable(
             VariableSymbol {
-                name: statement.identifier.value.clone(),
-                variable_type: assigned_type
+                name: name.clone(),
+                variable_type,
+                parameter: false,
+                assigned: statement.expression.is_some(),
+                line: statement.identifier.node.token.line,
+                column: statement.identifier.node.token.column,
             }
         )
     );
+    symbol_table.bind(&statement.identifier);
+
+    Ok(())
+}
+
+// Structs declared in blocks are only known from their declaration on
+fn check_struct_statement(symbol_table: &mut SymbolTable, statement: &ast::StructStatement) -> Result<(), TypeError> {
+    let symbol = struct_symbol(statement);
+    symbol_table.insert_struct(symbol.clone());
+
+    for (index, (name, field_type)) in symbol.fields.iter().enumerate() {
+        if symbol.fields[..index].iter().any(|(other, _)| other == name) {
+            type_error!("Field '{name}' is declared twice in struct '{}'", symbol.name);
+        }
+
+        check_type(symbol_table, field_type)?;
+    }
+
+    Ok(())
+}
+
+// Annotations may only name declared structs
+fn check_type(symbol_table: &SymbolTable, annotation: &Type) -> Result<(), TypeError> {
+    match annotation {
+        Type::Struct(name) if symbol_table.get_struct(name).is_none() => {
+            type_error!("Unknown type '{name}'");
+        },
+        Type::Array(element) => check_type(symbol_table, element)?,
+        Type::Function(parameters, return_type) => {
+            for parameter in parameters {
+                check_type(symbol_table, parameter)?;
+            }
+
+            check_type(symbol_table, return_type)?;
+        },
+        _ => {},
+    }
+
+    Ok(())
 }
 
-fn check_expression(symbol_table: &mut SymbolTable, expression: &ast::Expression, expected_type: Type) {
+// Checked expressions are recorded in the model with their type
+fn check_expression(symbol_table: &mut SymbolTable, expression: &ast::Expression, expected_type: Type) -> Result<(), TypeError> {
+    check_expression_type(symbol_table, expression, expected_type.clone())?;
+    symbol_table.model.set_type(expression, expected_type);
+
+    Ok(())
+}
+
+fn check_expression_type(symbol_table: &mut SymbolTable, expression: &ast::Expression, expected_type: Type) -> Result<(), TypeError> {
     match expression {
         ast::Expression::Identifier(identifier) => check_identifier(symbol_table, identifier, expected_type),
         ast::Expression::NumberLiteral(_) => {
             if expected_type != Type::Integer {
-                panic!("Expected {:?}, instead got {:?}", expected_type, Type::Integer);
+                type_error!("Expected {:?}, instead got {:?}", expected_type, Type::Integer);
             }
+
+            Ok(())
         },
         ast::Expression::BooleanLiteral(_) => {
             if expected_type != Type::Boolean {
-                panic!("Expected {:?}, instead got {:?}", expected_type, Type::Boolean);
+                type_error!("Expected {:?}, instead got {:?}", expected_type, Type::Boolean);
             }
+
+            Ok(())
         },
         ast::Expression::StringLiteral(_) => {
             if expected_type != Type::String {
-                panic!("Expected {:?}, instead got {:?}", expected_type, Type::String);
+                type_error!("Expected {:?}, instead got {:?}", expected_type, Type::String);
+            }
+
+            Ok(())
+        },
+        ast::Expression::Function(function) => {
+            let function_type = function_type(function);
+            if expected_type != function_type {
+                type_error!("Expected {}, instead got {}", expected_type, function_type);
             }
+
+            check_function(symbol_table, function)
         },
-        ast::Expression::Function(function) => check_function(symbol_table, function),
         ast::Expression::Prefix(expression) => check_prefix_expession(symbol_table, expression, expected_type),
         ast::Expression::Infix(expression) => check_infix_expession(symbol_table, expression, expected_type),
         ast::Expression::Assign(expression) => check_assignment_expression(symbol_table, expression, expected_type),
@@ -213,25 +570,87 @@ fn check_expression(symbol_table: &mut SymbolTable, expression: &ast::Expression
         ast::Expression::Call(expression) => check_call_expression(symbol_table, expression, expected_type),
         ast::Expression::Return(expression) => check_return_expression(symbol_table, expression),
         ast::Expression::Index(expression) => check_index_expression(symbol_table, expression, expected_type),
-        _ => todo!()
+        ast::Expression::Struct(expression) => check_struct_expression(symbol_table, expression, expected_type),
+        ast::Expression::Field(expression) => check_field_expression(symbol_table, expression, expected_type),
+        ast::Expression::FieldAssign(expression) => check_field_assignment_expression(symbol_table, expression, expected_type),
+        _ => type_error!("Unsupported expression")
     }
 }
 
-fn check_identifier(symbol_table: &mut SymbolTable, identifier: &ast::Identifier, expected_type: Type) {
-    let variable_type = synthesize_identifier(symbol_table, identifier);
+// Functions don't capture the variables and functions declared in the functions enclosing them
+fn check_capture(symbol_table: &SymbolTable, identifier: &ast::Identifier) -> Result<(), TypeError> {
+    if let Some((symbol, BindingKind::Upvalue)) = symbol_table.resolve(&identifier.value) {
+        let declaration = match symbol {
+            Symbol::Variable(variable) => (variable.line, variable.column),
+            Symbol::Function(function) => (function.line, function.column),
+        };
 
-    if expected_type != variable_type {
-        panic!("Expected {:?}, instead got {:?}", expected_type, variable_type);
+        return Err(TypeError::at_use(
+            format!("Cannot use '{}' of an enclosing function, functions don't capture it", identifier.value),
+            identifier,
+            declaration,
+        ))
     }
+
+    Ok(())
 }
 
-fn check_function(symbol_table: &mut SymbolTable, function: &ast::Function) {
+fn check_identifier(symbol_table: &mut SymbolTable, identifier: &ast::Identifier, expected_type: Type) -> Result<(), TypeError> {
+    check_capture(symbol_table, identifier)?;
+
+    let variable = match symbol_table.get(&identifier.value) {
+        Some(Symbol::Variable(variable)) => variable.clone(),
+        Some(Symbol::Function(function)) => {
+            let function = function.clone();
+            symbol_table.bind(identifier);
+
+            let function_type = Type::Function(function.parameters, Box::new(function.return_type));
+            if expected_type != function_type {
+                return Err(TypeError::at_use(
+                    format!("Expected {expected_type}, instead got {function_type}"),
+                    identifier,
+                    (function.line, function.column),
+                ))
+            }
 
-    let body = match function.body.as_ref() {
-        ast::Expression::Block(block) => block,
-        _ => todo!("Function body must be a BlockExpression."),
+            return Ok(())
+        },
+        None => type_error!("Variable not found"),
     };
 
+    symbol_table.bind(identifier);
+    let declaration = (variable.line, variable.column);
+
+    if !variable.assigned {
+        return Err(TypeError::at_use(
+            format!("'{}' is read before being assigned", identifier.value),
+            identifier,
+            declaration,
+        ))
+    }
+
+    if expected_type != variable.variable_type {
+        return Err(TypeError::at_use(
+            format!("Expected {:?}, instead got {:?}", expected_type, variable.variable_type),
+            identifier,
+            declaration,
+        ))
+    }
+
+    Ok(())
+}
+
+fn check_function(symbol_table: &mut SymbolTable, function: &ast::Function) -> Result<(), TypeError> {
+    check_type(symbol_table, &function_type(function))?;
+
+    symbol_table.model.set_signature(function, FunctionSignature {
+        name: function.name().to_string(),
+        parameters: function.parameters.iter().map(|parameter| parameter.annotation.clone()).collect(),
+        return_type: function.annotation.clone(),
+    });
+
+    // The body runs when the function is called, its assignments don't count after the declaration
+    let unassigned = symbol_table.unassigned();
     symbol_table.enter_function_scope(function.annotation.clone());
 
     for parameter in &function.parameters {
@@ -240,346 +659,582 @@ fn check_function(symbol_table: &mut SymbolTable, function: &ast::Function) {
                 VariableSymbol {
                     name: parameter.identifier.value.clone(),
                     variable_type: parameter.annotation.clone(),
+                    parameter: true,
+                    assigned: true,
+                    line: parameter.identifier.node.token.line,
+                    column: parameter.identifier.node.token.column,
                 }
             )
         );
+        symbol_table.bind(&parameter.identifier);
     }
 
+    match function.body.as_ref() {
+        ast::Expression::Block(body) => check_function_body(symbol_table, function, body)?,
+        // The expression after an arrow is the returned value
+        body => {
+            let body_type = match &function.annotation {
+                Type::Void => synthesize_expression(symbol_table, body)?,
+                annotation => annotation.clone(),
+            };
+
+            check_expression(symbol_table, body, body_type)?;
+        },
+    }
+
+    symbol_table.exit_scope();
+    symbol_table.set_unassigned(&unassigned);
+    Ok(())
+}
+
+fn check_function_body(symbol_table: &mut SymbolTable, function: &ast::Function, body: &ast::BlockExpression) -> Result<(), TypeError> {
     // We first declare functions so their can be used before their declaration
+    declare_scope_structs(symbol_table, &body.statements);
     declare_scope_functions(symbol_table, &body.statements);
 
     for statement in &body.statements {
-        check_statement(symbol_table, statement);
+        check_statement(symbol_table, statement)?;
     }
 
     if function.annotation != Type::Void {
-        match &body.statements[body.statements.len() - 1] {
-            ast::Statement::Let(_) => {
-                panic!("Expected {:?}, instead got {:?}", function.annotation, Type::Void);
+        match body.statements.last() {
+            Some(ast::Statement::Expression(expression)) => {
+                check_expression(symbol_table, &expression.expression, function.annotation.clone())?;
             },
-            ast::Statement::Expression(expression) => {
-                check_expression(symbol_table, &expression.expression, function.annotation.clone());
-            }
+            _ => type_error!("Expected {:?}, instead got {:?}", function.annotation, Type::Void),
         }
     }
 
-    symbol_table.exit_scope();
+    set_block_type(symbol_table, &function.body);
+    Ok(())
 }
 
-fn check_prefix_expession(symbol_table: &mut SymbolTable, expression: &ast::PrefixExpression, expected_type: Type) {
+fn check_prefix_expession(symbol_table: &mut SymbolTable, expression: &ast::PrefixExpression, expected_type: Type) -> Result<(), TypeError> {
     match expression.operator.as_str() {
         "!" => {
-            check_expression(symbol_table, &expression.expression, Type::Boolean);
+            check_expression(symbol_table, &expression.expression, Type::Boolean)?;
 
             if expected_type != Type::Boolean {
-                panic!("Type error: Expected type {:?}, got {:?} instead.", expected_type, Type::Boolean)
+                type_error!("Type error: Expected type {:?}, got {:?} instead.", expected_type, Type::Boolean)
             }
         },
         "-" => {
-            check_expression(symbol_table, &expression.expression, Type::Integer);
+            check_expression(symbol_table, &expression.expression, Type::Integer)?;
 
             if expected_type != Type::Integer {
-                panic!("Type error: Expected type {:?}, got {:?} instead.", expected_type, Type::Integer)
+                type_error!("Type error: Expected type {:?}, got {:?} instead.", expected_type, Type::Integer)
             }
         },
-        operator => panic!("Invalid operator {:?} found", operator),
+        operator => type_error!("Invalid operator {:?} found", operator),
     }
+
+    Ok(())
 }
 
-fn check_infix_expession(symbol_table: &SymbolTable, expression: &ast::InfixExpression, expected_type: Type) {
-    let left_type = synthesize_expression(symbol_table, &expression.left_expression);
-    let right_type = synthesize_expression(symbol_table, &expression.right_expression);
+fn check_infix_expession(symbol_table: &mut SymbolTable, expression: &ast::InfixExpression, expected_type: Type) -> Result<(), TypeError> {
+    let left_type = synthesize_expression(symbol_table, &expression.left_expression)?;
+    let right_type = synthesize_expression(symbol_table, &expression.right_expression)?;
+
+    check_expression(symbol_table, &expression.left_expression, left_type.clone())?;
+    check_expression(symbol_table, &expression.right_expression, right_type.clone())?;
 
     if left_type != right_type {
-        panic!("Type mismatch in infix expression: {:?} != {:?}", left_type, right_type);
+        type_error!("Type mismatch in infix expression: {:?} != {:?}", left_type, right_type);
     }
 
     match expression.operator.as_str() {
         "+" | "-" | "/" | "*" => {
             if left_type != Type::Integer {
-                panic!("Type error: Expected type {:?}, got {:?} instead.", Type::Integer, left_type)
+                type_error!("Type error: Expected type {:?}, got {:?} instead.", Type::Integer, left_type)
             }
 
             if expected_type != Type::Integer {
-                panic!("Expected {:?}, instead got {:?}", expected_type, Type::Integer);
+                type_error!("Expected {:?}, instead got {:?}", expected_type, Type::Integer);
             }
         },
         ">" | "<" => {
             if left_type != Type::Integer {
-                panic!("Type error: Expected type {:?}, got {:?} instead.", Type::Integer, left_type)
+                type_error!("Type error: Expected type {:?}, got {:?} instead.", Type::Integer, left_type)
             }
             
             if expected_type != Type::Boolean {
-                panic!("Expected {:?}, instead got {:?}", expected_type, Type::Boolean);
+                type_error!("Expected {:?}, instead got {:?}", expected_type, Type::Boolean);
             }
         },
         "==" | "!=" | "&&" | "||" => {
             if expected_type != Type::Boolean {
-                panic!("Expected {:?}, instead got {:?}", expected_type, Type::Boolean);
+                type_error!("Expected {:?}, instead got {:?}", expected_type, Type::Boolean);
             }
         },
-        _ => panic!("Operator not supported.")
+        _ => type_error!("Operator not supported.")
     }
+
+    Ok(())
 }
 
-fn check_assignment_expression(symbol_table: &mut SymbolTable, expression: &ast::AssignmentExpression, expected_type: Type) {
+fn check_assignment_expression(symbol_table: &mut SymbolTable, expression: &ast::AssignmentExpression, expected_type: Type) -> Result<(), TypeError> {
     if expected_type != Type::Void {
-        panic!("Type error: Expected type {:?}, got {:?} instead.", expected_type, Type::Void)
+        type_error!("Type error: Expected type {:?}, got {:?} instead.", expected_type, Type::Void)
     }
     
-    let variable_type = synthesize_identifier(symbol_table, &expression.identifier);
-    check_expression(symbol_table, &expression.expression, variable_type);
+    check_capture(symbol_table, &expression.identifier)?;
+
+    let variable = match symbol_table.get(&expression.identifier.value) {
+        Some(Symbol::Variable(variable)) => variable.clone(),
+        Some(Symbol::Function(function)) => {
+            let declaration = (function.line, function.column);
+            return Err(TypeError::at_use(
+                format!("Cannot assign to the function '{}'", expression.identifier.value),
+                &expression.identifier,
+                declaration,
+            ))
+        },
+        None => type_error!("Variable not found"),
+    };
+
+    symbol_table.bind(&expression.identifier);
+
+    let assigned_type = synthesize_expression(symbol_table, &expression.expression)?;
+    if assigned_type != variable.variable_type && assigned_type != Type::None {
+        return Err(TypeError::at_use(
+            format!("Cannot assign {} to '{}' of type {}", assigned_type, variable.name, variable.variable_type),
+            &expression.identifier,
+            (variable.line, variable.column),
+        ))
+    }
+
+    check_expression(symbol_table, &expression.expression, variable.variable_type)?;
+    symbol_table.assign(&expression.identifier.value);
+
+    Ok(())
 }
 
-fn check_array_expression(symbol_table: &mut SymbolTable, expression: &ast::ArrayExpression, expected_type: Type) {
+fn check_array_expression(symbol_table: &mut SymbolTable, expression: &ast::ArrayExpression, expected_type: Type) -> Result<(), TypeError> {
     let array_type = match expected_type {
         Type::Array(array_type) => array_type,
-        _ => panic!("Type error: Expected type {:?}, got array instead.", expected_type),
+        _ => type_error!("Type error: Expected type {:?}, got array instead.", expected_type),
     };
 
     for element in &expression.elements {
         // TODO: "Type" should be passed as reference
-        check_expression(symbol_table, element, *array_type.clone());
+        check_expression(symbol_table, element, *array_type.clone())?;
     }
+
+    Ok(())
 }
 
-fn check_block_expression(symbol_table: &mut SymbolTable, expression: &ast::BlockExpression, expected_type: Type) {
+// Blocks have their own scope, their variables are locals
+fn check_block_expression(symbol_table: &mut SymbolTable, expression: &ast::BlockExpression, expected_type: Type) -> Result<(), TypeError> {
+    symbol_table.enter_scope();
+    let result = check_block_statements(symbol_table, expression, expected_type);
+    symbol_table.exit_scope();
 
+    result
+}
+
+fn check_block_statements(symbol_table: &mut SymbolTable, expression: &ast::BlockExpression, expected_type: Type) -> Result<(), TypeError> {
     for statement in &expression.statements {
-        check_statement(symbol_table, statement);
+        check_statement(symbol_table, statement)?;
     };
 
-    match &expression.statements[expression.statements.len() - 1] {
-        ast::Statement::Let(_) => {
+    // Empty blocks and blocks ending with a declaration are void
+    match expression.statements.last() {
+        None | Some(ast::Statement::Let(_) | ast::Statement::Struct(_)) => {
             if expected_type != Type::Void {
-                panic!("Expected {:?}, instead got {:?}", expected_type, Type::Void);
+                type_error!("Expected {:?}, instead got {:?}", expected_type, Type::Void);
             }
+
+            Ok(())
         },
-        ast::Statement::Expression(expression) => {
-            check_expression(symbol_table, &expression.expression, expected_type);
+        Some(ast::Statement::Expression(expression)) => {
+            check_expression(symbol_table, &expression.expression, expected_type)
         }
     }
 }
 
-fn check_if_expression(symbol_table: &mut SymbolTable, expression: &ast::IfExpression, expected_type: Type) {
-    check_expression(symbol_table, &expression.condition, Type::Boolean);
+fn check_if_expression(symbol_table: &mut SymbolTable, expression: &ast::IfExpression, expected_type: Type) -> Result<(), TypeError> {
+    check_expression(symbol_table, &expression.condition, Type::Boolean)?;
+    let unassigned = symbol_table.unassigned();
 
-    symbol_table.enter_scope();
-    check_expression(symbol_table, &expression.consequence, expected_type.clone());
-    symbol_table.exit_scope();
-    
-    if let Some(alternative) = &expression.alternative {
-        symbol_table.enter_scope();
-        check_expression(symbol_table, &alternative, expected_type);
-        symbol_table.exit_scope();
+    check_expression(symbol_table, &expression.consequence, expected_type.clone())?;
+
+    // Variables are assigned after the if when every branch reaching its end assigns them
+    let mut unassigned_after = match diverges(symbol_table, &expression.consequence) {
+        true => HashSet::new(),
+        false => symbol_table.unassigned(),
     };
+    symbol_table.set_unassigned(&unassigned);
+
+    match &expression.alternative {
+        Some(alternative) => {
+            check_expression(symbol_table, alternative, expected_type)?;
+
+            if !diverges(symbol_table, alternative) {
+                unassigned_after.extend(symbol_table.unassigned());
+            }
+        },
+        None => unassigned_after = unassigned,
+    }
+
+    symbol_table.set_unassigned(&unassigned_after);
+    Ok(())
 }
 
-fn check_while_expression(symbol_table: &mut SymbolTable, expression: &ast::WhileExpression, _: Type) {
-    check_expression(symbol_table, &expression.condition, Type::Boolean);
+fn check_while_expression(symbol_table: &mut SymbolTable, expression: &ast::WhileExpression, _: Type) -> Result<(), TypeError> {
+    check_expression(symbol_table, &expression.condition, Type::Boolean)?;
 
     match &expression.iteration.as_ref() {
-        ast::Expression::Block(expression) => {
+        ast::Expression::Block(block) => {
+            // The loop may not run, its assignments don't count after it
+            let unassigned = symbol_table.unassigned();
+
             symbol_table.enter_scope();
-            for statement in &expression.statements {
-                check_statement(symbol_table, statement);
+            for statement in &block.statements {
+                check_statement(symbol_table, statement)?;
             };
+            set_block_type(symbol_table, &expression.iteration);
             symbol_table.exit_scope();
+
+            symbol_table.set_unassigned(&unassigned);
         },
-        _ => panic!("Expected BlockExpression."), // TODO: This should be put in semantic analysis
+        _ => type_error!("Expected BlockExpression."), // TODO: This should be put in semantic analysis
     }
+
+    Ok(())
 }
 
-fn check_break_expression(_: &mut SymbolTable, _: &ast::BreakExpression, _: Type) {
+fn check_break_expression(_: &mut SymbolTable, _: &ast::BreakExpression, _: Type) -> Result<(), TypeError> {
     // TODO: This needs semantic analysis, to check if we're in a loop
+    Ok(())
 }
 
-fn check_call_expression(symbol_table: &mut SymbolTable, expression: &ast::CallExpression, expected_type: Type) {
+// Anything typed as a function can be called, not only declared functions
+fn check_call_expression(symbol_table: &mut SymbolTable, expression: &ast::CallExpression, expected_type: Type) -> Result<(), TypeError> {
+    let callee_type = synthesize_expression(symbol_table, &expression.identifier)?;
+    check_expression(symbol_table, &expression.identifier, callee_type.clone())?;
 
-    let get_symbol_result = match expression.identifier.as_ref() {
-        ast::Expression::Identifier(identifier) => symbol_table.get(&identifier.value).cloned(),
-        _ => todo!(),
+    let (parameters, return_type) = match callee_type {
+        Type::Function(parameters, return_type) => (parameters, *return_type),
+        callee_type => type_error!("Expected a function, instead got {}", callee_type),
     };
 
-    let function_symbol = match get_symbol_result {
-        Some(symbol) => {
-            match symbol {
-                Symbol::Function(function) => function,
-                _ => panic!("Expected function"),
-            }
-        },
-        None => panic!("Function not found"),
-    };
-
-    if function_symbol.return_type != expected_type {
-        panic!("Expected {:?}, instead got {:?}", expected_type, function_symbol.return_type);
+    if return_type != expected_type {
+        type_error!("Expected {:?}, instead got {:?}", expected_type, return_type);
     }
 
-    if expression.arguments.len() != function_symbol.parameters.len() {
-        panic!("Expected {:?} arguments, instead got {:?}", function_symbol.parameters.len(), expression.arguments.len());
+    if expression.arguments.len() != parameters.len() {
+        type_error!("Expected {:?} arguments, instead got {:?}", parameters.len(), expression.arguments.len());
     }
 
-    for index in 0..expression.arguments.len() {
-        check_expression(
-            symbol_table, 
-            expression.arguments[index].as_ref(), 
-            function_symbol.parameters[index].clone() // TODO: reference instead of clone
-        );
+    for (argument, parameter) in expression.arguments.iter().zip(parameters) {
+        check_expression(symbol_table, argument, parameter)?;
     }
+
+    Ok(())
 }
 
-fn check_return_expression(symbol_table: &SymbolTable, expression: &ast::ReturnExpression) {
-    let current_scope = symbol_table.get_current_scope();
+fn check_return_expression(symbol_table: &mut SymbolTable, expression: &ast::ReturnExpression) -> Result<(), TypeError> {
+    let expected_type = symbol_table.get_current_scope().return_type.clone();
 
-    let return_type = synthesize_expression(symbol_table, &expression.expression);
-    if current_scope.return_type != return_type {
-        panic!("Type error: Expected type {:?}, got {:?} instead.", current_scope.return_type, return_type);
+    let return_type = synthesize_expression(symbol_table, &expression.expression)?;
+    if expected_type != return_type {
+        type_error!("Type error: Expected type {:?}, got {:?} instead.", expected_type, return_type);
     }
+
+    check_expression(symbol_table, &expression.expression, return_type)
 }
 
-fn check_index_expression(symbol_table: &mut SymbolTable, expression: &ast::IndexExpression, expected_type: Type) {
-    check_expression(symbol_table, &expression.index, Type::Integer);
-    let indexed_type = synthesize_expression(symbol_table, &expression.indexed);
+fn check_index_expression(symbol_table: &mut SymbolTable, expression: &ast::IndexExpression, expected_type: Type) -> Result<(), TypeError> {
+    check_expression(symbol_table, &expression.index, Type::Integer)?;
+    let indexed_type = synthesize_expression(symbol_table, &expression.indexed)?;
+    check_expression(symbol_table, &expression.indexed, indexed_type.clone())?;
 
     match indexed_type {
         Type::Array(array_type) => {
             if array_type.as_ref() != &expected_type {
-                panic!("Type error: Expected type {:?}, got {:?} instead.", expected_type, array_type);
+                type_error!("Type error: Expected type {:?}, got {:?} instead.", expected_type, array_type);
             }
         },
-        actual_type => panic!("Type error: Expected type array, got {:?} instead.", actual_type),
+        actual_type => type_error!("Type error: Expected type array, got {:?} instead.", actual_type),
+    }
+
+    Ok(())
+}
+
+// Every field is initialized once, in any order
+fn check_struct_expression(symbol_table: &mut SymbolTable, expression: &ast::StructExpression, expected_type: Type) -> Result<(), TypeError> {
+    let name = &expression.identifier.value;
+    let symbol = match symbol_table.get_struct(name) {
+        Some(symbol) => symbol.clone(),
+        None => type_error!("Unknown struct '{name}'"),
+    };
+
+    let struct_type = Type::Struct(name.clone());
+    if expected_type != struct_type {
+        type_error!("Expected {expected_type}, instead got {struct_type}");
+    }
+
+    for (index, field) in expression.fields.iter().enumerate() {
+        let field_name = &field.identifier.value;
+
+        let slot = match symbol.fields.iter().position(|(name, _)| name == field_name) {
+            Some(slot) => slot,
+            None => type_error!("Struct '{name}' has no field '{field_name}'"),
+        };
+
+        if expression.fields[..index].iter().any(|other| &other.identifier.value == field_name) {
+            type_error!("Field '{field_name}' is initialized twice");
+        }
+
+        check_expression(symbol_table, &field.expression, symbol.fields[slot].1.clone())?;
+        symbol_table.model.set_field_slot(&field.identifier, slot);
+    }
+
+    for (field_name, _) in &symbol.fields {
+        if !expression.fields.iter().any(|field| &field.identifier.value == field_name) {
+            type_error!("Missing field '{field_name}' in '{name}' literal");
+        }
+    }
+
+    Ok(())
+}
+
+fn check_field_expression(symbol_table: &mut SymbolTable, expression: &ast::FieldExpression, expected_type: Type) -> Result<(), TypeError> {
+    let (slot, field_type) = check_field(symbol_table, expression)?;
+
+    if expected_type != field_type {
+        type_error!("Expected {expected_type}, instead got {field_type}");
+    }
+
+    symbol_table.model.set_field_slot(&expression.field, slot);
+    Ok(())
+}
+
+// Structs are values, a field is assigned through the variable holding its struct
+fn check_field_assignment_expression(
+    symbol_table: &mut SymbolTable,
+    expression: &ast::FieldAssignmentExpression,
+    expected_type: Type,
+) -> Result<(), TypeError> {
+    if expected_type != Type::Void {
+        type_error!("Type error: Expected type {:?}, got {:?} instead.", expected_type, Type::Void)
+    }
+
+    let mut root = expression.target.expression.as_ref();
+    while let ast::Expression::Field(field) = root {
+        root = field.expression.as_ref();
+    }
+
+    let root = match root {
+        ast::Expression::Identifier(identifier) => identifier,
+        _ => type_error!("Cannot assign to a field of a temporary value, only of a variable"),
+    };
+
+    if let Some(Symbol::Function(function)) = symbol_table.get(&root.value) {
+        let declaration = (function.line, function.column);
+        return Err(TypeError::at_use(format!("Cannot assign to the function '{}'", root.value), root, declaration))
+    }
+
+    let (slot, field_type) = check_field(symbol_table, &expression.target)?;
+    symbol_table.model.set_field_slot(&expression.target.field, slot);
+
+    let assigned_type = synthesize_expression(symbol_table, &expression.expression)?;
+    if assigned_type != field_type && assigned_type != Type::None {
+        type_error!("Cannot assign {assigned_type} to the field '{}' of type {field_type}", expression.target.field.value);
+    }
+
+    check_expression(symbol_table, &expression.expression, field_type)
+}
+
+// Slot and type of the field, after checking the struct it's read from
+fn check_field(symbol_table: &mut SymbolTable, expression: &ast::FieldExpression) -> Result<(usize, Type), TypeError> {
+    let struct_type = synthesize_expression(symbol_table, &expression.expression)?;
+    check_expression(symbol_table, &expression.expression, struct_type.clone())?;
+
+    find_field(symbol_table, &struct_type, &expression.field.value)
+}
+
+fn find_field(symbol_table: &SymbolTable, struct_type: &Type, field_name: &str) -> Result<(usize, Type), TypeError> {
+    let symbol = match struct_type {
+        Type::Struct(name) => match symbol_table.get_struct(name) {
+            Some(symbol) => symbol,
+            None => type_error!("Unknown type '{name}'"),
+        },
+        struct_type => type_error!("Expected a struct, instead got {struct_type}"),
+    };
+
+    match symbol.fields.iter().position(|(name, _)| name == field_name) {
+        Some(slot) => Ok((slot, symbol.fields[slot].1.clone())),
+        None => type_error!("Struct '{}' has no field '{field_name}'", symbol.name),
+    }
+}
+
+// Branches ending with a return or a break never reach the code after them
+fn diverges(symbol_table: &mut SymbolTable, expression: &ast::Expression) -> bool {
+    matches!(synthesize_expression(symbol_table, expression), Ok(Type::None))
+}
+
+fn function_type(function: &ast::Function) -> Type {
+    let parameters = function.parameters.iter().map(|parameter| parameter.annotation.clone()).collect();
+    Type::Function(parameters, Box::new(function.annotation.clone()))
+}
+
+// Blocks checked statement by statement, such as bodies, still get a type in the model
+fn set_block_type(symbol_table: &mut SymbolTable, expression: &ast::Expression) {
+    if let ast::Expression::Block(block) = expression {
+        let block_type = checked_block_type(symbol_table, block);
+        symbol_table.model.set_type(expression, block_type);
+    }
+}
+
+// Type of a block whose statements were checked, empty blocks and blocks ending with a declaration are void
+fn checked_block_type(symbol_table: &SymbolTable, block: &ast::BlockExpression) -> Type {
+    match block.statements.last() {
+        Some(ast::Statement::Expression(statement)) => {
+            symbol_table.model.type_of(&statement.expression).cloned().unwrap_or(Type::Void)
+        },
+        _ => Type::Void,
     }
 }
 
 // Synthesizing
 
-fn synthesize_expression(symbol_table: &SymbolTable, expression: &ast::Expression) -> Type {
+fn synthesize_expression(symbol_table: &mut SymbolTable, expression: &ast::Expression) -> Result<Type, TypeError> {
     match expression {
         ast::Expression::Identifier(identifier) => synthesize_identifier(symbol_table, identifier),
-        ast::Expression::NumberLiteral(_) => Type::Integer,
-        ast::Expression::BooleanLiteral(_) => Type::Boolean,
-        ast::Expression::StringLiteral(_) => Type::String,
-        ast::Expression::Function(_) => Type::Void,
+        ast::Expression::NumberLiteral(_) => Ok(Type::Integer),
+        ast::Expression::BooleanLiteral(_) => Ok(Type::Boolean),
+        ast::Expression::StringLiteral(_) => Ok(Type::String),
+        ast::Expression::Function(function) => Ok(function_type(function)),
         ast::Expression::Prefix(expression) => synthesize_prefix_expression(expression),
         ast::Expression::Infix(expression) => synthesize_infix_expression(expression),
         ast::Expression::Assign(expression) => synthesize_assignment_expression(expression),
         ast::Expression::Array(expression) => synthesize_array_expression(symbol_table, expression),
-        ast::Expression::Block(expression) => synthesize_block_expression(symbol_table, expression),
+        // Blocks are only synthesized once, checking them again keeps their type
+        ast::Expression::Block(block) => match symbol_table.model.type_of(expression) {
+            Some(block_type) => Ok(block_type.clone()),
+            None => {
+                let block_type = synthesize_block_expression(symbol_table, block)?;
+                symbol_table.model.set_type(expression, block_type.clone());
+                Ok(block_type)
+            },
+        },
         ast::Expression::If(expression) => synthesize_if_expression(symbol_table, expression),
-        ast::Expression::While(_) => Type::Void,
+        ast::Expression::While(_) => Ok(Type::Void),
         ast::Expression::Break(_) => {
-            return Type::None; // Just as return, it doesn't hold the value
+            return Ok(Type::None); // Just as return, it doesn't hold the value
         },
         ast::Expression::Call(expression) => synthesize_call_expression(symbol_table, expression),
         ast::Expression::Return(_) => {
-            return Type::None;
+            return Ok(Type::None);
             // TODO: synthesize_expression must return an optional type
             // synthesize_expression(symbol_table, &expression.expression)
         },
         ast::Expression::Index(expression) => synthesize_index_expression(symbol_table, expression),
-        _ => todo!(),
+        ast::Expression::Struct(expression) => Ok(Type::Struct(expression.identifier.value.clone())),
+        ast::Expression::Field(expression) => {
+            let struct_type = synthesize_expression(symbol_table, &expression.expression)?;
+            find_field(symbol_table, &struct_type, &expression.field.value).map(|(_, field_type)| field_type)
+        },
+        ast::Expression::FieldAssign(_) => Ok(Type::Void),
+        _ => type_error!("Unsupported expression"),
     }
 }
 
-fn synthesize_identifier(symbol_table: &SymbolTable, identifier: &ast::Identifier) -> Type {
-    let variable_symbol = match symbol_table.get(&identifier.value) {
-        Some(symbol) => {
-            match symbol {
-                Symbol::Variable(variable) => variable,
-                _ => panic!("Expected variable"),
-            }
-        },
-        None => panic!("Variable not found"),
-    };
+fn synthesize_identifier(symbol_table: &mut SymbolTable, identifier: &ast::Identifier) -> Result<Type, TypeError> {
+    check_capture(symbol_table, identifier)?;
 
-    return variable_symbol.variable_type.clone()
+    match symbol_table.get(&identifier.value) {
+        Some(Symbol::Variable(variable)) => Ok(variable.variable_type.clone()),
+        Some(Symbol::Function(function)) => {
+            Ok(Type::Function(function.parameters.clone(), Box::new(function.return_type.clone())))
+        },
+        None => type_error!("Variable not found"),
+    }
 }
 
-fn synthesize_prefix_expression(expression: &ast::PrefixExpression) -> Type {
+fn synthesize_prefix_expression(expression: &ast::PrefixExpression) -> Result<Type, TypeError> {
     match expression.operator.as_str() {
-        "-" => Type::Integer,
-        "!" => Type::Boolean,
-        operator => panic!("Invalid operator {:?} found", operator),
+        "-" => Ok(Type::Integer),
+        "!" => Ok(Type::Boolean),
+        operator => type_error!("Invalid operator {:?} found", operator),
     }
 }
 
-fn synthesize_infix_expression(expression: &ast::InfixExpression) -> Type {
+fn synthesize_infix_expression(expression: &ast::InfixExpression) -> Result<Type, TypeError> {
     match expression.operator.as_str() {
-        "+" | "-" | "*" | "/" => Type::Integer,
-        "==" | "!=" | ">" | "<" => Type::Boolean,
-        "&&" | "||" => Type::Boolean,
-        operator => panic!("Invalid operator {:?} found", operator),
+        "+" | "-" | "*" | "/" => Ok(Type::Integer),
+        "==" | "!=" | ">" | "<" => Ok(Type::Boolean),
+        "&&" | "||" => Ok(Type::Boolean),
+        operator => type_error!("Invalid operator {:?} found", operator),
     }
 }
 
-fn synthesize_assignment_expression(_: &ast::AssignmentExpression) -> Type {
-    return Type::Void // TODO: Assignment expressions may return the assigned value
+fn synthesize_assignment_expression(_: &ast::AssignmentExpression) -> Result<Type, TypeError> {
+    return Ok(Type::Void) // TODO: Assignment expressions may return the assigned value
 }
 
 // The type of the array should be determined on the first element
-fn synthesize_array_expression(symbol_table: &SymbolTable, expression: &ast::ArrayExpression) -> Type {
-    let array_type = synthesize_expression(symbol_table, &expression.elements[0]);
-    return Type::Array(Box::new(array_type))
+fn synthesize_array_expression(symbol_table: &mut SymbolTable, expression: &ast::ArrayExpression) -> Result<Type, TypeError> {
+    if expression.elements.is_empty() {
+        type_error!("Cannot infer the type of an empty array, it requires an annotation");
+    }
+
+    let array_type = synthesize_expression(symbol_table, &expression.elements[0])?;
+    return Ok(Type::Array(Box::new(array_type)))
 }
 
-fn synthesize_block_expression(symbol_table: &SymbolTable, expression: &ast::BlockExpression) -> Type {
-    match &expression.statements[expression.statements.len() - 1] {
-        ast::Statement::Let(_) => Type::Void,
-        ast::Statement::Expression(expression) => {
-            synthesize_expression(symbol_table, &expression.expression)
-        }
-    }
+fn synthesize_block_expression(symbol_table: &mut SymbolTable, expression: &ast::BlockExpression) -> Result<Type, TypeError> {
+    // The statements are checked in order in the scope of the block, though its assignments
+    // only count once the block itself is checked
+    let unassigned = symbol_table.unassigned();
+    symbol_table.enter_scope();
+
+    let result = expression.statements.iter().try_for_each(|statement| check_statement(symbol_table, statement));
+    let block_type = result.map(|_| checked_block_type(symbol_table, expression));
+
+    symbol_table.exit_scope();
+    symbol_table.set_unassigned(&unassigned);
+    block_type
 }
 
-fn synthesize_if_expression(symbol_table: &SymbolTable, expression: &ast::IfExpression) -> Type {
-    let consequence_type = synthesize_expression(symbol_table, &expression.consequence);
+fn synthesize_if_expression(symbol_table: &mut SymbolTable, expression: &ast::IfExpression) -> Result<Type, TypeError> {
+    let consequence_type = synthesize_expression(symbol_table, &expression.consequence)?;
     
     match &expression.alternative {
         Some(alternative) => {
-            let alternative_type = synthesize_expression(symbol_table, alternative.as_ref());
+            let alternative_type = synthesize_expression(symbol_table, alternative.as_ref())?;
 
             if consequence_type == Type::None {
-                return alternative_type;
+                return Ok(alternative_type);
             }
 
             if alternative_type == Type::None {
-                return consequence_type;
+                return Ok(consequence_type);
             }
 
             if consequence_type != alternative_type {
-                panic!("Type mismatch in if expression: {:?} != {:?}", consequence_type, alternative_type);
+                type_error!("Type mismatch in if expression: {:?} != {:?}", consequence_type, alternative_type);
             }
 
-            consequence_type
+            Ok(consequence_type)
         },
-        None => consequence_type,
+        None => Ok(consequence_type),
     }
 }
 
-fn synthesize_call_expression(symbol_table: &SymbolTable, expression: &ast::CallExpression) -> Type {
-    let get_symbol_result = match expression.identifier.as_ref() {
-        ast::Expression::Identifier(identifier) => symbol_table.get(&identifier.value),
-        _ => todo!(),
-    };
-
-    let function_symbol = match get_symbol_result {
-        Some(symbol) => {
-            match symbol {
-                Symbol::Function(function) => function,
-                _ => panic!("Expected function"),
-            }
-        },
-        None => panic!("Function not found"),
-    };
-
-    return function_symbol.return_type.clone()
+fn synthesize_call_expression(symbol_table: &mut SymbolTable, expression: &ast::CallExpression) -> Result<Type, TypeError> {
+    match synthesize_expression(symbol_table, &expression.identifier)? {
+        Type::Function(_, return_type) => Ok(*return_type),
+        callee_type => type_error!("Expected a function, instead got {}", callee_type),
+    }
 }
 
-fn synthesize_index_expression(symbol_table: &SymbolTable, expression: &ast::IndexExpression) -> Type {
-    let indexed_type = synthesize_expression(symbol_table, &expression.indexed);
+fn synthesize_index_expression(symbol_table: &mut SymbolTable, expression: &ast::IndexExpression) -> Result<Type, TypeError> {
+    let indexed_type = synthesize_expression(symbol_table, &expression.indexed)?;
 
     match indexed_type {
-        Type::Array(array_type) => *array_type,
-        actual_type => panic!("Type error: Expected type array, got {:?} instead.", actual_type),
+        Type::Array(array_type) => Ok(*array_type),
+        actual_type => type_error!("Type error: Expected type array, got {:?} instead.", actual_type),
     }
 }
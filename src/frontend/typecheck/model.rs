@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use super::{types::Type, FunctionSignature};
+use crate::frontend::ast;
+
+// Where the value of a name lives once compiled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BindingKind {
+    Global,
+    Local,
+    Parameter,
+    Upvalue, // Local or parameter of an enclosing function
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    pub kind: BindingKind,
+    pub binding_type: Type,
+    pub line: usize, // Declaration, 0 for names provided by the host
+    pub column: usize,
+}
+
+// What the typechecker learned about a file. Nodes are identified by address,
+// so the model describes the tree it was built from as long as it isn't modified.
+#[derive(Debug, Default, Clone)]
+pub struct SemanticModel {
+    types: HashMap<usize, Type>,
+    bindings: HashMap<usize, Binding>,
+    signatures: HashMap<usize, FunctionSignature>,
+    slots: HashMap<usize, usize>,
+}
+
+impl SemanticModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn type_of(&self, expression: &ast::Expression) -> Option<&Type> {
+        self.types.get(&address(expression))
+    }
+
+    // Declaration a name refers to, declared names refer to themselves
+    pub fn binding(&self, identifier: &ast::Identifier) -> Option<&Binding> {
+        self.bindings.get(&address(identifier))
+    }
+
+    pub fn signature(&self, function: &ast::Function) -> Option<&FunctionSignature> {
+        self.signatures.get(&address(function))
+    }
+
+    pub fn signatures(&self) -> impl Iterator<Item = &FunctionSignature> {
+        self.signatures.values()
+    }
+
+    // Position in its struct declaration of a field, named in an access, an assignment or a literal
+    pub fn field_slot(&self, field: &ast::Identifier) -> Option<usize> {
+        self.slots.get(&address(field)).copied()
+    }
+
+    // Expressions checked again against an expected type, like the last one of a function, keep their own type
+    pub(super) fn set_type(&mut self, expression: &ast::Expression, expression_type: Type) {
+        self.types.entry(address(expression)).or_insert(expression_type);
+    }
+
+    pub(super) fn set_binding(&mut self, identifier: &ast::Identifier, binding: Binding) {
+        self.bindings.insert(address(identifier), binding);
+    }
+
+    pub(super) fn set_field_slot(&mut self, field: &ast::Identifier, slot: usize) {
+        self.slots.insert(address(field), slot);
+    }
+
+    pub(super) fn set_signature(&mut self, function: &ast::Function, signature: FunctionSignature) {
+        self.signatures.insert(address(function), signature);
+    }
+}
+
+fn address<T>(node: &T) -> usize {
+    node as *const T as usize
+}
@@ -1,20 +1,77 @@
 #[cfg(test)]
 mod tests {
     use crate::frontend::{
+        ast::{self, visit::{walk_expression, Visitor}},
         lexer::Lexer, 
         parser::{
             parse_file, 
             Parser
-        }, typecheck::check_program
+        },
+        typecheck::{
+            check_program,
+            model::{BindingKind, SemanticModel},
+            types::Type,
+            TypeError,
+        },
     };
 
+    fn parse(code: &str) -> ast::File {
+        let mut lexer = Lexer::new(code);
+        let mut parser = Parser::new(&mut lexer);
+
+        let file = parse_file(&mut parser);
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+
+        file
+    }
+
+    fn type_error(code: &str) -> TypeError {
+        check_program(&parse(code)).expect_err(code)
+    }
+
+    type Types = Vec<(String, Option<Type>)>;
+    type Bindings = Vec<(String, Option<BindingKind>, usize)>;
+
+    // Rendered expressions with their type, and identifiers with their binding and its line
+    struct Collector<'a> {
+        model: &'a SemanticModel,
+        types: Types,
+        bindings: Bindings,
+    }
+
+    impl Visitor for Collector<'_> {
+        fn visit_expression(&mut self, expression: &ast::Expression) {
+            self.types.push((expression.to_string(), self.model.type_of(expression).cloned()));
+            walk_expression(self, expression);
+        }
+
+        fn visit_identifier(&mut self, identifier: &ast::Identifier) {
+            let binding = self.model.binding(identifier);
+            self.bindings.push((
+                identifier.value.clone(),
+                binding.map(|binding| binding.kind),
+                binding.map_or(0, |binding| binding.line),
+            ));
+        }
+    }
+
+    fn collect(code: &str) -> (Types, Bindings) {
+        let file = parse(code);
+        let model = check_program(&file).unwrap();
+
+        let mut collector = Collector { model: &model, types: vec![], bindings: vec![] };
+        collector.visit_file(&file);
+
+        (collector.types, collector.bindings)
+    }
+
     fn test_typecheck(code: &str) {
         let mut lexer = Lexer::new(code);
         let mut parser = Parser::new(&mut lexer);
 
         let ast_file = parse_file(&mut parser);
 
-        if parser.errors.len() > 0 {
+        if !parser.errors.is_empty() {
             for error in parser.errors {
                 println!("{error}");
             }
@@ -22,7 +79,7 @@ mod tests {
             panic!("Found parsing errors.");
         }
 
-        check_program(&ast_file);
+        check_program(&ast_file).unwrap();
     }
 
     #[test]
@@ -159,6 +216,26 @@ mod tests {
         test_typecheck(code);
     }
 
+    #[test]
+    fn test_typecheck_empty_blocks() {
+        test_typecheck("if true {}");
+        test_typecheck("{}");
+        test_typecheck("let x: void = if true {} else {};");
+        test_typecheck("fn nothing() -> void {}");
+    }
+
+    #[test]
+    fn test_typecheck_block_declarations() {
+        // The last expression of a block sees the variables declared before it
+        test_typecheck("if true { let z = 2; z; }");
+        test_typecheck("let y = { let z = 2; z * 3; }; let w: int = y;");
+        test_typecheck("let a = if true { let z = 2; z; } else { 3; }; a + 1;");
+
+        // Assignments in a block only count where the block always runs
+        test_typecheck("let x: int; let y = { x = 1; x; }; x + y;");
+        assert!(type_error("let x: int; if true { x = 1; } x;").message.contains("before being assigned"));
+    }
+
     #[test]
     fn test_typecheck_while_expression() {
         let code = "
@@ -239,4 +316,214 @@ mod tests {
         test_typecheck(code);
     }
 
-}
\ No newline at end of file
+    // Semantic model
+
+    #[test]
+    fn test_model_types() {
+        let (types, _) = collect("
+            let array: [int] = [1, 2];
+            fn first(values: [int]) -> int { return values[0] + 1; }
+            first(array) > 2;
+        ");
+
+        for (expression, expression_type) in &types {
+            assert!(expression_type.is_some(), "{expression} has no type");
+        }
+
+        let type_of = |rendered: &str| types.iter().find(|(expression, _)| expression == rendered).and_then(|(_, t)| t.clone());
+        assert_eq!(type_of("[1, 2]"), Some(Type::Array(Box::new(Type::Integer))));
+        assert_eq!(type_of("values[0]"), Some(Type::Integer));
+        assert_eq!(type_of("(first(array) > 2)"), Some(Type::Boolean));
+        assert_eq!(type_of("first"), Some(Type::Function(vec![Type::Array(Box::new(Type::Integer))], Box::new(Type::Integer))));
+        assert_eq!(type_of("(return (values[0] + 1))"), Some(Type::None));
+    }
+
+    #[test]
+    fn test_model_bindings() {
+        let (_, bindings) = collect("
+            let total: int = 0;
+            fn add(a: int) -> int {
+                let b: int = a;
+                fn twice() -> int { return total * 2; }
+                total = b + twice();
+                return total;
+            }
+        ");
+
+        assert_eq!(bindings, [
+            (String::from("total"), Some(BindingKind::Global), 2),
+            (String::from("add"), Some(BindingKind::Global), 3),
+            (String::from("a"), Some(BindingKind::Parameter), 3),
+            (String::from("b"), Some(BindingKind::Local), 4),
+            (String::from("a"), Some(BindingKind::Parameter), 3),
+            (String::from("twice"), Some(BindingKind::Local), 5),
+            (String::from("total"), Some(BindingKind::Global), 2),
+            (String::from("total"), Some(BindingKind::Global), 2),
+            (String::from("b"), Some(BindingKind::Local), 4),
+            (String::from("twice"), Some(BindingKind::Local), 5),
+            (String::from("total"), Some(BindingKind::Global), 2),
+        ]);
+    }
+
+    #[test]
+    fn test_model_block_bindings() {
+        let (_, bindings) = collect("
+            let x = 1;
+            {
+                let y = x;
+                y = y + 1;
+            }
+        ");
+
+        assert_eq!(bindings, [
+            (String::from("x"), Some(BindingKind::Global), 2),
+            (String::from("y"), Some(BindingKind::Local), 4),
+            (String::from("x"), Some(BindingKind::Global), 2),
+            (String::from("y"), Some(BindingKind::Local), 4),
+            (String::from("y"), Some(BindingKind::Local), 4),
+        ]);
+
+        // Block variables don't outlive their block
+        let file = parse("{ let y = 1; } y;");
+        assert!(check_program(&file).is_err());
+    }
+
+    #[test]
+    fn test_model_signatures() {
+        let file = parse("fn add(a: int, b: int) -> int { return a + b; } fn main() { add(1, 2); }");
+        let model = check_program(&file).unwrap();
+
+        let mut signatures: Vec<String> = model.signatures()
+            .map(|signature| format!("{}: {}", signature.name, Type::Function(signature.parameters.clone(), Box::new(signature.return_type.clone()))))
+            .collect();
+        signatures.sort();
+
+        assert_eq!(signatures, ["add: fn(int, int) -> int", "main: fn() -> void"]);
+    }
+
+    // Inference and definite assignment
+
+    #[test]
+    fn test_typecheck_let_inference() {
+        let (types, _) = collect("let x = [1, 2]; let y = x[0] > 1; let z: [bool] = []; x; y; z;");
+        let last: Vec<Option<Type>> = types[types.len() - 3..].iter().map(|(_, t)| t.clone()).collect();
+
+        assert_eq!(last, [
+            Some(Type::Array(Box::new(Type::Integer))),
+            Some(Type::Boolean),
+            Some(Type::Array(Box::new(Type::Boolean))),
+        ]);
+
+        assert!(type_error("let x;").message.contains("requires an annotation"));
+        assert!(type_error("let x = [];").message.contains("empty array"));
+        assert!(type_error("fn f() {} let x = f();").message.contains("void"));
+    }
+
+    #[test]
+    fn test_typecheck_definite_assignment() {
+        test_typecheck("let x: int; x = 1; x + 1;");
+        test_typecheck("let x: int; if true { x = 1; } else { x = 2; } x;");
+        test_typecheck("fn f(c: bool) -> int { let x: int; if c { x = 1; } else { return 0; } return x; }");
+        test_typecheck("fn f() -> int { let x: int; while true { x = 1; break; } x = 2; return x; }");
+
+        for code in [
+            "let x: int; x + 1;",
+            "let x: int; if true { x = 1; } x;",
+            "let x: int; while false { x = 1; } x;",
+            "let x: int; fn f() -> void { x = 1; } f(); x;",
+        ] {
+            assert!(type_error(code).message.contains("read before being assigned"), "{code}");
+        }
+    }
+
+    #[test]
+    fn test_typecheck_errors_point_at_declaration() {
+        let error = type_error("let x: int;\nlet y: int = 2;\ny = x;");
+        assert_eq!((error.line, error.column, error.declaration), (3, 5, Some((1, 5))));
+        assert_eq!(error.to_string(), "Type error on line 3:5: 'x' is read before being assigned (declared on line 1:5)");
+
+        let error = type_error("let flag = true;\n\nflag = 1;");
+        assert_eq!((error.line, error.column, error.declaration), (3, 1, Some((1, 5))));
+        assert_eq!(error.message, "Cannot assign int to 'flag' of type bool");
+    }
+
+    // Function values
+
+    #[test]
+    fn test_typecheck_function_values() {
+        test_typecheck("
+            fn double(x: int) -> int { return x * 2; }
+            fn twice(f: fn(int) -> int, x: int) -> int { return f(f(x)); }
+            let functions = [double];
+            let h: fn(fn(int) -> int, int) -> int = twice;
+            h(functions[0], 1) > 2;
+        ");
+        test_typecheck("fn make() -> fn() -> bool { fn yes() -> bool { return true; } return yes; } make()();");
+        test_typecheck("
+            fn apply(f: fn(int) -> int, x: int) -> int { return f(x); }
+            let square = fn(x: int) -> int { x * x; };
+            apply(square, 2) + apply(fn(x: int) -> int => x - 1, 3);
+            let check: fn(int) -> bool = fn(x: int) -> bool => x > 0;
+            let double = fn(x: int) -> int => x * 2;
+            let y: int = double(2) + apply(fn(x: int) -> int => x * 2, 3);
+        ");
+
+        for (code, message) in [
+            ("let x = 1; x(2);", "Expected a function, instead got int"),
+            ("fn f(x: int) -> int { return x; } let g: fn(bool) -> int = f;", "Expected fn(bool) -> int, instead got fn(int) -> int"),
+            ("fn f(x: int) -> int { return x; } let g = f; g(true);", "Expected Integer, instead got Boolean"),
+            ("fn f() {} fn g() {} f = g;", "Cannot assign to the function 'f'"),
+            ("let f = fn(x: int) -> bool => x + 1;", "Expected Boolean, instead got Integer"),
+            (
+                "fn outer(y: int) -> int { let f = fn(x: int) -> int { x + y; }; f(1); } outer(2);",
+                "Cannot use 'y' of an enclosing function, functions don't capture it",
+            ),
+            (
+                "fn outer() -> int { let y = 1; fn set() { y = 2; } set(); y; }",
+                "Cannot use 'y' of an enclosing function, functions don't capture it",
+            ),
+            (
+                "fn outer() -> int { fn a() -> int { return 1; } fn b() -> int { return a(); } return b(); }",
+                "Cannot use 'a' of an enclosing function, functions don't capture it",
+            ),
+        ] {
+            assert_eq!(type_error(code).message, message, "{code}");
+        }
+    }
+
+    // Structs
+
+    #[test]
+    fn test_typecheck_structs() {
+        test_typecheck("
+            let origin = Point { x: 0, y: 0 };
+            struct Point { x: int, y: int }
+            struct Line { from: Point, to: Point }
+            fn length(line: Line) -> int { return line.to.x - line.from.x; }
+            let line = Line { to: Point { x: 3, y: 4 }, from: origin };
+            line.from.y = length(line);
+            let points: [Point] = [line.from, line.to];
+            points[0].x > 1;
+        ");
+        test_typecheck("fn f() -> bool { struct Flag { on: bool } let flag = Flag { on: true }; return flag.on; }");
+
+        for (code, message) in [
+            ("let p = Point { x: 1 };", "Unknown struct 'Point'"),
+            ("let p: Point;", "Unknown type 'Point'"),
+            ("struct P { x: int, x: bool }", "Field 'x' is declared twice in struct 'P'"),
+            ("struct P { q: Q }", "Unknown type 'Q'"),
+            ("struct P { x: int } let p = P { x: true };", "Expected Integer, instead got Boolean"),
+            ("struct P { x: int } let p = P { x: 1, z: 2 };", "Struct 'P' has no field 'z'"),
+            ("struct P { x: int } let p = P { x: 1, x: 2 };", "Field 'x' is initialized twice"),
+            ("struct P { x: int, y: int } let p = P { x: 1 };", "Missing field 'y' in 'P' literal"),
+            ("struct P { x: int } let p = P { x: 1 }; p.y;", "Struct 'P' has no field 'y'"),
+            ("let x = 1; x.y;", "Expected a struct, instead got int"),
+            ("struct P { x: int } let p = P { x: 1 }; let b: bool = p.x;", "Expected bool, instead got int"),
+            ("struct P { x: int } let p = P { x: 1 }; p.x = true;", "Cannot assign bool to the field 'x' of type int"),
+            ("struct P { x: int } fn f() -> P { return P { x: 1 }; } f().x = 2;", "Cannot assign to a field of a temporary value, only of a variable"),
+            ("struct P { x: int } struct Q { p: P } let q: Q = P { x: 1 };", "Expected Q, instead got P"),
+        ] {
+            assert_eq!(type_error(code).message, message, "{code}");
+        }
+    }
+}
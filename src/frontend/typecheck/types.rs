@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Type {
@@ -9,6 +9,26 @@ pub enum Type {
     String,
     Array(Box<Type>),
     Function(Vec<Type>, Box<Type>),
+    Struct(String), // Structs are told apart by name
 }
 
-pub type TypeEnvironment = HashMap<String, Type>;
\ No newline at end of file
+pub type TypeEnvironment = HashMap<String, Type>;
+
+// Types as they are written in annotations
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::None => write!(f, "none"),
+            Type::Void => write!(f, "void"),
+            Type::Integer => write!(f, "int"),
+            Type::Boolean => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Array(element) => write!(f, "[{element}]"),
+            Type::Function(parameters, return_type) => {
+                let parameters: Vec<String> = parameters.iter().map(Type::to_string).collect();
+                write!(f, "fn({}) -> {return_type}", parameters.join(", "))
+            },
+            Type::Struct(name) => write!(f, "{name}"),
+        }
+    }
+}
@@ -1,2 +1,4 @@
 pub mod frontend;
-pub mod backend;
\ No newline at end of file
+pub mod backend;
+pub mod engine;
+pub mod cli;
\ No newline at end of file